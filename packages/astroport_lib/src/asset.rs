@@ -18,7 +18,6 @@ use crate::pair::QueryMsg as PairQueryMsg;
 use crate::querier::{
     query_balance, query_token_balance, query_token_precision, query_token_symbol,
 };
-use crate::token::is_valid_symbol;
 
 /// UST token denomination
 pub const UUSD_DENOM: &str = "uusd";
@@ -27,7 +26,13 @@ pub const ULUNA_DENOM: &str = "uluna";
 /// Minimum initial LP share
 pub const MINIMUM_LIQUIDITY_AMOUNT: Uint128 = Uint128::new(1_000);
 /// Maximum denom length
-pub const DENOM_MAX_LENGTH: usize = 60;
+pub const DENOM_MAX_LENGTH: usize = 128;
+/// The prefix of IBC voucher denoms
+pub const IBC_DENOM_PREFIX: &str = "ibc/";
+/// The prefix of tokenfactory denoms
+pub const TOKEN_FACTORY_DENOM_PREFIX: &str = "factory/";
+/// Maximum tokenfactory subdenom length
+pub const SUBDENOM_MAX_LENGTH: usize = 44;
 
 /// This enum describes a Terra asset (native or CW20).
 #[cw_serde]
@@ -250,7 +255,17 @@ impl AssetInfo {
     /// Checks whether the native coin is IBCed token or not.
     pub fn is_ibc(&self) -> bool {
         match self {
-            AssetInfo::NativeToken { denom } => denom.to_lowercase().starts_with("ibc/"),
+            AssetInfo::NativeToken { denom } => denom
+                .strip_prefix(IBC_DENOM_PREFIX)
+                .map_or(false, is_ibc_hash),
+            AssetInfo::Token { .. } => false,
+        }
+    }
+
+    /// Checks whether the native coin is a tokenfactory token or not.
+    pub fn is_token_factory(&self) -> bool {
+        match self {
+            AssetInfo::NativeToken { denom } => denom.starts_with(TOKEN_FACTORY_DENOM_PREFIX),
             AssetInfo::Token { .. } => false,
         }
     }
@@ -315,13 +330,7 @@ impl AssetInfo {
             AssetInfo::Token { contract_addr } => {
                 api.addr_validate(contract_addr.as_str())?;
             }
-            AssetInfo::NativeToken { denom } => {
-                if !is_valid_symbol(denom, Some(DENOM_MAX_LENGTH)) {
-                    return Err(StdError::generic_err(format!(
-                        "Native denom is not in expected format [a-zA-Z\\-][3,{DENOM_MAX_LENGTH}]: {denom}",
-                    )));
-                }
-            }
+            AssetInfo::NativeToken { denom } => validate_native_denom(api, denom)?,
         }
 
         Ok(())
@@ -583,10 +592,60 @@ impl Decimal256Ext for Decimal256 {
     }
 }
 
+/// Checks a native denom against the Cosmos SDK denom rules. `ibc/{hash}` denoms must carry a
+/// hex encoded SHA-256 hash and `factory/{creator}/{subdenom}` tokenfactory denoms a valid
+/// creator address.
+pub fn validate_native_denom(api: &dyn Api, denom: &str) -> StdResult<()> {
+    if denom.len() < 3 || denom.len() > DENOM_MAX_LENGTH {
+        return Err(StdError::generic_err(format!(
+            "Native denom length must be between 3 and {DENOM_MAX_LENGTH}: {denom}"
+        )));
+    }
+
+    if let Some(hash) = denom.strip_prefix(IBC_DENOM_PREFIX) {
+        if !is_ibc_hash(hash) {
+            return Err(StdError::generic_err(format!(
+                "IBC denom is not in expected format ibc/{{64 hex characters}}: {denom}"
+            )));
+        }
+    } else if let Some(suffix) = denom.strip_prefix(TOKEN_FACTORY_DENOM_PREFIX) {
+        let (creator, subdenom) = suffix.split_once('/').ok_or_else(|| {
+            StdError::generic_err(format!(
+                "Tokenfactory denom is not in expected format factory/{{creator}}/{{subdenom}}: {denom}"
+            ))
+        })?;
+        api.addr_validate(creator)?;
+        if subdenom.len() > SUBDENOM_MAX_LENGTH || !subdenom.bytes().all(is_denom_char) {
+            return Err(StdError::generic_err(format!(
+                "Tokenfactory subdenom is not in expected format [a-zA-Z0-9/:._-][0,{SUBDENOM_MAX_LENGTH}]: {denom}"
+            )));
+        }
+    } else {
+        let mut bytes = denom.bytes();
+        let starts_with_letter = bytes.next().map_or(false, |byte| byte.is_ascii_alphabetic());
+        if !starts_with_letter || !bytes.all(is_denom_char) {
+            return Err(StdError::generic_err(format!(
+                "Native denom is not in expected format [a-zA-Z][a-zA-Z0-9/:._-][2,{}]: {denom}",
+                DENOM_MAX_LENGTH - 1
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+fn is_ibc_hash(hash: &str) -> bool {
+    hash.len() == 64 && hash.bytes().all(|byte| byte.is_ascii_hexdigit())
+}
+
+fn is_denom_char(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || matches!(byte, b'/' | b':' | b'.' | b'_' | b'-')
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use cosmwasm_std::testing::mock_info;
+    use cosmwasm_std::testing::{mock_info, MockApi};
     use cosmwasm_std::{coin, coins};
 
     #[test]
@@ -683,4 +742,38 @@ mod tests {
             )
         );
     }
+
+    #[test]
+    fn test_native_denom_check() {
+        let api = MockApi::default();
+        let ibc_denom =
+            "ibc/27394FB092D2ECCD56123C74F36E4C1F926001CEADA9CA97EA622B25F41E5EB2".to_string();
+        let factory_denom = "factory/creator/ustake".to_string();
+
+        for denom in ["usei", "uatom", &ibc_denom, &factory_denom, "factory/creator/a/b.c"] {
+            native_asset_info(denom.to_string()).check(&api).unwrap();
+        }
+        assert!(native_asset_info(ibc_denom.clone()).is_ibc());
+        assert!(!native_asset_info(factory_denom.clone()).is_ibc());
+        assert!(native_asset_info(factory_denom).is_token_factory());
+        assert!(!native_asset_info(ibc_denom).is_token_factory());
+
+        for denom in [
+            "ab",
+            "1usei",
+            "usei!",
+            "ibc/27394FB092D2ECCD",
+            "ibc/27394FB092D2ECCD56123C74F36E4C1F926001CEADA9CA97EA622B25F41E5EZZ",
+            "factory/creator",
+            "factory/Creator/ustake",
+            "factory/creator/this-subdenom-is-way-too-long-for-the-tokenfactory",
+        ] {
+            native_asset_info(denom.to_string())
+                .check(&api)
+                .unwrap_err();
+        }
+        native_asset_info(format!("u{}", "a".repeat(DENOM_MAX_LENGTH)))
+            .check(&api)
+            .unwrap_err();
+    }
 }
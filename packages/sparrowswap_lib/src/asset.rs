@@ -12,6 +12,10 @@ use cosmwasm_std::{
 use cw20::{Cw20ExecuteMsg, Cw20QueryMsg, MinterResponse};
 use cw_utils::must_pay;
 
+use astroport_lib::asset::{
+    validate_native_denom, AssetInfo as AstroportAssetInfo, TOKEN_FACTORY_DENOM_PREFIX,
+};
+
 pub const UUSD_DENOM: &str = "uusd";
 /// LUNA token denomination
 pub const ULUNA_DENOM: &str = "uluna";
@@ -220,7 +224,15 @@ impl AssetInfo {
     /// Checks whether the native coin is IBCed token or not.
     pub fn is_ibc(&self) -> bool {
         match self {
-            AssetInfo::NativeToken { denom } => denom.to_lowercase().starts_with("ibc/"),
+            AssetInfo::NativeToken { .. } => AstroportAssetInfo::from(self).is_ibc(),
+            AssetInfo::Token { .. } => false,
+        }
+    }
+
+    /// Checks whether the native coin is a tokenfactory token or not.
+    pub fn is_token_factory(&self) -> bool {
+        match self {
+            AssetInfo::NativeToken { denom } => denom.starts_with(TOKEN_FACTORY_DENOM_PREFIX),
             AssetInfo::Token { .. } => false,
         }
     }
@@ -284,8 +296,11 @@ impl AssetInfo {
     ///
     /// * **api** is a object of type [`Api`]
     pub fn check(&self, api: &dyn Api) -> StdResult<()> {
-        if let AssetInfo::Token { contract_addr } = self {
-            api.addr_validate(contract_addr.as_str())?;
+        match self {
+            AssetInfo::Token { contract_addr } => {
+                api.addr_validate(contract_addr.as_str())?;
+            }
+            AssetInfo::NativeToken { denom } => validate_native_denom(api, denom)?,
         }
 
         Ok(())
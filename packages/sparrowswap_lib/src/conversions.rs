@@ -0,0 +1,187 @@
+//! Conversions between the SparrowSwap types and their Astroport equivalents.
+//!
+//! Both DEXes share the asset model, so every conversion is lossless. The only fallible one is
+//! [`astroport_lib::asset::PairInfo`] to [`PairInfo`], because an Astroport pair may hold more
+//! than two assets.
+
+use std::convert::TryFrom;
+
+use astroport_lib::asset::{
+    Asset as AstroportAsset, AssetInfo as AstroportAssetInfo, PairInfo as AstroportPairInfo,
+};
+use astroport_lib::factory::PairType as AstroportPairType;
+use cosmwasm_std::StdError;
+
+use crate::asset::{Asset, AssetInfo, PairInfo};
+use crate::factory::PairType;
+
+impl From<AssetInfo> for AstroportAssetInfo {
+    fn from(asset_info: AssetInfo) -> Self {
+        match asset_info {
+            AssetInfo::Token { contract_addr } => AstroportAssetInfo::Token { contract_addr },
+            AssetInfo::NativeToken { denom } => AstroportAssetInfo::NativeToken { denom },
+        }
+    }
+}
+
+impl From<&AssetInfo> for AstroportAssetInfo {
+    fn from(asset_info: &AssetInfo) -> Self {
+        asset_info.clone().into()
+    }
+}
+
+impl From<AstroportAssetInfo> for AssetInfo {
+    fn from(asset_info: AstroportAssetInfo) -> Self {
+        match asset_info {
+            AstroportAssetInfo::Token { contract_addr } => AssetInfo::Token { contract_addr },
+            AstroportAssetInfo::NativeToken { denom } => AssetInfo::NativeToken { denom },
+        }
+    }
+}
+
+impl From<&AstroportAssetInfo> for AssetInfo {
+    fn from(asset_info: &AstroportAssetInfo) -> Self {
+        asset_info.clone().into()
+    }
+}
+
+impl From<Asset> for AstroportAsset {
+    fn from(asset: Asset) -> Self {
+        AstroportAsset {
+            info: asset.info.into(),
+            amount: asset.amount,
+        }
+    }
+}
+
+impl From<AstroportAsset> for Asset {
+    fn from(asset: AstroportAsset) -> Self {
+        Asset {
+            info: asset.info.into(),
+            amount: asset.amount,
+        }
+    }
+}
+
+impl From<PairType> for AstroportPairType {
+    fn from(pair_type: PairType) -> Self {
+        match pair_type {
+            PairType::Xyk {} => AstroportPairType::Xyk {},
+            PairType::Stable {} => AstroportPairType::Stable {},
+            PairType::Custom(name) => AstroportPairType::Custom(name),
+        }
+    }
+}
+
+impl From<AstroportPairType> for PairType {
+    fn from(pair_type: AstroportPairType) -> Self {
+        match pair_type {
+            AstroportPairType::Xyk {} => PairType::Xyk {},
+            AstroportPairType::Stable {} => PairType::Stable {},
+            AstroportPairType::Custom(name) => PairType::Custom(name),
+        }
+    }
+}
+
+impl From<PairInfo> for AstroportPairInfo {
+    fn from(pair_info: PairInfo) -> Self {
+        AstroportPairInfo {
+            asset_infos: pair_info.asset_infos.into_iter().map(Into::into).collect(),
+            contract_addr: pair_info.contract_addr,
+            liquidity_token: pair_info.liquidity_token,
+            pair_type: pair_info.pair_type.into(),
+        }
+    }
+}
+
+impl TryFrom<AstroportPairInfo> for PairInfo {
+    type Error = StdError;
+
+    fn try_from(pair_info: AstroportPairInfo) -> Result<Self, Self::Error> {
+        let asset_infos: [AssetInfo; 2] = <[AstroportAssetInfo; 2]>::try_from(pair_info.asset_infos)
+            .map_err(|asset_infos| {
+                StdError::generic_err(format!(
+                    "A SparrowSwap pair holds two assets, got {}",
+                    asset_infos.len()
+                ))
+            })?
+            .map(Into::into);
+
+        Ok(PairInfo {
+            asset_infos,
+            contract_addr: pair_info.contract_addr,
+            liquidity_token: pair_info.liquidity_token,
+            pair_type: pair_info.pair_type.into(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asset::{native_asset_info, token_asset_info};
+    use cosmwasm_std::{Addr, Uint128};
+
+    #[test]
+    fn test_asset_conversions() {
+        let asset_infos = [
+            native_asset_info("usei".to_string()),
+            token_asset_info(Addr::unchecked("token0000")),
+        ];
+        for asset_info in asset_infos {
+            let astroport_asset_info = AstroportAssetInfo::from(&asset_info);
+            assert_eq!(astroport_asset_info.to_string(), asset_info.to_string());
+            assert_eq!(AssetInfo::from(astroport_asset_info), asset_info);
+
+            let asset = Asset {
+                info: asset_info,
+                amount: Uint128::new(123),
+            };
+            assert_eq!(Asset::from(AstroportAsset::from(asset.clone())), asset);
+        }
+    }
+
+    #[test]
+    fn test_pair_info_conversions() {
+        for pair_type in [
+            PairType::Xyk {},
+            PairType::Stable {},
+            PairType::Custom("concentrated".to_string()),
+        ] {
+            let pair_info = PairInfo {
+                asset_infos: [
+                    native_asset_info("usei".to_string()),
+                    token_asset_info(Addr::unchecked("token0000")),
+                ],
+                contract_addr: Addr::unchecked("pair0000"),
+                liquidity_token: Addr::unchecked("lp0000"),
+                pair_type,
+            };
+            let astroport_pair_info = AstroportPairInfo::from(pair_info.clone());
+            assert_eq!(
+                astroport_pair_info.pair_type.to_string(),
+                pair_info.pair_type.to_string()
+            );
+            assert_eq!(PairInfo::try_from(astroport_pair_info).unwrap(), pair_info);
+        }
+
+        let mut astroport_pair_info = AstroportPairInfo::from(PairInfo {
+            asset_infos: [
+                native_asset_info("usei".to_string()),
+                native_asset_info("uatom".to_string()),
+            ],
+            contract_addr: Addr::unchecked("pair0000"),
+            liquidity_token: Addr::unchecked("lp0000"),
+            pair_type: PairType::Stable {},
+        });
+        astroport_pair_info
+            .asset_infos
+            .push(AstroportAssetInfo::NativeToken {
+                denom: "uosmo".to_string(),
+            });
+        assert_eq!(
+            PairInfo::try_from(astroport_pair_info).unwrap_err(),
+            StdError::generic_err("A SparrowSwap pair holds two assets, got 3")
+        );
+    }
+}
@@ -1,5 +1,6 @@
 pub mod asset;
 pub mod common;
+pub mod conversions;
 pub mod factory;
 pub mod generator;
 pub mod generator_proxy;
@@ -0,0 +1,447 @@
+use cosmwasm_std::{
+    to_binary, Binary, Coin, CosmosMsg, Decimal, QuerierWrapper, StdError, StdResult, Uint128,
+    WasmMsg,
+};
+use cw20::Cw20ExecuteMsg;
+
+// Sparrow Swap
+use sparrowswap_lib::asset::{
+    Asset as SparrowSwapAsset, AssetInfo as SparrowSwapAssetInfo, PairInfo as SparrowSwapPairInfo,
+};
+use sparrowswap_lib::pair::{
+    CumulativePricesResponse as SparrowSwapCumulativePricesResponse,
+    Cw20HookMsg as SparrowSwapCw20HookMsg, ExecuteMsg as SparrowSwapMsg,
+    PoolResponse as SparrowSwapPoolResponse, QueryMsg as SparrowSwapQueryMsg,
+    ReverseSimulationResponse as SparrowSwapReverseSimulationResponse,
+    SimulationResponse as SparrowSwapSimulationResponse,
+};
+
+// Astroport Swap
+use astroport_lib::asset::{Asset, AssetInfo, PairInfo};
+use astroport_lib::pair::{
+    CumulativePricesResponse, Cw20HookMsg as AstroportCw20HookMsg, ExecuteMsg as AstroportMsg,
+    PoolResponse, QueryMsg as AstroportQueryMsg, ReverseSimulationResponse, SimulationResponse,
+};
+
+/// The adapters of the supported DEXes keyed by DEX id. A venue forked from one of them only
+/// needs an entry that reuses its adapter.
+const DEX_ADAPTERS: &[(&str, &dyn DexAdapter)] = &[
+    (SPARROWSWAP, &SparrowSwapAdapter),
+    (ASTROPORT, &AstroportAdapter),
+];
+
+/// The ids of the DEXes with dedicated swap operations
+pub const SPARROWSWAP: &str = "sparrowswap";
+pub const ASTROPORT: &str = "astroport";
+
+/// Returns the adapter registered under a DEX id.
+pub fn dex_adapter(dex: &str) -> Option<&'static dyn DexAdapter> {
+    DEX_ADAPTERS
+        .iter()
+        .find(|(id, _)| *id == dex)
+        .map(|(_, adapter)| *adapter)
+}
+
+/// This trait describes how the router talks to the pairs of a DEX. Assets are always given in
+/// the Astroport model and converted by the adapter.
+pub trait DexAdapter {
+    /// Builds the message that swaps `offer_asset` held by the router in `pool`.
+    fn swap_msg(
+        &self,
+        pool: &str,
+        offer_asset: &Asset,
+        ask_asset_info: Option<AssetInfo>,
+        belief_price: Option<Decimal>,
+        max_spread: Option<Decimal>,
+        to: Option<String>,
+    ) -> StdResult<CosmosMsg>;
+
+    fn simulate(
+        &self,
+        querier: &QuerierWrapper,
+        pool: &str,
+        offer_asset: &Asset,
+        ask_asset_info: Option<AssetInfo>,
+    ) -> StdResult<SimulationResponse>;
+
+    fn reverse_simulate(
+        &self,
+        querier: &QuerierWrapper,
+        pool: &str,
+        offer_asset_info: Option<AssetInfo>,
+        ask_asset: &Asset,
+    ) -> StdResult<ReverseSimulationResponse>;
+
+    fn pair_info(&self, querier: &QuerierWrapper, pool: &str) -> StdResult<PairInfo>;
+
+    /// Returns the assets of a two-asset pool with their reserves, in pool order.
+    fn pool(&self, querier: &QuerierWrapper, pool: &str) -> StdResult<Vec<Asset>>;
+
+    /// Returns the cumulative price of `asset_info` in the other asset of a two-asset pool, if
+    /// it is a pool asset.
+    fn cumulative_price(
+        &self,
+        querier: &QuerierWrapper,
+        pool: &str,
+        asset_info: &AssetInfo,
+    ) -> StdResult<Option<Uint128>>;
+
+    /// Builds the message that provides `assets` held by the router to `pool`. CW20 assets must
+    /// be approved to the pool beforehand.
+    fn provide_liquidity_msg(
+        &self,
+        pool: &str,
+        assets: Vec<Asset>,
+        slippage_tolerance: Option<Decimal>,
+        auto_stake: Option<bool>,
+        receiver: Option<String>,
+    ) -> StdResult<CosmosMsg>;
+
+    /// Builds the message that withdraws `amount` of the `lp_token` of `pool` held by the router.
+    fn withdraw_liquidity_msg(
+        &self,
+        pool: &str,
+        lp_token: &str,
+        amount: Uint128,
+    ) -> StdResult<CosmosMsg>;
+
+    /// Returns the asset of a two-asset pair that `offer_asset_info` swaps into.
+    fn ask_asset_info(
+        &self,
+        querier: &QuerierWrapper,
+        pool: &str,
+        offer_asset_info: &AssetInfo,
+    ) -> StdResult<AssetInfo> {
+        self.pair_info(querier, pool)?
+            .asset_infos
+            .into_iter()
+            .find(|asset_info| !asset_info.equal(offer_asset_info))
+            .ok_or_else(|| StdError::generic_err("Offer asset is not in the pool"))
+    }
+}
+
+pub struct SparrowSwapAdapter;
+
+impl DexAdapter for SparrowSwapAdapter {
+    fn swap_msg(
+        &self,
+        pool: &str,
+        offer_asset: &Asset,
+        _ask_asset_info: Option<AssetInfo>,
+        belief_price: Option<Decimal>,
+        max_spread: Option<Decimal>,
+        to: Option<String>,
+    ) -> StdResult<CosmosMsg> {
+        swap_msg(
+            pool,
+            offer_asset,
+            to_binary(&SparrowSwapCw20HookMsg::Swap {
+                belief_price,
+                max_spread,
+                to: to.clone(),
+            })?,
+            to_binary(&SparrowSwapMsg::Swap {
+                offer_asset: offer_asset.clone().into(),
+                belief_price,
+                max_spread,
+                to,
+            })?,
+        )
+    }
+
+    fn simulate(
+        &self,
+        querier: &QuerierWrapper,
+        pool: &str,
+        offer_asset: &Asset,
+        _ask_asset_info: Option<AssetInfo>,
+    ) -> StdResult<SimulationResponse> {
+        let res: SparrowSwapSimulationResponse = querier.query_wasm_smart(
+            pool,
+            &SparrowSwapQueryMsg::Simulation {
+                offer_asset: offer_asset.clone().into(),
+            },
+        )?;
+        Ok(SimulationResponse {
+            return_amount: res.return_amount,
+            spread_amount: res.spread_amount,
+            commission_amount: res.commission_amount,
+        })
+    }
+
+    fn reverse_simulate(
+        &self,
+        querier: &QuerierWrapper,
+        pool: &str,
+        _offer_asset_info: Option<AssetInfo>,
+        ask_asset: &Asset,
+    ) -> StdResult<ReverseSimulationResponse> {
+        let res: SparrowSwapReverseSimulationResponse = querier.query_wasm_smart(
+            pool,
+            &SparrowSwapQueryMsg::ReverseSimulation {
+                ask_asset: ask_asset.clone().into(),
+            },
+        )?;
+        Ok(ReverseSimulationResponse {
+            offer_amount: res.offer_amount,
+            spread_amount: res.spread_amount,
+            commission_amount: res.commission_amount,
+        })
+    }
+
+    fn pair_info(&self, querier: &QuerierWrapper, pool: &str) -> StdResult<PairInfo> {
+        let pair_info: SparrowSwapPairInfo =
+            querier.query_wasm_smart(pool, &SparrowSwapQueryMsg::Pair {})?;
+        Ok(pair_info.into())
+    }
+
+    fn pool(&self, querier: &QuerierWrapper, pool: &str) -> StdResult<Vec<Asset>> {
+        let res: SparrowSwapPoolResponse =
+            querier.query_wasm_smart(pool, &SparrowSwapQueryMsg::Pool {})?;
+        Ok(res.assets.into_iter().map(Asset::from).collect())
+    }
+
+    fn cumulative_price(
+        &self,
+        querier: &QuerierWrapper,
+        pool: &str,
+        asset_info: &AssetInfo,
+    ) -> StdResult<Option<Uint128>> {
+        let res: SparrowSwapCumulativePricesResponse =
+            querier.query_wasm_smart(pool, &SparrowSwapQueryMsg::CumulativePrices {})?;
+        let asset_info = SparrowSwapAssetInfo::from(asset_info);
+        Ok(if res.assets[0].info.equal(&asset_info) {
+            Some(res.price0_cumulative_last)
+        } else if res.assets[1].info.equal(&asset_info) {
+            Some(res.price1_cumulative_last)
+        } else {
+            None
+        })
+    }
+
+    fn provide_liquidity_msg(
+        &self,
+        pool: &str,
+        assets: Vec<Asset>,
+        slippage_tolerance: Option<Decimal>,
+        auto_stake: Option<bool>,
+        receiver: Option<String>,
+    ) -> StdResult<CosmosMsg> {
+        let pool_assets: [SparrowSwapAsset; 2] = assets
+            .iter()
+            .map(|asset| SparrowSwapAsset::from(asset.clone()))
+            .collect::<Vec<_>>()
+            .try_into()
+            .map_err(|_| StdError::generic_err("SparrowSwap pools take two assets"))?;
+        provide_liquidity_msg(
+            pool,
+            &assets,
+            to_binary(&SparrowSwapMsg::ProvideLiquidity {
+                assets: pool_assets,
+                slippage_tolerance,
+                auto_stake,
+                receiver,
+            })?,
+        )
+    }
+
+    fn withdraw_liquidity_msg(
+        &self,
+        pool: &str,
+        lp_token: &str,
+        amount: Uint128,
+    ) -> StdResult<CosmosMsg> {
+        withdraw_liquidity_msg(
+            pool,
+            lp_token,
+            amount,
+            to_binary(&SparrowSwapCw20HookMsg::WithdrawLiquidity {})?,
+        )
+    }
+}
+
+pub struct AstroportAdapter;
+
+impl DexAdapter for AstroportAdapter {
+    fn swap_msg(
+        &self,
+        pool: &str,
+        offer_asset: &Asset,
+        ask_asset_info: Option<AssetInfo>,
+        belief_price: Option<Decimal>,
+        max_spread: Option<Decimal>,
+        to: Option<String>,
+    ) -> StdResult<CosmosMsg> {
+        swap_msg(
+            pool,
+            offer_asset,
+            to_binary(&AstroportCw20HookMsg::Swap {
+                ask_asset_info: ask_asset_info.clone(),
+                belief_price,
+                max_spread,
+                to: to.clone(),
+            })?,
+            to_binary(&AstroportMsg::Swap {
+                offer_asset: offer_asset.clone(),
+                ask_asset_info,
+                belief_price,
+                max_spread,
+                to,
+            })?,
+        )
+    }
+
+    fn simulate(
+        &self,
+        querier: &QuerierWrapper,
+        pool: &str,
+        offer_asset: &Asset,
+        ask_asset_info: Option<AssetInfo>,
+    ) -> StdResult<SimulationResponse> {
+        querier.query_wasm_smart(
+            pool,
+            &AstroportQueryMsg::Simulation {
+                offer_asset: offer_asset.clone(),
+                ask_asset_info,
+            },
+        )
+    }
+
+    fn reverse_simulate(
+        &self,
+        querier: &QuerierWrapper,
+        pool: &str,
+        offer_asset_info: Option<AssetInfo>,
+        ask_asset: &Asset,
+    ) -> StdResult<ReverseSimulationResponse> {
+        querier.query_wasm_smart(
+            pool,
+            &AstroportQueryMsg::ReverseSimulation {
+                offer_asset_info,
+                ask_asset: ask_asset.clone(),
+            },
+        )
+    }
+
+    fn pair_info(&self, querier: &QuerierWrapper, pool: &str) -> StdResult<PairInfo> {
+        querier.query_wasm_smart(pool, &AstroportQueryMsg::Pair {})
+    }
+
+    fn pool(&self, querier: &QuerierWrapper, pool: &str) -> StdResult<Vec<Asset>> {
+        let res: PoolResponse = querier.query_wasm_smart(pool, &AstroportQueryMsg::Pool {})?;
+        if res.assets.len() != 2 {
+            return Err(StdError::generic_err("Pool must contain two assets"));
+        }
+        Ok(res.assets)
+    }
+
+    fn cumulative_price(
+        &self,
+        querier: &QuerierWrapper,
+        pool: &str,
+        asset_info: &AssetInfo,
+    ) -> StdResult<Option<Uint128>> {
+        let res: CumulativePricesResponse =
+            querier.query_wasm_smart(pool, &AstroportQueryMsg::CumulativePrices {})?;
+        if res.assets.len() != 2 {
+            return Err(StdError::generic_err("Pool must contain two assets"));
+        }
+        Ok(res
+            .cumulative_prices
+            .into_iter()
+            .find(|(from, _, _)| from.equal(asset_info))
+            .map(|(_, _, cumulative_price)| cumulative_price))
+    }
+
+    fn provide_liquidity_msg(
+        &self,
+        pool: &str,
+        assets: Vec<Asset>,
+        slippage_tolerance: Option<Decimal>,
+        auto_stake: Option<bool>,
+        receiver: Option<String>,
+    ) -> StdResult<CosmosMsg> {
+        let msg = to_binary(&AstroportMsg::ProvideLiquidity {
+            assets: assets.clone(),
+            slippage_tolerance,
+            auto_stake,
+            receiver,
+        })?;
+        provide_liquidity_msg(pool, &assets, msg)
+    }
+
+    fn withdraw_liquidity_msg(
+        &self,
+        pool: &str,
+        lp_token: &str,
+        amount: Uint128,
+    ) -> StdResult<CosmosMsg> {
+        withdraw_liquidity_msg(
+            pool,
+            lp_token,
+            amount,
+            to_binary(&AstroportCw20HookMsg::WithdrawLiquidity { assets: vec![] })?,
+        )
+    }
+}
+
+/// Sends a CW20 offer to the pair with its swap hook and a native offer with the swap message.
+fn swap_msg(
+    pool: &str,
+    offer_asset: &Asset,
+    hook_msg: Binary,
+    msg: Binary,
+) -> StdResult<CosmosMsg> {
+    Ok(match &offer_asset.info {
+        AssetInfo::Token { contract_addr } => CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: contract_addr.to_string(),
+            funds: vec![],
+            msg: to_binary(&Cw20ExecuteMsg::Send {
+                contract: pool.to_string(),
+                amount: offer_asset.amount,
+                msg: hook_msg,
+            })?,
+        }),
+        AssetInfo::NativeToken { denom } => CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: pool.to_string(),
+            funds: vec![Coin {
+                denom: denom.clone(),
+                amount: offer_asset.amount,
+            }],
+            msg,
+        }),
+    })
+}
+
+/// Sends the provide liquidity message to the pair with the native assets attached.
+fn provide_liquidity_msg(pool: &str, assets: &[Asset], msg: Binary) -> StdResult<CosmosMsg> {
+    let mut funds = assets
+        .iter()
+        .filter(|asset| asset.is_native_token() && !asset.amount.is_zero())
+        .map(|asset| asset.as_coin())
+        .collect::<StdResult<Vec<Coin>>>()?;
+    funds.sort_by(|a, b| a.denom.cmp(&b.denom));
+    Ok(CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: pool.to_string(),
+        funds,
+        msg,
+    }))
+}
+
+/// Sends the LP tokens to the pair with its withdraw hook.
+fn withdraw_liquidity_msg(
+    pool: &str,
+    lp_token: &str,
+    amount: Uint128,
+    hook_msg: Binary,
+) -> StdResult<CosmosMsg> {
+    Ok(CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: lp_token.to_string(),
+        funds: vec![],
+        msg: to_binary(&Cw20ExecuteMsg::Send {
+            contract: pool.to_string(),
+            amount,
+            msg: hook_msg,
+        })?,
+    }))
+}
@@ -1,30 +1,127 @@
 #[cfg(not(feature = "library"))]
-use std::str;
+use std::{slice, str};
 
 use cosmwasm_std::{
-    entry_point, to_binary, Addr, CosmosMsg, Decimal, Deps, DepsMut, Env,
-    MessageInfo, Response, StdResult, Uint128, WasmMsg, Coin
+    coins, entry_point, from_binary, to_binary, Addr, Binary, CosmosMsg, Decimal, Deps, DepsMut,
+    Env, Event, MessageInfo, Reply, Response, StdError, StdResult, SubMsg, SubMsgResult, Uint128,
+    Uint256, WasmMsg, Coin, Empty, Order, Storage,
 };
 
-use cw2::set_contract_version;
+use cw2::{get_contract_version, set_contract_version};
+use cw20::{Cw20ExecuteMsg, Cw20QueryMsg, Cw20ReceiveMsg, MinterResponse};
+use cw_storage_plus::Bound;
+use cw_utils::{must_pay, Expiration};
+use ripemd::Ripemd160;
+use sha2::{Digest, Sha256};
 
+use crate::adapter::{dex_adapter, DexAdapter, ASTROPORT, SPARROWSWAP};
 use crate::error::ContractError;
-use crate::msg::{ExecuteMsg, SwapOperation};
-use crate::state::{State, STATE};
-
-// sparrowswap
-use sparrowswap_lib::pair::{ExecuteMsg as SparrowSwapeMsg};
-use sparrowswap_lib::asset::{Asset as SparrowSwapAsset, AssetInfo as SparrowSwapAssetInfo};
+use crate::ibc::{
+    decode_transfer_sequence, ibc_callback_memo, MsgTransfer, MSG_TRANSFER_TYPE_URL,
+};
+use crate::msg::{
+    AuctionBatchResponse, AuctionOrderResponse, CallbackMsg, CommitmentResponse,
+    CompoundConfigResponse, CompoundPositionResponse, CompoundRoute, ConditionalOrderResponse,
+    ConditionalOrdersResponse, ConsolidateInput, Cw20HookMsg, DcaExecutionResponse,
+    DcaHistoryResponse, DcaResponse, DcaStatus, DistributeLeg, ExecuteMsg, GetCountResponse,
+    HashedTimelockResponse, IbcForward, IbcLifecycleComplete, IbcTransferResponse, InstantiateMsg,
+    MigrateMsg, OrderResponse, OrdersResponse, PairOracleResponse, PostAction, PriceCondition,
+    QueryMsg, RfqQuote, RfqQuotePayload, SignedSwapPayload, SwapIntent, SwapOperation, SwapResponse,
+    SwapReveal, SudoMsg, UnxswapParams,
+};
+use crate::state::{
+    pair_key, AuctionBatch, AuctionOrder, CompoundConfig, ConditionalOrder, DcaExecution,
+    DcaSchedule, HashedTimelock, IbcTransfer, LimitOrder, PairOracle, PendingBatchSwap,
+    PendingRecovery, PendingStake, PriceObservation, State, SwapCommitment, AUCTION_BATCHES,
+    AUCTION_ORDERS, COMPOUND_CONFIGS, COMPOUND_IDLE, COMPOUND_SHARES, COMPOUND_TOTAL_SHARES,
+    CONDITIONAL_ORDERS, DCA_EXECUTIONS, DCA_SCHEDULES, ESCROWED, GENERATOR, HASHED_TIMELOCKS,
+    IBC_TRANSFERS, IBC_TRANSFERS_BY_OWNER, KEEPER_TIP_BPS, LAST_CONDITIONAL_ORDER_ID, LAST_DCA_ID,
+    LAST_ORDER_ID, LAST_STAKE_ID, LIMIT_ORDERS, ORDERS_BY_OWNER, ORDERS_BY_PAIR, PAIR_ORACLES,
+    PENDING_BATCH_SWAPS, PENDING_CLAIM, PENDING_IBC_TRANSFER, PENDING_RECOVERY, PENDING_STAKES,
+    RECOVERIES, RFQ_FILLED, RFQ_MAKERS, SIGNER_NONCES, SWAP_COMMITMENTS, STATE,
+};
 
 // astroport
-use astroport_lib::pair::{ExecuteMsg as AstroportMsg};
-use astroport_lib::asset::{Asset as AstroportAsset, AssetInfo};
-use astroport_lib::querier::{query_balance, query_token_balance};
+use astroport_lib::pair::TWAP_PRECISION;
+use astroport_lib::asset::{check_swap_parameters, Asset as AstroportAsset, AssetInfo, CoinsExt};
+use astroport_lib::generator::{
+    Config as GeneratorConfig, Cw20HookMsg as GeneratorCw20HookMsg,
+    ExecuteMsg as GeneratorExecuteMsg, PendingTokenResponse, PoolInfoResponse,
+    QueryMsg as GeneratorQueryMsg,
+};
+use astroport_lib::oracle::QueryMsg as OracleQueryMsg;
+use astroport_lib::querier::query_token_balance;
 
 // version info for migration info
 const CONTRACT_NAME: &str = "crates.io:wasm-dexrouter";
 const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
 
+const BATCH_SWAP_REPLY_ID: u64 = 1;
+const SWAP_CALLBACK_REPLY_ID: u64 = 2;
+const RECOVERABLE_SWAP_REPLY_ID: u64 = 5;
+const IBC_TRANSFER_REPLY_ID: u64 = 6;
+const CLAIM_ROUTE_REPLY_ID: u64 = 7;
+
+/// `ProvideAndStake` replies add the id of their pending stake to these offsets
+const PROVIDE_LIQUIDITY_REPLY_ID: u64 = 1 << 32;
+const STAKE_REPLY_ID: u64 = 2 << 32;
+
+/// The sum of leg weights in a distribution
+const MAX_WEIGHT_BPS: u16 = 10_000;
+
+/// The maximum share of a limit order's return paid to its keeper
+const MAX_KEEPER_TIP_BPS: u16 = 500;
+
+/// Pagination limits of list queries
+const DEFAULT_LIMIT: u32 = 10;
+const MAX_LIMIT: u32 = 30;
+
+/// The number of blocks after a swap commitment during which it can be revealed
+const COMMIT_REVEAL_TIMEOUT_BLOCKS: u64 = 100;
+
+/// The number of blocks a batch auction collects orders for
+const AUCTION_PERIOD_BLOCKS: u64 = 10;
+
+/// The maximum number of orders in a batch auction
+const MAX_AUCTION_ORDERS: u64 = 50;
+
+/// The minimum amount of each pool asset a batch auction order must be worth at the pool mid price
+const MIN_AUCTION_ORDER_VALUE: u128 = 1_000;
+
+/// The spread limit the pool applies to the net swap of a batch auction, the highest the pairs
+/// accept. The order limits are checked against a simulation of the swap at settlement.
+const AUCTION_NET_SWAP_MAX_SPREAD: u64 = 50;
+
+/// Virtual compounding shares and LP tokens that make inflating the share price of a new vault
+/// cost the depositor more than it can take from later deposits
+const VIRTUAL_COMPOUND_SHARES: u128 = 1_000_000;
+const VIRTUAL_COMPOUND_LP: u128 = 1;
+
+/// The number of atomics in one unit of a `Decimal`
+const DECIMAL_FRACTIONAL: u128 = 1_000_000_000_000_000_000;
+
+/// The precision of pool fees used to compute zap amounts
+const FEE_PRECISION: u128 = 1_000_000;
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn instantiate(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    msg: InstantiateMsg,
+) -> Result<Response, ContractError> {
+    let state = State {
+        count: msg.count,
+        owner: info.sender.clone(),
+    };
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+    STATE.save(deps.storage, &state)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "instantiate")
+        .add_attribute("owner", info.sender))
+}
+
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn execute(
     deps: DepsMut,
@@ -36,24 +133,305 @@ pub fn execute(
         ExecuteMsg::SparrowSwap {
             pool_address,
             offer_asset_info,
+            offer_amount,
             belief_price,
             max_spread,
             to
-        } => execute::sparrowSwap( deps, exe_env, info, pool_address, offer_asset_info, belief_price, max_spread, to),
+        } => execute::sparrow_swap(
+            deps,
+            exe_env,
+            info,
+            execute::SwapParams {
+                pool_address,
+                offer_asset_info: offer_asset_info.into(),
+                offer_amount,
+                ask_asset_info: None,
+                belief_price,
+                max_spread,
+                to,
+            },
+        ),
         ExecuteMsg::AstroportSwap {
             pool_address,
             offer_asset_info,
+            offer_amount,
             ask_asset_info,
             belief_price,
             max_spread,
             to
-        } => execute::astroportSwap(deps, exe_env, info, pool_address, offer_asset_info, ask_asset_info, belief_price, max_spread, to),
-        ExecuteMsg::Unxswap{
+        } => execute::astroport_swap(
+            deps,
+            exe_env,
+            info,
+            execute::SwapParams {
+                pool_address,
+                offer_asset_info,
+                offer_amount,
+                ask_asset_info,
+                belief_price,
+                max_spread,
+                to,
+            },
+        ),
+        ExecuteMsg::DexSwap {
+            dex,
+            pool_address,
+            offer_asset_info,
+            offer_amount,
+            ask_asset_info,
+            belief_price,
+            max_spread,
+            to,
+        } => execute::dex_swap(
+            deps,
+            exe_env,
+            dex,
+            execute::SwapParams {
+                pool_address,
+                offer_asset_info,
+                offer_amount,
+                ask_asset_info,
+                belief_price,
+                max_spread,
+                to,
+            },
+        ),
+        ExecuteMsg::Unxswap {
+            steps,
+            minimum_receive,
+            to,
+            target_asset_info,
+            post_action,
+            callback,
+            recovery_addr,
+            ibc_forward,
+        } => execute::unxswap(
+            deps,
+            exe_env,
+            info,
+            execute::RouteParams {
+                steps,
+                minimum_receive,
+                to,
+                target_asset_info,
+                post_action,
+                callback,
+                ibc_forward,
+            },
+            recovery_addr,
+        ),
+        ExecuteMsg::ExecuteIbcForward {
+            asset_info,
+            prev_balance,
+            ibc_forward,
+            owner,
+            recovery_addr,
+        } => execute::execute_ibc_forward(
+            deps,
+            exe_env,
+            info,
+            execute::IbcForwardParams {
+                asset_info,
+                prev_balance,
+                ibc_forward,
+                owner,
+                recovery_addr,
+            },
+        ),
+        ExecuteMsg::ExecuteSwapCallback {
+            offer_asset,
+            target_asset_info,
+            prev_balance,
+            receiver,
+            sender,
+            callback,
+        } => execute::execute_swap_callback(
+            deps,
+            exe_env,
+            info,
+            execute::SwapCallbackParams {
+                offer_asset,
+                target_asset_info,
+                prev_balance,
+                receiver,
+                sender,
+                callback,
+            },
+        ),
+        ExecuteMsg::ExecutePostAction {
+            asset_info,
+            prev_balance,
+            post_action,
+        } => execute::execute_post_action(deps, exe_env, info, asset_info, prev_balance, post_action),
+        ExecuteMsg::UnxswapFrom {
+            owner,
+            token,
+            amount,
+            steps,
+            minimum_receive,
+            to,
+            target_asset_info,
+        } => execute::unxswap_from(
+            deps,
+            exe_env,
+            info,
+            execute::UnxswapFromParams {
+                owner,
+                token,
+                amount,
+                steps,
+                minimum_receive,
+                to,
+                target_asset_info,
+            },
+        ),
+        ExecuteMsg::Consolidate {
+            inputs,
+            target_asset_info,
+            minimum_receive,
+            to,
+        } => execute::consolidate(deps, exe_env, info, inputs, target_asset_info, minimum_receive, to),
+        ExecuteMsg::Distribute {
+            offer_asset,
+            legs,
+            to,
+        } => execute::distribute(deps, exe_env, info, offer_asset, legs, to),
+        ExecuteMsg::ZapIn {
+            pool,
+            dex,
+            offer_asset,
+            swap_steps,
+            belief_price,
+            slippage_tolerance,
+            auto_stake,
+            receiver,
+        } => execute::zap_in(
+            deps,
+            exe_env,
+            info,
+            execute::ZapInParams {
+                pool,
+                dex,
+                offer_asset,
+                swap_steps,
+                belief_price,
+                slippage_tolerance,
+                auto_stake,
+                receiver,
+            },
+        ),
+        ExecuteMsg::ZapInSwap {
+            pool,
+            dex,
+            asset_info,
+            prev_balance,
+            belief_price,
+            slippage_tolerance,
+            auto_stake,
+            receiver,
+            refund_to,
+        } => execute::zap_in_swap(
+            deps,
+            exe_env,
+            info,
+            execute::ZapInSwapParams {
+                pool,
+                dex,
+                asset_info,
+                prev_balance,
+                belief_price,
+                slippage_tolerance,
+                auto_stake,
+                receiver,
+                refund_to,
+            },
+        ),
+        ExecuteMsg::ZapInProvide {
+            pool,
+            dex,
+            asset,
+            other_asset_info,
+            other_prev_balance,
+            slippage_tolerance,
+            auto_stake,
+            receiver,
+            refund_to,
+        } => execute::zap_in_provide(
+            deps,
+            exe_env,
+            info,
+            execute::ZapInProvideParams {
+                pool,
+                dex,
+                asset,
+                other_asset_info,
+                other_prev_balance,
+                slippage_tolerance,
+                auto_stake,
+                receiver,
+                refund_to,
+            },
+        ),
+        ExecuteMsg::Receive(msg) => execute::receive_cw20(deps, exe_env, info, msg),
+        ExecuteMsg::ZapOutRoute {
+            prev_balances,
+            target_asset_info,
+            steps_per_asset,
+            to,
+            sender,
+        } => execute::zap_out_route(
+            deps,
+            exe_env,
+            info,
+            execute::ZapOutRouteParams {
+                prev_balances,
+                target_asset_info,
+                steps_per_asset,
+                to,
+                sender,
+            },
+        ),
+        ExecuteMsg::BatchUnxswap { swaps, atomic } => {
+            execute::batch_unxswap(deps, exe_env, info, swaps, atomic)
+        }
+        ExecuteMsg::ExecuteRoute {
+            offer_asset,
             steps,
             minimum_receive,
             to,
-            target_asset_info
-        } => execute::unxswap(deps, exe_env, info, steps, minimum_receive, to, target_asset_info),
+            target_asset_info,
+        } => {
+            let to = deps.api.addr_validate(&to)?;
+            execute::execute_route(
+                deps,
+                exe_env,
+                info,
+                execute::ExecuteRouteParams {
+                    offer_asset,
+                    steps,
+                    minimum_receive,
+                    to,
+                    target_asset_info,
+                },
+            )
+        }
+        ExecuteMsg::Arbitrage { steps, min_profit } => {
+            execute::arbitrage(deps, exe_env, info, steps, min_profit)
+        }
+        ExecuteMsg::AssertMinimumProfit {
+            asset_info,
+            prev_balance,
+            offer_amount,
+            min_profit,
+            receiver,
+        } => execute::assert_minimum_profit(
+            deps.as_ref(),
+            asset_info,
+            prev_balance,
+            offer_amount,
+            min_profit,
+            deps.api.addr_validate(&receiver)?,
+        ),
         ExecuteMsg::AssertMinimumReceive {
             asset_info,
             prev_balance,
@@ -66,104 +444,470 @@ pub fn execute(
             minimum_receive,
             deps.api.addr_validate(&receiver)?,
         ),
-        
+        ExecuteMsg::SetPairOracle {
+            asset_infos,
+            oracle,
+            max_deviation,
+        } => execute::set_pair_oracle(deps, info, asset_infos, oracle, max_deviation),
+        ExecuteMsg::RemovePairOracle { asset_infos } => {
+            execute::remove_pair_oracle(deps, info, asset_infos)
+        }
+        ExecuteMsg::WithdrawCompound { lp_token, shares } => {
+            execute::withdraw_compound(deps, exe_env, info, lp_token, shares)
+        }
+        ExecuteMsg::SetCompoundConfig {
+            lp_token,
+            pool,
+            reward_routes,
+            max_spread,
+        } => execute::set_compound_config(deps, info, lp_token, pool, reward_routes, max_spread),
+        ExecuteMsg::Compound { lp_token } => execute::compound(deps, exe_env, lp_token),
+        ExecuteMsg::CompoundSwap {
+            lp_token,
+            reward_routes,
+            prev_balances,
+        } => execute::compound_swap(deps, exe_env, info, lp_token, reward_routes, prev_balances),
+        ExecuteMsg::EscrowCompoundLeftovers {
+            lp_token,
+            prev_balances,
+        } => execute::escrow_compound_leftovers(deps, exe_env, info, lp_token, prev_balances),
+        ExecuteMsg::SetKeeperTip { tip_bps } => execute::set_keeper_tip(deps, info, tip_bps),
+        ExecuteMsg::PlaceLimitOrder {
+            offer_asset,
+            steps,
+            min_price,
+            expiry,
+        } => execute::place_limit_order(deps, exe_env, info, offer_asset, steps, min_price, expiry),
+        ExecuteMsg::CancelOrder { id } => execute::cancel_order(deps, info, id),
+        ExecuteMsg::ExecuteOrder { id } => execute::execute_order(deps, exe_env, info, id),
+        ExecuteMsg::SettleOrder {
+            id,
+            owner,
+            keeper,
+            target_asset_info,
+            prev_balance,
+            minimum_receive,
+        } => execute::settle_order(
+            deps,
+            exe_env,
+            info,
+            execute::SettleOrderParams {
+                id,
+                owner,
+                keeper,
+                target_asset_info,
+                prev_balance,
+                minimum_receive,
+            },
+        ),
+        ExecuteMsg::CreateDca {
+            offer_asset,
+            steps,
+            chunk_amount,
+            interval_seconds,
+            max_price,
+        } => execute::create_dca(
+            deps,
+            exe_env,
+            info,
+            execute::DcaParams {
+                offer_asset,
+                steps,
+                chunk_amount,
+                interval_seconds,
+                max_price,
+            },
+        ),
+        ExecuteMsg::ExecuteDca { id } => execute::execute_dca(deps, exe_env, info, id),
+        ExecuteMsg::CancelDca { id } => execute::cancel_dca(deps, info, id),
+        ExecuteMsg::SettleDca {
+            id,
+            index,
+            offer_amount,
+            prev_balance,
+            minimum_receive,
+        } => execute::settle_dca(
+            deps,
+            exe_env,
+            info,
+            execute::SettleDcaParams {
+                id,
+                index,
+                offer_amount,
+                prev_balance,
+                minimum_receive,
+            },
+        ),
+        ExecuteMsg::PlaceConditionalOrder {
+            offer_asset,
+            steps,
+            minimum_receive,
+            price_pool,
+            dex,
+            condition,
+            twap_window_seconds,
+        } => execute::place_conditional_order(
+            deps,
+            exe_env,
+            info,
+            execute::ConditionalOrderParams {
+                offer_asset,
+                steps,
+                minimum_receive,
+                price_pool,
+                dex,
+                condition,
+                twap_window_seconds,
+            },
+        ),
+        ExecuteMsg::CancelConditionalOrder { id } => {
+            execute::cancel_conditional_order(deps, info, id)
+        }
+        ExecuteMsg::Trigger { id } => execute::trigger(deps, exe_env, id),
+        ExecuteMsg::CommitSwap { hash } => execute::commit_native_swap(deps, exe_env, info, hash),
+        ExecuteMsg::RevealSwap {
+            steps,
+            minimum_receive,
+            salt,
+        } => execute::reveal_swap(deps, exe_env, info, steps, minimum_receive, salt),
+        ExecuteMsg::RefundCommit { hash } => execute::refund_commit(deps, exe_env, info, hash),
+        ExecuteMsg::Recover {} => execute::recover(deps, info),
+        ExecuteMsg::LockSwap {
+            hashlock,
+            timelock,
+            recipient,
+            asset,
+            route,
+            minimum_receive,
+        } => execute::lock_swap(
+            deps,
+            exe_env,
+            info,
+            execute::LockSwapParams {
+                hashlock,
+                timelock,
+                recipient,
+                asset,
+                route,
+                minimum_receive,
+            },
+        ),
+        ExecuteMsg::Claim { sender, preimage } => execute::claim(deps, exe_env, sender, preimage),
+        ExecuteMsg::ExecuteClaimRoute {} => execute::execute_claim_route(deps, exe_env, info),
+        ExecuteMsg::Refund { sender, hashlock } => {
+            execute::refund(deps, exe_env, sender, hashlock)
+        }
+        ExecuteMsg::SubmitAuctionOrder {
+            pool,
+            dex,
+            max_spread,
+        } => execute::submit_native_auction_order(deps, exe_env, info, pool, dex, max_spread),
+        ExecuteMsg::CancelAuctionOrder { pool, round, index } => {
+            execute::cancel_auction_order(deps, info, pool, round, index)
+        }
+        ExecuteMsg::SettleAuction { pool, round } => {
+            execute::settle_auction(deps, exe_env, pool, round)
+        }
+        ExecuteMsg::DistributeAuction {
+            pool,
+            round,
+            prices,
+            prev_balances,
+        } => execute::distribute_auction(
+            deps,
+            exe_env,
+            info,
+            pool,
+            round,
+            prices,
+            prev_balances,
+        ),
+        ExecuteMsg::ExecuteSignedSwap {
+            intent,
+            signature,
+            pubkey,
+        } => execute::execute_signed_swap(deps, exe_env, info, intent, signature, pubkey),
+        ExecuteMsg::SettleSignedSwap {
+            target_asset_info,
+            prev_balance,
+            minimum_receive,
+            max_relayer_fee,
+            receiver,
+            relayer,
+        } => execute::settle_signed_swap(
+            deps,
+            exe_env,
+            info,
+            execute::SettleSignedSwapParams {
+                target_asset_info,
+                prev_balance,
+                minimum_receive,
+                max_relayer_fee,
+                receiver,
+                relayer,
+            },
+        ),
+        ExecuteMsg::RfqSwap {
+            maker,
+            quote,
+            signature,
+            offer_amount,
+            to,
+        } => execute::rfq_swap(
+            deps,
+            exe_env,
+            info,
+            execute::RfqSwapParams {
+                maker,
+                quote,
+                signature,
+                offer_amount,
+                to,
+            },
+        ),
+        ExecuteMsg::SetRfqMaker { maker, pubkey } => {
+            execute::set_rfq_maker(deps, info, maker, pubkey)
+        }
+        ExecuteMsg::RemoveRfqMaker { maker } => execute::remove_rfq_maker(deps, info, maker),
+        ExecuteMsg::SetGenerator { generator } => execute::set_generator(deps, info, generator),
+        ExecuteMsg::ProvideAndStake {
+            pool,
+            assets,
+            slippage_tolerance,
+            receiver,
+        } => execute::provide_and_stake(
+            deps,
+            exe_env,
+            info,
+            pool,
+            assets,
+            slippage_tolerance,
+            receiver,
+        ),
+    }
+
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn migrate(deps: DepsMut, _env: Env, msg: MigrateMsg) -> Result<Response, ContractError> {
+    let contract_version = get_contract_version(deps.storage)?;
+    if contract_version.contract != CONTRACT_NAME {
+        return Err(ContractError::MigrationError {});
+    }
+
+    let mut state = STATE.load(deps.storage)?;
+    if let Some(owner) = msg.owner {
+        state.owner = deps.api.addr_validate(&owner)?;
+    }
+    STATE.save(deps.storage, &state)?;
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "migrate")
+        .add_attribute("previous_contract_version", contract_version.version)
+        .add_attribute("owner", state.owner))
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn reply(deps: DepsMut, env: Env, msg: Reply) -> Result<Response, ContractError> {
+    match msg.id {
+        BATCH_SWAP_REPLY_ID => reply::batch_swap(deps, msg.result),
+        SWAP_CALLBACK_REPLY_ID => reply::swap_callback(msg.result),
+        RECOVERABLE_SWAP_REPLY_ID => reply::recoverable_swap(deps, msg.result),
+        IBC_TRANSFER_REPLY_ID => reply::ibc_transfer(deps, msg.result),
+        CLAIM_ROUTE_REPLY_ID => reply::claim_route(deps, msg.result),
+        id if id >= STAKE_REPLY_ID => {
+            reply::stake(deps, stake_id(id - STAKE_REPLY_ID)?, msg.result)
+        }
+        id if id >= PROVIDE_LIQUIDITY_REPLY_ID => reply::provide_liquidity(
+            deps,
+            env,
+            stake_id(id - PROVIDE_LIQUIDITY_REPLY_ID)?,
+            msg.result,
+        ),
+        _ => Err(ContractError::Std(StdError::generic_err(format!(
+            "Unknown reply id: {}",
+            msg.id
+        )))),
+    }
+}
+
+/// Returns the pending stake id carried in a `ProvideAndStake` reply id.
+fn stake_id(offset: u64) -> Result<u32, ContractError> {
+    u32::try_from(offset).map_err(|_| {
+        ContractError::Std(StdError::generic_err(format!(
+            "Unknown reply id offset: {offset}"
+        )))
+    })
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn sudo(deps: DepsMut, _env: Env, msg: SudoMsg) -> Result<Response, ContractError> {
+    match msg {
+        SudoMsg::IbcLifecycleComplete(IbcLifecycleComplete::IbcAck {
+            channel,
+            sequence,
+            ack: _,
+            success,
+        }) => sudo::ibc_lifecycle_complete(deps, channel, sequence, success),
+        SudoMsg::IbcLifecycleComplete(IbcLifecycleComplete::IbcTimeout { channel, sequence }) => {
+            sudo::ibc_lifecycle_complete(deps, channel, sequence, false)
+        }
     }
+}
 
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
+    match msg {
+        QueryMsg::GetCount {} => to_binary(&query::count(deps)?),
+        QueryMsg::PairOracle { asset_infos } => to_binary(&query::pair_oracle(deps, asset_infos)?),
+        QueryMsg::Generator {} => to_binary(&GENERATOR.may_load(deps.storage)?),
+        QueryMsg::CompoundConfig { lp_token } => {
+            to_binary(&query::compound_config(deps, lp_token)?)
+        }
+        QueryMsg::CompoundPosition { lp_token, owner } => {
+            to_binary(&query::compound_position(deps, env, lp_token, owner)?)
+        }
+        QueryMsg::KeeperTip {} => {
+            to_binary(&KEEPER_TIP_BPS.may_load(deps.storage)?.unwrap_or_default())
+        }
+        QueryMsg::Order { id } => to_binary(&query::order(deps, id)?),
+        QueryMsg::OrdersByOwner {
+            owner,
+            start_after,
+            limit,
+        } => to_binary(&query::orders_by_owner(deps, owner, start_after, limit)?),
+        QueryMsg::OrdersByPair {
+            asset_infos,
+            start_after,
+            limit,
+        } => to_binary(&query::orders_by_pair(deps, asset_infos, start_after, limit)?),
+        QueryMsg::ConditionalOrder { id } => to_binary(&query::conditional_order(deps, id)?),
+        QueryMsg::ConditionalOrders { start_after, limit } => {
+            to_binary(&query::conditional_orders(deps, start_after, limit)?)
+        }
+        QueryMsg::AuctionBatch { pool, round } => {
+            to_binary(&query::auction_batch(deps, pool, round)?)
+        }
+        QueryMsg::IbcTransfersByOwner {
+            owner,
+            start_after,
+            limit,
+        } => to_binary(&query::ibc_transfers_by_owner(deps, owner, start_after, limit)?),
+        QueryMsg::Recoveries { addr } => to_binary(&query::recoveries(deps, addr)?),
+        QueryMsg::HashedTimelock { sender, hashlock } => {
+            to_binary(&query::hashed_timelock(deps, sender, hashlock)?)
+        }
+        QueryMsg::Commitment { owner, hash } => to_binary(&query::commitment(deps, owner, hash)?),
+        QueryMsg::RfqMaker { maker } => to_binary(&query::rfq_maker(deps, maker)?),
+        QueryMsg::SignerNonce { signer } => to_binary(&query::signer_nonce(deps, signer)?),
+        QueryMsg::Dca { id } => to_binary(&query::dca(deps, id)?),
+        QueryMsg::DcaHistory {
+            id,
+            start_after,
+            limit,
+        } => to_binary(&query::dca_history(deps, id, start_after, limit)?),
+    }
 }
 
 pub mod execute {
     use super::*;
 
-    pub fn sparrowSwap(
+    /// This structure describes a swap of the spendable offer balance in a single pair.
+    pub struct SwapParams {
+        pub pool_address: String,
+        pub offer_asset_info: AssetInfo,
+        /// The amount to swap, the whole spendable balance if not set
+        pub offer_amount: Option<Uint128>,
+        pub ask_asset_info: Option<AssetInfo>,
+        pub belief_price: Option<Decimal>,
+        pub max_spread: Option<Decimal>,
+        pub to: Option<String>,
+    }
+
+    pub fn sparrow_swap(
         deps: DepsMut,
         env: Env,
         _info: MessageInfo,
-        pool_address: String,
-        offer_asset_info: SparrowSwapAssetInfo,
-        belief_price: Option<Decimal>,
-        max_spread: Option<Decimal>,
-        to: Option<String>,
+        params: SwapParams,
     ) -> Result<Response, ContractError> {
-
-        let mut temp_offer_asset_info = offer_asset_info.clone();
-
-        // smart query
-        let offer_balance = match &temp_offer_asset_info {
-            SparrowSwapAssetInfo::NativeToken { denom } => {
-                query_balance(&deps.querier, env.contract.address, denom)?
-            }
-            SparrowSwapAssetInfo::Token { contract_addr } => {
-                query_token_balance(&deps.querier, contract_addr, env.contract.address)?
-            }
-        };
-        let offer_denom = str::from_utf8(temp_offer_asset_info.as_bytes());
-        let new_funds = vec![Coin{
-            denom: (*(offer_denom.unwrap())).to_string(),
-            amount: offer_balance
-        }];
-
-        let new_offer_asset = SparrowSwapAsset {
-            info: offer_asset_info,
-            amount: offer_balance,
-        };
-
-        Ok(Response::new().add_message(WasmMsg::Execute {
-            contract_addr: pool_address,
-            funds: new_funds,
-            msg: to_binary(&SparrowSwapeMsg::Swap {
-                offer_asset: new_offer_asset,
-                belief_price: belief_price,
-                max_spread: max_spread,
-                to: to
-            })?,
-        }))
+        swap_with_adapter(deps, &env, registered_adapter(SPARROWSWAP)?, params)
     }
 
-    
-    pub fn astroportSwap(
+    pub fn astroport_swap(
         deps: DepsMut,
         exe_env: Env,
         _info: MessageInfo,
-        pool_address: String,
-        offer_asset_info: AssetInfo,
-        ask_asset_info: Option<AssetInfo>,
-        belief_price: Option<Decimal>,
-        max_spread: Option<Decimal>,
-        to: Option<String>
+        params: SwapParams,
     ) -> Result<Response, ContractError> {
+        swap_with_adapter(deps, &exe_env, registered_adapter(ASTROPORT)?, params)
+    }
 
-        let mut temp_offer_asset_info = offer_asset_info.clone();
-        // smart query
-        let offer_balance = match &temp_offer_asset_info {
-            AssetInfo::NativeToken { denom } => {
-                query_balance(&deps.querier, exe_env.contract.address, denom)?
-            }
-            AssetInfo::Token { contract_addr } => {
-                query_token_balance(&deps.querier, contract_addr, exe_env.contract.address)?
-            }
-        };
-        let offer_denom = str::from_utf8(temp_offer_asset_info.as_bytes());
-        let new_funds = vec![Coin{
-            denom: (*(offer_denom.unwrap())).to_string(),
-            amount: offer_balance
-        }];
+    pub fn dex_swap(
+        deps: DepsMut,
+        env: Env,
+        dex: String,
+        params: SwapParams,
+    ) -> Result<Response, ContractError> {
+        swap_with_adapter(deps, &env, registered_adapter(&dex)?, params)
+    }
+
+    /// Swaps the spendable offer balance in a pair after checking the simulated return against
+    /// the pair oracle, if there is one.
+    fn swap_with_adapter(
+        deps: DepsMut,
+        env: &Env,
+        adapter: &dyn DexAdapter,
+        params: SwapParams,
+    ) -> Result<Response, ContractError> {
+        let SwapParams {
+            pool_address,
+            offer_asset_info,
+            offer_amount,
+            ask_asset_info,
+            belief_price,
+            max_spread,
+            to,
+        } = params;
 
-        let new_offer_asset = AstroportAsset {
+        let offer_asset = AstroportAsset {
+            amount: spendable_amount(deps.as_ref(), env, &offer_asset_info, offer_amount)?,
             info: offer_asset_info,
-            amount: offer_balance,
-        };
-
-        Ok(Response::new().add_message(WasmMsg::Execute {
-            contract_addr: pool_address,
-            funds: new_funds,
-            msg: to_binary(&AstroportMsg::Swap {
-                offer_asset: new_offer_asset,
-                ask_asset_info: ask_asset_info,
-                belief_price: belief_price,
-                max_spread: max_spread,
-                to: to
-            })?,
-        }))
+        };
+
+        // The pair is only queried for its ask asset when an oracle could apply to the hop
+        let resolved_ask_asset_info = match &ask_asset_info {
+            Some(ask_asset_info) => Some(ask_asset_info.clone()),
+            None if has_pair_oracle(deps.storage, &offer_asset.info)? => Some(
+                adapter.ask_asset_info(&deps.querier, &pool_address, &offer_asset.info)?,
+            ),
+            None => None,
+        };
+        if let Some(resolved_ask_asset_info) = resolved_ask_asset_info {
+            assert_oracle_price(
+                deps.as_ref(),
+                &offer_asset,
+                &resolved_ask_asset_info,
+                || {
+                    let res = adapter.simulate(
+                        &deps.querier,
+                        &pool_address,
+                        &offer_asset,
+                        ask_asset_info.clone(),
+                    )?;
+                    Ok(res.return_amount)
+                },
+            )?;
+        }
+
+        Ok(Response::new().add_message(adapter.swap_msg(
+            &pool_address,
+            &offer_asset,
+            ask_asset_info,
+            belief_price,
+            max_spread,
+            to,
+        )?))
     }
 
     pub fn assert_minimum_receive(
@@ -187,99 +931,4948 @@ pub mod execute {
         Ok(Response::default())
     }
 
+    /// Runs a cyclic route with the attached funds and reverts unless the sender ends up with
+    /// at least `min_profit` more of the offered asset.
+    pub fn arbitrage(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        steps: Vec<SwapOperation>,
+        min_profit: Uint128,
+    ) -> Result<Response, ContractError> {
+        let (first, last) = match (steps.first(), steps.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return Err(ContractError::MustProvideOperations {}),
+        };
+        let asset_info = operation_offer_asset_info(first);
+        let denom = match &asset_info {
+            AssetInfo::NativeToken { denom } => denom.clone(),
+            AssetInfo::Token { .. } => return Err(ContractError::NativeOfferRequired {}),
+        };
+        if !operation_ask_asset_info(deps.as_ref(), last)?.equal(&asset_info) {
+            return Err(ContractError::NotCyclicRoute {
+                asset: asset_info.to_string(),
+            });
+        }
+        let offer_amount = must_pay(&info, &denom)
+            .map_err(|err| StdError::generic_err(err.to_string()))?;
+
+        let prev_balance = asset_info.query_pool(&deps.querier, &info.sender)?;
+        let mut messages =
+            route_messages(&env, steps, Some(offer_amount), &info.sender, &info.sender)?;
+        messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: env.contract.address.to_string(),
+            funds: vec![],
+            msg: to_binary(&ExecuteMsg::AssertMinimumProfit {
+                asset_info: asset_info.clone(),
+                prev_balance,
+                offer_amount,
+                min_profit,
+                receiver: info.sender.to_string(),
+            })?,
+        }));
+
+        Ok(Response::new()
+            .add_messages(messages)
+            .add_attribute("action", "arbitrage")
+            .add_attribute("offer_asset", format!("{offer_amount}{asset_info}"))
+            .add_attribute("min_profit", min_profit))
+    }
+
+    pub fn assert_minimum_profit(
+        deps: Deps,
+        asset_info: AssetInfo,
+        prev_balance: Uint128,
+        offer_amount: Uint128,
+        min_profit: Uint128,
+        receiver: Addr,
+    ) -> Result<Response, ContractError> {
+        let receiver_balance = asset_info.query_pool(&deps.querier, receiver)?;
+        let return_amount = receiver_balance.checked_sub(prev_balance)?;
+
+        if return_amount < offer_amount.checked_add(min_profit)? {
+            return Err(ContractError::AssertionMinimumProfit {
+                min_profit,
+                offer_amount,
+                return_amount,
+            });
+        }
+
+        Ok(Response::new()
+            .add_attribute("action", "assert_minimum_profit")
+            .add_attribute("offer_amount", offer_amount)
+            .add_attribute("return_amount", return_amount)
+            .add_attribute("profit", return_amount - offer_amount))
+    }
+
+    /// This structure describes a route of `Unxswap` and what happens to its output.
+    pub struct RouteParams {
+        pub steps: Vec<SwapOperation>,
+        pub minimum_receive: Option<Uint128>,
+        pub to: Option<Addr>,
+        pub target_asset_info: AssetInfo,
+        pub post_action: Option<PostAction>,
+        pub callback: Option<Binary>,
+        pub ibc_forward: Option<IbcForward>,
+    }
+
     pub fn unxswap(
         deps: DepsMut,
         env: Env,
         raw_info: MessageInfo,
-        steps: Vec<SwapOperation>,
-        minimum_receive: Option<Uint128>,
-        to: Option<Addr>,
-        target_asset_info: AssetInfo,
+        route: RouteParams,
+        recovery_addr: Option<String>,
     ) -> Result<Response, ContractError> {
-        let operations_len = steps.len();
-        let mut operation_index = 0;        
+        let RouteParams {
+            steps,
+            minimum_receive,
+            to,
+            target_asset_info,
+            post_action,
+            callback,
+            ibc_forward,
+        } = route;
+        if post_action.is_some() && to.is_some() {
+            return Err(ContractError::PostActionWithReceiver {});
+        }
+        if ibc_forward.is_some() {
+            if post_action.is_some() || to.is_some() {
+                return Err(ContractError::IbcForwardWithReceiver {});
+            }
+            if !target_asset_info.is_native_token() {
+                return Err(ContractError::IbcForwardNotNative {});
+            }
+        }
+        if let Some(recovery_addr) = recovery_addr {
+            return recoverable_unxswap(
+                deps,
+                env,
+                raw_info,
+                RouteParams {
+                    steps,
+                    minimum_receive,
+                    to,
+                    target_asset_info,
+                    post_action,
+                    callback,
+                    ibc_forward,
+                },
+                recovery_addr,
+            );
+        }
+
+        // A recoverable route runs as a self-call on behalf of the sender of the recoverable swap
+        let pending_recovery = match PENDING_RECOVERY.may_load(deps.storage)? {
+            Some(pending) if raw_info.sender == env.contract.address => Some(pending),
+            _ => None,
+        };
+        let sender = match &pending_recovery {
+            Some(pending) => pending.sender.clone(),
+            None => raw_info.sender.clone(),
+        };
 
-        let to = if let Some(to) = to {
+        // With a post action or an IBC forward the output is collected by the contract and
+        // forwarded afterwards
+        let to = if post_action.is_some() || ibc_forward.is_some() {
+            env.contract.address.clone()
+        } else if let Some(to) = to {
             deps.api.addr_validate(to.as_str())?
         } else {
-            raw_info.sender
+            raw_info.sender.clone()
         };
+        let prev_balance = target_asset_info.query_pool(&deps.querier, &to)?;
 
-        let mut messages: Vec<CosmosMsg> = steps
-            .into_iter()
-            .map(|op| {
-                operation_index += 1;
-                match op {
-                    SwapOperation::SparrowSwap  {
-                        pool_address,
-                        offer_asset_info,
-                        belief_price,
-                        max_spread
-                    } => {
-                        Ok(
-                            CosmosMsg::Wasm(
-                                WasmMsg::Execute {
-                                    contract_addr: env.contract.address.to_string(),
-                                    funds: vec![],   
-                                    msg: to_binary(&ExecuteMsg::SparrowSwap {
-                                        pool_address: pool_address,
-                                        offer_asset_info: offer_asset_info,
-                                        belief_price: belief_price,
-                                        max_spread: max_spread,
-                                        to: if operation_index == operations_len {
-                                            Some(to.to_string())
-                                        } else {
-                                            None
-                                        },
-                                    })?,
-                                }
-                            )
-                        )
-                    },
-                    SwapOperation::AstroportSwap  {
-                        pool_address,
-                        offer_asset_info,
-                        ask_asset_info,
-                        belief_price,
-                        max_spread
-                    } => {
-                        Ok(CosmosMsg::Wasm(WasmMsg::Execute {
-                            contract_addr: env.contract.address.to_string(),
-                            funds: vec![],
-                            msg: to_binary(&ExecuteMsg::AstroportSwap {
-                                pool_address: pool_address,
-                                offer_asset_info: offer_asset_info,
-                                ask_asset_info: ask_asset_info,
-                                belief_price: belief_price,
-                                max_spread: max_spread,
-                                to: if operation_index == operations_len {
-                                    Some(to.to_string())
-                                } else {
-                                    None
-                                },
-                            })?,
-                        }))
-                    }
-                } 
-            })
-            .collect::<StdResult<Vec<CosmosMsg>>>()?;
+        // The first hop swaps the spendable contract balance of the offer asset
+        let offer_asset = match steps.first() {
+            Some(operation) => {
+                let offer_asset_info = operation_offer_asset_info(operation);
+                AstroportAsset {
+                    amount: spendable_amount(deps.as_ref(), &env, &offer_asset_info, None)?,
+                    info: offer_asset_info,
+                }
+            }
+            None => return Err(ContractError::MustProvideOperations {}),
+        };
+
+        let mut messages = route_messages(&env, steps, None, &to, &sender)?;
 
         // Execute minimum amount assertion
         if let Some(minimum_receive) = minimum_receive {
-            let receiver_balance = target_asset_info.query_pool(&deps.querier, to.clone())?;
-            messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
+            messages.push(minimum_receive_message(
+                deps.as_ref(),
+                &env,
+                target_asset_info.clone(),
+                minimum_receive,
+                &to,
+            )?);
+        }
+
+        // Report the result before a post action moves the output away
+        let mut sub_messages = vec![];
+        if let Some(callback) = callback {
+            sub_messages.push(SubMsg::reply_on_success(
+                WasmMsg::Execute {
+                    contract_addr: env.contract.address.to_string(),
+                    funds: vec![],
+                    msg: to_binary(&ExecuteMsg::ExecuteSwapCallback {
+                        offer_asset,
+                        target_asset_info: target_asset_info.clone(),
+                        prev_balance,
+                        receiver: to.to_string(),
+                        sender: raw_info.sender.to_string(),
+                        callback,
+                    })?,
+                },
+                SWAP_CALLBACK_REPLY_ID,
+            ));
+        }
+
+        if let Some(post_action) = post_action {
+            deps.api.addr_validate(&post_action.contract)?;
+            sub_messages.push(SubMsg::new(WasmMsg::Execute {
                 contract_addr: env.contract.address.to_string(),
                 funds: vec![],
-                msg: to_binary(&ExecuteMsg::AssertMinimumReceive {
-                    asset_info: target_asset_info,
-                    prev_balance: receiver_balance,
-                    minimum_receive,
-                    receiver: to.to_string(),
+                msg: to_binary(&ExecuteMsg::ExecutePostAction {
+                    asset_info: target_asset_info.clone(),
+                    prev_balance,
+                    post_action,
                 })?,
             }));
         }
 
-        Ok(Response::new().add_messages(messages))
-
+        if let Some(ibc_forward) = ibc_forward {
+            // A recoverable route keeps the sender and the recovery address it was given, which
+            // is also where a failed transfer is credited
+            let recovery_addr = match pending_recovery {
+                Some(pending) => pending.recovery_addr,
+                None => raw_info.sender.clone(),
+            };
+            sub_messages.push(SubMsg::new(WasmMsg::Execute {
+                contract_addr: env.contract.address.to_string(),
+                funds: vec![],
+                msg: to_binary(&ExecuteMsg::ExecuteIbcForward {
+                    asset_info: target_asset_info,
+                    prev_balance,
+                    ibc_forward,
+                    owner: sender.to_string(),
+                    recovery_addr: recovery_addr.to_string(),
+                })?,
+            }));
+        }
+
+        Ok(Response::new()
+            .add_messages(messages)
+            .add_submessages(sub_messages))
+
+    }
+
+    /// Runs `Unxswap` as a submessage so that a failed route credits its offer to the recovery
+    /// address instead of reverting the whole transaction.
+    fn recoverable_unxswap(
+        deps: DepsMut,
+        env: Env,
+        raw_info: MessageInfo,
+        route: RouteParams,
+        recovery_addr: String,
+    ) -> Result<Response, ContractError> {
+        let RouteParams {
+            steps,
+            minimum_receive,
+            to,
+            target_asset_info,
+            post_action,
+            callback,
+            ibc_forward,
+        } = route;
+        if callback.is_some() {
+            return Err(ContractError::Std(StdError::generic_err(
+                "A swap callback can't be combined with a recovery address",
+            )));
+        }
+        let recovery_addr = deps.api.addr_validate(&recovery_addr)?;
+
+        // The inner route is executed by the contract, so the default receiver is resolved here
+        let to = if post_action.is_some() || ibc_forward.is_some() {
+            None
+        } else {
+            Some(match to {
+                Some(to) => deps.api.addr_validate(to.as_str())?,
+                None => raw_info.sender.clone(),
+            })
+        };
+        let offer_asset_info = operation_offer_asset_info(
+            steps.first().ok_or(ContractError::MustProvideOperations {})?,
+        );
+        let offer_asset = AstroportAsset {
+            amount: spendable_amount(deps.as_ref(), &env, &offer_asset_info, None)?,
+            info: offer_asset_info,
+        };
+        PENDING_RECOVERY.save(
+            deps.storage,
+            &PendingRecovery {
+                offer_asset: offer_asset.clone(),
+                sender: raw_info.sender,
+                recovery_addr: recovery_addr.clone(),
+            },
+        )?;
+
+        Ok(Response::new()
+            .add_submessage(SubMsg::reply_always(
+                WasmMsg::Execute {
+                    contract_addr: env.contract.address.to_string(),
+                    funds: vec![],
+                    msg: to_binary(&ExecuteMsg::Unxswap {
+                        steps,
+                        minimum_receive,
+                        to,
+                        target_asset_info,
+                        post_action,
+                        callback: None,
+                        recovery_addr: None,
+                        ibc_forward,
+                    })?,
+                },
+                RECOVERABLE_SWAP_REPLY_ID,
+            ))
+            .add_attribute("action", "recoverable_unxswap")
+            .add_attribute("offer_asset", offer_asset.to_string())
+            .add_attribute("recovery_addr", recovery_addr))
+    }
+
+    /// This structure describes the route output forwarded over IBC and who is credited if the
+    /// transfer fails.
+    pub struct IbcForwardParams {
+        pub asset_info: AssetInfo,
+        pub prev_balance: Uint128,
+        pub ibc_forward: IbcForward,
+        pub owner: String,
+        pub recovery_addr: String,
+    }
+
+    /// Sends the output of a route over IBC with an ibc-hooks callback and records the transfer
+    /// until the callback arrives. Only the contract itself can execute it.
+    pub fn execute_ibc_forward(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        params: IbcForwardParams,
+    ) -> Result<Response, ContractError> {
+        let IbcForwardParams {
+            asset_info,
+            prev_balance,
+            ibc_forward,
+            owner,
+            recovery_addr,
+        } = params;
+
+        if info.sender != env.contract.address {
+            return Err(ContractError::Unauthorized {});
+        }
+
+        let denom = match &asset_info {
+            AssetInfo::NativeToken { denom } => denom.clone(),
+            AssetInfo::Token { .. } => return Err(ContractError::IbcForwardNotNative {}),
+        };
+        let amount = asset_info
+            .query_pool(&deps.querier, &env.contract.address)?
+            .checked_sub(prev_balance)?;
+        if amount.is_zero() {
+            return Err(ContractError::Std(StdError::generic_err(
+                "Nothing to forward over IBC",
+            )));
+        }
+
+        // Ask ibc-hooks to report the acknowledgement or timeout through `sudo`
+        let memo = ibc_callback_memo(ibc_forward.memo.as_deref(), env.contract.address.as_str())?;
+
+        PENDING_IBC_TRANSFER.save(
+            deps.storage,
+            &IbcTransfer {
+                channel: ibc_forward.channel.clone(),
+                owner: deps.api.addr_validate(&owner)?,
+                asset: AstroportAsset {
+                    info: asset_info,
+                    amount,
+                },
+                recovery_addr: deps.api.addr_validate(&recovery_addr)?,
+            },
+        )?;
+
+        let transfer = MsgTransfer {
+            source_port: "transfer".to_string(),
+            source_channel: ibc_forward.channel.clone(),
+            token: Coin {
+                denom: denom.clone(),
+                amount,
+            },
+            sender: env.contract.address.to_string(),
+            receiver: ibc_forward.receiver.clone(),
+            timeout_timestamp: env
+                .block
+                .time
+                .plus_seconds(ibc_forward.timeout_seconds)
+                .nanos(),
+            memo,
+        };
+
+        Ok(Response::new()
+            .add_submessage(SubMsg::reply_on_success(
+                CosmosMsg::Stargate {
+                    type_url: MSG_TRANSFER_TYPE_URL.to_string(),
+                    value: transfer.encode(),
+                },
+                IBC_TRANSFER_REPLY_ID,
+            ))
+            .add_attribute("action", "execute_ibc_forward")
+            .add_attribute("channel", ibc_forward.channel)
+            .add_attribute("receiver", ibc_forward.receiver)
+            .add_attribute("amount", amount)
+            .add_attribute("denom", denom))
+    }
+
+    /// This structure describes the route result reported to the contract that called `Unxswap`.
+    pub struct SwapCallbackParams {
+        pub offer_asset: AstroportAsset,
+        pub target_asset_info: AssetInfo,
+        pub prev_balance: Uint128,
+        pub receiver: String,
+        pub sender: String,
+        pub callback: Binary,
+    }
+
+    /// Sends the route result to the contract that called `Unxswap` and returns it as data.
+    /// Only the contract itself can execute it.
+    pub fn execute_swap_callback(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        params: SwapCallbackParams,
+    ) -> Result<Response, ContractError> {
+        let SwapCallbackParams {
+            offer_asset,
+            target_asset_info,
+            prev_balance,
+            receiver,
+            sender,
+            callback,
+        } = params;
+
+        if info.sender != env.contract.address {
+            return Err(ContractError::Unauthorized {});
+        }
+
+        let receiver_balance = target_asset_info.query_pool(&deps.querier, receiver)?;
+        let return_asset = AstroportAsset {
+            info: target_asset_info,
+            amount: receiver_balance.checked_sub(prev_balance)?,
+        };
+
+        Ok(Response::new()
+            .add_message(WasmMsg::Execute {
+                contract_addr: sender,
+                funds: vec![],
+                msg: to_binary(&CallbackMsg::SwapCallback {
+                    offer_asset: offer_asset.clone(),
+                    return_asset: return_asset.clone(),
+                    original_msg: callback,
+                })?,
+            })
+            .add_attribute("action", "swap_callback")
+            .add_attribute("return_asset", return_asset.to_string())
+            .set_data(to_binary(&SwapResponse {
+                offer_asset,
+                return_asset,
+            })?))
+    }
+
+    /// Delivers the output of a route to the post action contract. Only the contract itself
+    /// can execute it.
+    pub fn execute_post_action(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        asset_info: AssetInfo,
+        prev_balance: Uint128,
+        post_action: PostAction,
+    ) -> Result<Response, ContractError> {
+        if info.sender != env.contract.address {
+            return Err(ContractError::Unauthorized {});
+        }
+
+        let balance = asset_info.query_pool(&deps.querier, &env.contract.address)?;
+        let amount = balance.checked_sub(prev_balance)?;
+
+        let message = match &asset_info {
+            AssetInfo::NativeToken { denom } => WasmMsg::Execute {
+                contract_addr: post_action.contract.clone(),
+                funds: coins(amount.u128(), denom),
+                msg: post_action.msg,
+            },
+            AssetInfo::Token { contract_addr } => WasmMsg::Execute {
+                contract_addr: contract_addr.to_string(),
+                funds: vec![],
+                msg: to_binary(&Cw20ExecuteMsg::Send {
+                    contract: post_action.contract.clone(),
+                    amount,
+                    msg: post_action.msg,
+                })?,
+            },
+        };
+
+        Ok(Response::new()
+            .add_message(message)
+            .add_attribute("action", "post_action")
+            .add_attribute("contract", post_action.contract)
+            .add_attribute("amount", format!("{amount}{asset_info}")))
+    }
+
+    /// This structure describes a route of a CW20 offer pulled from the owner's allowance.
+    pub struct UnxswapFromParams {
+        pub owner: String,
+        pub token: String,
+        pub amount: Uint128,
+        pub steps: Vec<SwapOperation>,
+        pub minimum_receive: Option<Uint128>,
+        pub to: Option<String>,
+        pub target_asset_info: AssetInfo,
+    }
+
+    /// Pulls a CW20 offer from the owner's allowance and routes it like [`unxswap`].
+    pub fn unxswap_from(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        params: UnxswapFromParams,
+    ) -> Result<Response, ContractError> {
+        let UnxswapFromParams {
+            owner,
+            token,
+            amount,
+            steps,
+            minimum_receive,
+            to,
+            target_asset_info,
+        } = params;
+
+        let owner = deps.api.addr_validate(&owner)?;
+        if info.sender != owner {
+            return Err(ContractError::Unauthorized {});
+        }
+        let token = deps.api.addr_validate(&token)?;
+        assert_route(&steps, &AssetInfo::Token { contract_addr: token.clone() })?;
+
+        let to = match to {
+            Some(to) => deps.api.addr_validate(&to)?,
+            None => owner.clone(),
+        };
+
+        let mut messages = vec![CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: token.to_string(),
+            funds: vec![],
+            msg: to_binary(&Cw20ExecuteMsg::TransferFrom {
+                owner: owner.to_string(),
+                recipient: env.contract.address.to_string(),
+                amount,
+            })?,
+        })];
+        messages.extend(route_messages(&env, steps, Some(amount), &to, &owner)?);
+        if let Some(minimum_receive) = minimum_receive {
+            messages.push(minimum_receive_message(
+                deps.as_ref(),
+                &env,
+                target_asset_info,
+                minimum_receive,
+                &to,
+            )?);
+        }
+
+        Ok(Response::new()
+            .add_messages(messages)
+            .add_attribute("action", "unxswap_from")
+            .add_attribute("owner", owner)
+            .add_attribute("offer_asset", format!("{amount}{token}"))
+            .add_attribute("receiver", to))
+    }
+
+    /// Routes several inputs into one target asset. Native inputs are attached as funds while
+    /// CW20 inputs are pulled from the sender's allowance.
+    pub fn consolidate(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        inputs: Vec<ConsolidateInput>,
+        target_asset_info: AssetInfo,
+        minimum_receive: Option<Uint128>,
+        to: Option<String>,
+    ) -> Result<Response, ContractError> {
+        if inputs.is_empty() {
+            return Err(ContractError::MustProvideOperations {});
+        }
+
+        let offer_assets = inputs
+            .iter()
+            .map(|input| input.asset.clone())
+            .collect::<Vec<_>>();
+        let mut messages = collect_offer(&info, &env, &offer_assets)?;
+
+        let to = match to {
+            Some(to) => deps.api.addr_validate(&to)?,
+            None => info.sender.clone(),
+        };
+
+        for input in &inputs {
+            assert_route(&input.steps, &input.asset.info)?;
+            assert_no_route_overlap(&input.steps, &offer_assets)?;
+        }
+
+        for input in inputs {
+            messages.extend(route_messages(
+                &env,
+                input.steps,
+                Some(input.asset.amount),
+                &to,
+                &info.sender,
+            )?);
+        }
+
+        // A single assertion covers the total received from every route
+        if let Some(minimum_receive) = minimum_receive {
+            messages.push(minimum_receive_message(
+                deps.as_ref(),
+                &env,
+                target_asset_info,
+                minimum_receive,
+                &to,
+            )?);
+        }
+
+        Ok(Response::new()
+            .add_messages(messages)
+            .add_attribute("action", "consolidate")
+            .add_attribute("inputs", offer_assets.len().to_string())
+            .add_attribute("receiver", to))
+    }
+
+    /// Splits one offer asset between several legs by weight and routes every leg to the receiver.
+    pub fn distribute(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        offer_asset: AstroportAsset,
+        legs: Vec<DistributeLeg>,
+        to: Option<String>,
+    ) -> Result<Response, ContractError> {
+        if legs.is_empty() {
+            return Err(ContractError::MustProvideOperations {});
+        }
+        let total_weight = legs
+            .iter()
+            .try_fold(0u16, |total, leg| {
+                if leg.weight_bps == 0 {
+                    None
+                } else {
+                    total.checked_add(leg.weight_bps)
+                }
+            });
+        if total_weight != Some(MAX_WEIGHT_BPS) {
+            return Err(ContractError::InvalidWeights {});
+        }
+        for (index, leg) in legs.iter().enumerate() {
+            assert_route(&leg.steps, &offer_asset.info)?;
+            assert_no_route_overlap(&leg.steps, slice::from_ref(&offer_asset))?;
+            // Minimum assertions compare receiver balances, so every leg needs its own target
+            if legs[..index]
+                .iter()
+                .any(|other| other.target_asset_info.equal(&leg.target_asset_info))
+            {
+                return Err(ContractError::DoublingAssets {});
+            }
+        }
+
+        let to = match to {
+            Some(to) => deps.api.addr_validate(&to)?,
+            None => info.sender.clone(),
+        };
+
+        let mut messages = collect_offer(&info, &env, slice::from_ref(&offer_asset))?;
+
+        // The last leg takes the rounding remainder
+        let legs_len = legs.len();
+        let mut remaining = offer_asset.amount;
+        let mut response = Response::new();
+        for (index, leg) in legs.into_iter().enumerate() {
+            let amount = if index + 1 == legs_len {
+                remaining
+            } else {
+                offer_asset
+                    .amount
+                    .multiply_ratio(leg.weight_bps, MAX_WEIGHT_BPS)
+            };
+            remaining = remaining.checked_sub(amount)?;
+            if amount.is_zero() {
+                return Err(ContractError::Std(StdError::generic_err(format!(
+                    "Leg {index} has nothing to swap"
+                ))));
+            }
+
+            response = response.add_attribute(
+                format!("leg_{index}"),
+                format!("{}{}", amount, leg.target_asset_info),
+            );
+            messages.extend(route_messages(
+                &env,
+                leg.steps,
+                Some(amount),
+                &to,
+                &info.sender,
+            )?);
+            if let Some(minimum_receive) = leg.minimum_receive {
+                messages.push(minimum_receive_message(
+                    deps.as_ref(),
+                    &env,
+                    leg.target_asset_info,
+                    minimum_receive,
+                    &to,
+                )?);
+            }
+        }
+
+        Ok(response
+            .add_messages(messages)
+            .add_attribute("action", "distribute")
+            .add_attribute("offer_asset", offer_asset.to_string())
+            .add_attribute("receiver", to))
+    }
+
+    /// This structure describes a zap of a single asset into a two-asset pool.
+    pub struct ZapInParams {
+        pub pool: String,
+        pub dex: String,
+        pub offer_asset: AstroportAsset,
+        pub swap_steps: Vec<SwapOperation>,
+        pub belief_price: Option<Decimal>,
+        pub slippage_tolerance: Option<Decimal>,
+        pub auto_stake: Option<bool>,
+        pub receiver: Option<String>,
+    }
+
+    /// Provides liquidity from a single asset. The offer is routed into one of the pool assets if
+    /// needed, the XYK-optimal part of it is swapped into the other pool asset and both are
+    /// provided. Unmatched amounts are refunded to the sender.
+    pub fn zap_in(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        params: ZapInParams,
+    ) -> Result<Response, ContractError> {
+        let ZapInParams {
+            pool,
+            dex,
+            offer_asset,
+            swap_steps,
+            belief_price,
+            slippage_tolerance,
+            auto_stake,
+            receiver,
+        } = params;
+
+        let pool = deps.api.addr_validate(&pool)?;
+        let receiver = match receiver {
+            Some(receiver) => deps.api.addr_validate(&receiver)?,
+            None => info.sender.clone(),
+        };
+        let pool_assets = query_pool_assets(deps.as_ref(), &pool, &dex)?;
+
+        let asset_info = match swap_steps.last() {
+            None => offer_asset.info.clone(),
+            Some(operation) => {
+                assert_route(&swap_steps, &offer_asset.info)?;
+                operation_ask_asset_info(deps.as_ref(), operation)?
+            }
+        };
+        if !pool_assets.iter().any(|asset| asset.info.equal(&asset_info)) {
+            return Err(ContractError::AssetNotInPool {
+                asset: asset_info.to_string(),
+            });
+        }
+        if !swap_steps.is_empty()
+            && pool_assets
+                .iter()
+                .any(|asset| asset.info.equal(&offer_asset.info))
+        {
+            return Err(ContractError::Std(StdError::generic_err(
+                "Pool assets can be zapped in without swap steps",
+            )));
+        }
+
+        let mut messages = collect_offer(&info, &env, slice::from_ref(&offer_asset))?;
+        // Native offers are already part of the contract balance
+        let mut prev_balance = asset_info.query_pool(&deps.querier, &env.contract.address)?;
+        if offer_asset.is_native_token() && swap_steps.is_empty() {
+            prev_balance = prev_balance.checked_sub(offer_asset.amount)?;
+        }
+        if !swap_steps.is_empty() {
+            messages.extend(route_messages(
+                &env,
+                swap_steps,
+                Some(offer_asset.amount),
+                &env.contract.address,
+                &info.sender,
+            )?);
+        }
+
+        messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: env.contract.address.to_string(),
+            funds: vec![],
+            msg: to_binary(&ExecuteMsg::ZapInSwap {
+                pool: pool.to_string(),
+                dex,
+                asset_info,
+                prev_balance,
+                belief_price,
+                slippage_tolerance,
+                auto_stake,
+                receiver: receiver.to_string(),
+                refund_to: info.sender.to_string(),
+            })?,
+        }));
+
+        Ok(Response::new()
+            .add_messages(messages)
+            .add_attribute("action", "zap_in")
+            .add_attribute("pool", pool)
+            .add_attribute("offer_asset", offer_asset.to_string())
+            .add_attribute("receiver", receiver))
+    }
+
+    /// This structure describes the swap of part of a zap into the other pool asset.
+    pub struct ZapInSwapParams {
+        pub pool: String,
+        pub dex: String,
+        pub asset_info: AssetInfo,
+        pub prev_balance: Uint128,
+        pub belief_price: Option<Decimal>,
+        pub slippage_tolerance: Option<Decimal>,
+        pub auto_stake: Option<bool>,
+        pub receiver: String,
+        pub refund_to: String,
+    }
+
+    /// Swaps the XYK-optimal part of a zap into the other pool asset. Only the contract itself
+    /// can execute it.
+    pub fn zap_in_swap(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        params: ZapInSwapParams,
+    ) -> Result<Response, ContractError> {
+        let ZapInSwapParams {
+            pool,
+            dex,
+            asset_info,
+            prev_balance,
+            belief_price,
+            slippage_tolerance,
+            auto_stake,
+            receiver,
+            refund_to,
+        } = params;
+
+        if info.sender != env.contract.address {
+            return Err(ContractError::Unauthorized {});
+        }
+
+        let pool = deps.api.addr_validate(&pool)?;
+        let amount = asset_info
+            .query_pool(&deps.querier, &env.contract.address)?
+            .checked_sub(prev_balance)?;
+        let pool_assets = query_pool_assets(deps.as_ref(), &pool, &dex)?;
+        let reserve = pool_assets
+            .iter()
+            .find(|asset| asset.info.equal(&asset_info))
+            .map(|asset| asset.amount)
+            .ok_or_else(|| ContractError::AssetNotInPool {
+                asset: asset_info.to_string(),
+            })?;
+        let other_asset_info = pool_assets
+            .iter()
+            .find(|asset| !asset.info.equal(&asset_info))
+            .map(|asset| asset.info.clone())
+            .ok_or_else(|| StdError::generic_err("Pool must contain two assets"))?;
+        check_swap_parameters(
+            pool_assets.iter().map(|asset| asset.amount).collect(),
+            amount,
+        )?;
+
+        let fee = query_fee_rate(deps.as_ref(), &pool, &dex, &asset_info, reserve, amount)?;
+        let swap_amount = optimal_swap_amount(reserve, amount, fee)?;
+
+        // The slippage tolerance of the provision also bounds the swap into the other asset
+        let mut messages: Vec<CosmosMsg> = vec![];
+        if !swap_amount.is_zero() {
+            messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: env.contract.address.to_string(),
+                funds: vec![],
+                msg: to_binary(&ExecuteMsg::DexSwap {
+                    dex: dex.clone(),
+                    pool_address: pool.to_string(),
+                    offer_asset_info: asset_info.clone(),
+                    offer_amount: Some(swap_amount),
+                    ask_asset_info: Some(other_asset_info.clone()),
+                    belief_price,
+                    max_spread: slippage_tolerance,
+                    to: None,
+                })?,
+            }));
+        }
+
+        messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: env.contract.address.to_string(),
+            funds: vec![],
+            msg: to_binary(&ExecuteMsg::ZapInProvide {
+                pool: pool.to_string(),
+                dex,
+                asset: AstroportAsset {
+                    info: asset_info,
+                    amount: amount - swap_amount,
+                },
+                other_asset_info: other_asset_info.clone(),
+                other_prev_balance: other_asset_info
+                    .query_pool(&deps.querier, &env.contract.address)?,
+                slippage_tolerance,
+                auto_stake,
+                receiver,
+                refund_to,
+            })?,
+        }));
+
+        Ok(Response::new()
+            .add_messages(messages)
+            .add_attribute("action", "zap_in_swap")
+            .add_attribute("swap_amount", swap_amount))
+    }
+
+    /// This structure describes the liquidity provided with the zapped asset and the swap output.
+    pub struct ZapInProvideParams {
+        pub pool: String,
+        pub dex: String,
+        pub asset: AstroportAsset,
+        pub other_asset_info: AssetInfo,
+        pub other_prev_balance: Uint128,
+        pub slippage_tolerance: Option<Decimal>,
+        pub auto_stake: Option<bool>,
+        pub receiver: String,
+        pub refund_to: String,
+    }
+
+    /// Provides the balanced zap amounts to the pool and refunds the rest. Only the contract
+    /// itself can execute it.
+    pub fn zap_in_provide(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        params: ZapInProvideParams,
+    ) -> Result<Response, ContractError> {
+        let ZapInProvideParams {
+            pool,
+            dex,
+            asset,
+            other_asset_info,
+            other_prev_balance,
+            slippage_tolerance,
+            auto_stake,
+            receiver,
+            refund_to,
+        } = params;
+
+        if info.sender != env.contract.address {
+            return Err(ContractError::Unauthorized {});
+        }
+
+        let pool = deps.api.addr_validate(&pool)?;
+        let other_amount = other_asset_info
+            .query_pool(&deps.querier, &env.contract.address)?
+            .checked_sub(other_prev_balance)?;
+        let pool_assets = query_pool_assets(deps.as_ref(), &pool, &dex)?;
+        let reserve_of = |asset_info: &AssetInfo| -> Result<Uint128, ContractError> {
+            pool_assets
+                .iter()
+                .find(|pool_asset| pool_asset.info.equal(asset_info))
+                .map(|pool_asset| pool_asset.amount)
+                .ok_or_else(|| ContractError::AssetNotInPool {
+                    asset: asset_info.to_string(),
+                })
+        };
+        let reserve = reserve_of(&asset.info)?;
+        let other_reserve = reserve_of(&other_asset_info)?;
+
+        // Only provide the amounts that match the pool ratio
+        let (amount, other_amount_provided) =
+            if asset.amount.full_mul(other_reserve) > other_amount.full_mul(reserve) {
+                (other_amount.multiply_ratio(reserve, other_reserve), other_amount)
+            } else {
+                (asset.amount, asset.amount.multiply_ratio(other_reserve, reserve))
+            };
+        let assets = vec![
+            AstroportAsset {
+                info: asset.info.clone(),
+                amount,
+            },
+            AstroportAsset {
+                info: other_asset_info.clone(),
+                amount: other_amount_provided,
+            },
+        ];
+
+        let mut response = Response::new();
+        for dust in [
+            AstroportAsset {
+                info: asset.info.clone(),
+                amount: asset.amount - amount,
+            },
+            AstroportAsset {
+                info: other_asset_info,
+                amount: other_amount - other_amount_provided,
+            },
+        ] {
+            if !dust.amount.is_zero() {
+                response = response
+                    .add_attribute("refund", dust.to_string())
+                    .add_message(dust.into_msg(&refund_to)?);
+            }
+        }
+
+        Ok(response
+            .add_messages(provide_liquidity_messages(
+                &pool,
+                &dex,
+                assets,
+                slippage_tolerance,
+                auto_stake,
+                Some(receiver.clone()),
+            )?)
+            .add_attribute("action", "zap_in_provide")
+            .add_attribute("receiver", receiver))
+    }
+
+    /// Returns the assets of a two-asset pool on the given DEX.
+    fn query_pool_assets(
+        deps: Deps,
+        pool: &Addr,
+        dex: &str,
+    ) -> Result<Vec<AstroportAsset>, ContractError> {
+        Ok(registered_adapter(dex)?.pool(&deps.querier, pool.as_str())?)
+    }
+
+    /// Derives the pool's commission rate from a swap simulation.
+    fn query_fee_rate(
+        deps: Deps,
+        pool: &Addr,
+        dex: &str,
+        asset_info: &AssetInfo,
+        reserve: Uint128,
+        amount: Uint128,
+    ) -> Result<Decimal, ContractError> {
+        let offer_asset = AstroportAsset {
+            info: asset_info.clone(),
+            amount: optimal_swap_amount(reserve, amount, Decimal::zero())?.max(Uint128::new(1)),
+        };
+        let res = registered_adapter(dex)?.simulate(&deps.querier, pool.as_str(), &offer_asset, None)?;
+
+        let total = res.return_amount + res.commission_amount;
+        if total.is_zero() {
+            return Ok(Decimal::zero());
+        }
+        Ok(Decimal::from_ratio(res.commission_amount, total))
+    }
+
+    /// Returns the amount to swap so that the rest of `amount` and the swap return match the
+    /// XYK pool ratio after the swap:
+    /// (sqrt(R * (R * (2 - f)^2 + 4 * (1 - f) * a)) - R * (2 - f)) / (2 * (1 - f))
+    fn optimal_swap_amount(reserve: Uint128, amount: Uint128, fee: Decimal) -> StdResult<Uint128> {
+        let precision = Uint256::from(FEE_PRECISION);
+        let fee = Uint256::from(fee * Uint128::from(FEE_PRECISION));
+        if fee >= precision {
+            return Err(StdError::generic_err("Invalid pool fee"));
+        }
+        let reserve = Uint256::from(reserve);
+        let amount = Uint256::from(amount);
+
+        let two_minus_fee = precision + precision - fee;
+        let one_minus_fee = precision - fee;
+        let b = reserve * two_minus_fee;
+        let discriminant = reserve
+            * (reserve * two_minus_fee * two_minus_fee
+                + Uint256::from(4u8) * one_minus_fee * precision * amount);
+        let swap_amount = (isqrt(discriminant) - b) / (Uint256::from(2u8) * one_minus_fee);
+
+        Ok(Uint128::try_from(swap_amount)?.min(amount.try_into()?))
+    }
+
+    fn isqrt(value: Uint256) -> Uint256 {
+        if value.is_zero() {
+            return value;
+        }
+        let mut x = value;
+        let mut y = (x + Uint256::from(1u8)) / Uint256::from(2u8);
+        while y < x {
+            x = y;
+            y = (x + value / x) / Uint256::from(2u8);
+        }
+        x
+    }
+
+    /// Builds the messages providing `assets` to a pool. CW20 assets are approved first and
+    /// native assets are attached as funds.
+    fn provide_liquidity_messages(
+        pool: &Addr,
+        dex: &str,
+        assets: Vec<AstroportAsset>,
+        slippage_tolerance: Option<Decimal>,
+        auto_stake: Option<bool>,
+        receiver: Option<String>,
+    ) -> Result<Vec<CosmosMsg>, ContractError> {
+        let adapter = registered_adapter(dex)?;
+        let mut messages: Vec<CosmosMsg> = vec![];
+        for asset in &assets {
+            if let AssetInfo::Token { contract_addr } = &asset.info {
+                messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
+                    contract_addr: contract_addr.to_string(),
+                    funds: vec![],
+                    msg: to_binary(&Cw20ExecuteMsg::IncreaseAllowance {
+                        spender: pool.to_string(),
+                        amount: asset.amount,
+                        expires: None,
+                    })?,
+                }));
+            }
+        }
+        messages.push(adapter.provide_liquidity_msg(
+            pool.as_str(),
+            assets,
+            slippage_tolerance,
+            auto_stake,
+            receiver,
+        )?);
+
+        Ok(messages)
+    }
+
+    /// Handles the CW20 hook messages of the router.
+    pub fn receive_cw20(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        cw20_msg: Cw20ReceiveMsg,
+    ) -> Result<Response, ContractError> {
+        match from_binary(&cw20_msg.msg)? {
+            Cw20HookMsg::ZapOut {
+                dex,
+                target_asset_info,
+                steps_per_asset,
+                minimum_receive,
+                to,
+            } => zap_out(
+                deps,
+                env,
+                info,
+                ZapOutParams {
+                    sender: cw20_msg.sender,
+                    amount: cw20_msg.amount,
+                    dex,
+                    target_asset_info,
+                    steps_per_asset,
+                    minimum_receive,
+                    to,
+                },
+            ),
+            Cw20HookMsg::DepositCompound {} => {
+                deposit_compound(deps, env, info, cw20_msg.sender, cw20_msg.amount)
+            }
+            Cw20HookMsg::CommitSwap { hash } => {
+                let owner = deps.api.addr_validate(&cw20_msg.sender)?;
+                let offer_asset = AstroportAsset {
+                    info: AssetInfo::Token {
+                        contract_addr: info.sender,
+                    },
+                    amount: cw20_msg.amount,
+                };
+                commit_swap(deps, env, owner, offer_asset, hash)
+            }
+            Cw20HookMsg::SubmitAuctionOrder {
+                pool,
+                dex,
+                max_spread,
+            } => {
+                let owner = deps.api.addr_validate(&cw20_msg.sender)?;
+                let offer_asset = AstroportAsset {
+                    info: AssetInfo::Token {
+                        contract_addr: info.sender,
+                    },
+                    amount: cw20_msg.amount,
+                };
+                submit_auction_order(deps, env, owner, offer_asset, pool, dex, max_spread)
+            }
+        }
+    }
+
+    /// This structure describes a withdrawal of LP tokens routed into a single target asset.
+    pub struct ZapOutParams {
+        pub sender: String,
+        pub amount: Uint128,
+        pub dex: String,
+        pub target_asset_info: AssetInfo,
+        pub steps_per_asset: Vec<Vec<SwapOperation>>,
+        pub minimum_receive: Option<Uint128>,
+        pub to: Option<String>,
+    }
+
+    /// Withdraws the received LP tokens from their pair and routes every returned asset to the
+    /// target asset.
+    pub fn zap_out(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        params: ZapOutParams,
+    ) -> Result<Response, ContractError> {
+        let ZapOutParams {
+            sender,
+            amount,
+            dex,
+            target_asset_info,
+            steps_per_asset,
+            minimum_receive,
+            to,
+        } = params;
+
+        // The LP token is minted by its pair
+        let minter: Option<MinterResponse> = deps
+            .querier
+            .query_wasm_smart(&info.sender, &Cw20QueryMsg::Minter {})?;
+        let pair = minter
+            .map(|minter| deps.api.addr_validate(&minter.minter))
+            .transpose()?
+            .ok_or(ContractError::Unauthorized {})?;
+        let adapter = registered_adapter(&dex)?;
+        let pair_info = adapter.pair_info(&deps.querier, pair.as_str())?;
+        if pair_info.liquidity_token != info.sender {
+            return Err(ContractError::Unauthorized {});
+        }
+
+        let to = match to {
+            Some(to) => deps.api.addr_validate(&to)?,
+            None => deps.api.addr_validate(&sender)?,
+        };
+
+        let pool_assets = query_pool_assets(deps.as_ref(), &pair, &dex)?;
+        for steps in &steps_per_asset {
+            let offer_asset_info = steps
+                .first()
+                .map(operation_offer_asset_info)
+                .ok_or(ContractError::MustProvideOperations {})?;
+            if !pool_assets
+                .iter()
+                .any(|asset| asset.info.equal(&offer_asset_info))
+            {
+                return Err(ContractError::AssetNotInPool {
+                    asset: offer_asset_info.to_string(),
+                });
+            }
+            assert_no_route_overlap(steps, &pool_assets)?;
+        }
+        for asset in pool_assets
+            .iter()
+            .filter(|asset| !asset.info.equal(&target_asset_info))
+        {
+            let routes = steps_per_asset
+                .iter()
+                .filter(|steps| operation_offer_asset_info(&steps[0]).equal(&asset.info))
+                .count();
+            if routes != 1 {
+                return Err(ContractError::InvalidRouteOfferAsset {
+                    asset: asset.info.to_string(),
+                });
+            }
+        }
+
+        let prev_balances = pool_assets
+            .iter()
+            .map(|asset| {
+                Ok(AstroportAsset {
+                    info: asset.info.clone(),
+                    amount: asset.info.query_pool(&deps.querier, &env.contract.address)?,
+                })
+            })
+            .collect::<StdResult<Vec<_>>>()?;
+
+        let mut messages = vec![
+            adapter.withdraw_liquidity_msg(pair.as_str(), info.sender.as_str(), amount)?,
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: env.contract.address.to_string(),
+                funds: vec![],
+                msg: to_binary(&ExecuteMsg::ZapOutRoute {
+                    prev_balances,
+                    target_asset_info: target_asset_info.clone(),
+                    steps_per_asset,
+                    to: to.to_string(),
+                    sender,
+                })?,
+            }),
+        ];
+
+        // A single assertion covers the total received from every withdrawn asset
+        if let Some(minimum_receive) = minimum_receive {
+            messages.push(minimum_receive_message(
+                deps.as_ref(),
+                &env,
+                target_asset_info,
+                minimum_receive,
+                &to,
+            )?);
+        }
+
+        Ok(Response::new()
+            .add_messages(messages)
+            .add_attribute("action", "zap_out")
+            .add_attribute("pair", pair)
+            .add_attribute("amount", amount)
+            .add_attribute("receiver", to))
+    }
+
+    /// This structure describes the routes of the withdrawn pool assets into the target asset.
+    pub struct ZapOutRouteParams {
+        pub prev_balances: Vec<AstroportAsset>,
+        pub target_asset_info: AssetInfo,
+        pub steps_per_asset: Vec<Vec<SwapOperation>>,
+        pub to: String,
+        pub sender: String,
+    }
+
+    /// Routes the withdrawn pool assets to the receiver. Only the contract itself can execute it.
+    pub fn zap_out_route(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        params: ZapOutRouteParams,
+    ) -> Result<Response, ContractError> {
+        let ZapOutRouteParams {
+            prev_balances,
+            target_asset_info,
+            steps_per_asset,
+            to,
+            sender,
+        } = params;
+
+        if info.sender != env.contract.address {
+            return Err(ContractError::Unauthorized {});
+        }
+
+        let to = deps.api.addr_validate(&to)?;
+        let sender = deps.api.addr_validate(&sender)?;
+        let mut response = Response::new();
+        let mut steps_per_asset = steps_per_asset;
+        for prev_balance in prev_balances {
+            let withdrawn = AstroportAsset {
+                amount: prev_balance
+                    .info
+                    .query_pool(&deps.querier, &env.contract.address)?
+                    .checked_sub(prev_balance.amount)?,
+                info: prev_balance.info,
+            };
+            response = response.add_attribute("withdrawn", withdrawn.to_string());
+            if withdrawn.amount.is_zero() {
+                continue;
+            }
+
+            if withdrawn.info.equal(&target_asset_info) {
+                response = response.add_message(withdrawn.into_msg(&to)?);
+            } else {
+                let position = steps_per_asset
+                    .iter()
+                    .position(|steps| operation_offer_asset_info(&steps[0]).equal(&withdrawn.info))
+                    .ok_or_else(|| ContractError::InvalidRouteOfferAsset {
+                        asset: withdrawn.info.to_string(),
+                    })?;
+                response = response.add_messages(route_messages(
+                    &env,
+                    steps_per_asset.remove(position),
+                    Some(withdrawn.amount),
+                    &to,
+                    &sender,
+                )?);
+            }
+        }
+
+        Ok(response.add_attribute("action", "zap_out_route"))
+    }
+
+    pub fn batch_unxswap(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        swaps: Vec<UnxswapParams>,
+        atomic: bool,
+    ) -> Result<Response, ContractError> {
+        if swaps.is_empty() {
+            return Err(ContractError::MustProvideOperations {});
+        }
+
+        let offer_assets = swaps
+            .iter()
+            .map(|swap| swap.offer_asset.clone())
+            .collect::<Vec<_>>();
+        if offer_assets.iter().any(|asset| !asset.is_native_token()) {
+            return Err(ContractError::NativeOfferRequired {});
+        }
+        assert_sent_funds(&info, &offer_assets)?;
+
+        let mut pending = Vec::with_capacity(swaps.len());
+        let mut sub_messages = Vec::with_capacity(swaps.len());
+        for (index, swap) in swaps.into_iter().enumerate() {
+            assert_route(&swap.steps, &swap.offer_asset.info)?;
+            assert_no_route_overlap(&swap.steps, &offer_assets)?;
+
+            let to = match swap.to {
+                Some(to) => deps.api.addr_validate(to.as_str())?,
+                None => info.sender.clone(),
+            };
+            pending.push(PendingBatchSwap {
+                index: index as u64,
+                offer_asset: swap.offer_asset.clone(),
+                target_asset_info: swap.target_asset_info.clone(),
+                receiver: to.clone(),
+                refund_to: info.sender.clone(),
+                prev_balance: Uint128::zero(),
+            });
+
+            let msg = WasmMsg::Execute {
+                contract_addr: env.contract.address.to_string(),
+                funds: vec![],
+                msg: to_binary(&ExecuteMsg::ExecuteRoute {
+                    offer_asset: swap.offer_asset,
+                    steps: swap.steps,
+                    minimum_receive: swap.minimum_receive,
+                    to: to.to_string(),
+                    target_asset_info: swap.target_asset_info,
+                })?,
+            };
+            sub_messages.push(if atomic {
+                SubMsg::reply_on_success(msg, BATCH_SWAP_REPLY_ID)
+            } else {
+                SubMsg::reply_always(msg, BATCH_SWAP_REPLY_ID)
+            });
+        }
+        PENDING_BATCH_SWAPS.save(deps.storage, &pending)?;
+
+        Ok(Response::new()
+            .add_submessages(sub_messages)
+            .add_attribute("action", "batch_unxswap")
+            .add_attribute("swaps", pending.len().to_string())
+            .add_attribute("atomic", atomic.to_string()))
+    }
+
+    /// This structure describes a route of an offer already held by the contract.
+    pub struct ExecuteRouteParams {
+        pub offer_asset: AstroportAsset,
+        pub steps: Vec<SwapOperation>,
+        pub minimum_receive: Option<Uint128>,
+        pub to: Addr,
+        pub target_asset_info: AssetInfo,
+    }
+
+    /// Runs a single route on behalf of a batch. Only the contract itself can execute it.
+    pub fn execute_route(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        params: ExecuteRouteParams,
+    ) -> Result<Response, ContractError> {
+        let ExecuteRouteParams {
+            offer_asset,
+            steps,
+            minimum_receive,
+            to,
+            target_asset_info,
+        } = params;
+
+        if info.sender != env.contract.address {
+            return Err(ContractError::Unauthorized {});
+        }
+
+        // Remember the receiver balance so that the reply can report the amount received. The
+        // owner of the offer asset takes the RFQ quotes of the route.
+        let mut pending = PENDING_BATCH_SWAPS.load(deps.storage)?;
+        let swap = pending
+            .first_mut()
+            .ok_or_else(|| StdError::generic_err("No pending batch swap"))?;
+        swap.prev_balance = target_asset_info.query_pool(&deps.querier, &to)?;
+        let refund_to = swap.refund_to.clone();
+        PENDING_BATCH_SWAPS.save(deps.storage, &pending)?;
+
+        let mut messages =
+            route_messages(&env, steps, Some(offer_asset.amount), &to, &refund_to)?;
+        if let Some(minimum_receive) = minimum_receive {
+            messages.push(minimum_receive_message(
+                deps.as_ref(),
+                &env,
+                target_asset_info,
+                minimum_receive,
+                &to,
+            )?);
+        }
+
+        Ok(Response::new().add_messages(messages))
+    }
+
+    /// Builds the self-calls that execute `steps` one hop at a time and deliver the output of
+    /// the last hop to `to`. The first hop swaps `offer_amount` if it is set and the whole
+    /// contract balance of the offer asset otherwise. RFQ quotes must be made out to `taker`.
+    fn route_messages(
+        env: &Env,
+        steps: Vec<SwapOperation>,
+        offer_amount: Option<Uint128>,
+        to: &Addr,
+        taker: &Addr,
+    ) -> Result<Vec<CosmosMsg>, ContractError> {
+        if steps.is_empty() {
+            return Err(ContractError::MustProvideOperations {});
+        }
+        for operation in &steps {
+            if let SwapOperation::Rfq { quote, .. } = operation {
+                if quote.taker != *taker {
+                    return Err(ContractError::RfqTakerMismatch {
+                        taker: taker.to_string(),
+                    });
+                }
+            }
+        }
+
+        let operations_len = steps.len();
+        let messages = steps
+            .into_iter()
+            .enumerate()
+            .map(|(operation_index, op)| {
+                let offer_amount = if operation_index == 0 {
+                    offer_amount
+                } else {
+                    None
+                };
+                let to = if operation_index + 1 == operations_len {
+                    Some(to.to_string())
+                } else {
+                    None
+                };
+                let msg = match op {
+                    SwapOperation::SparrowSwap {
+                        pool_address,
+                        offer_asset_info,
+                        belief_price,
+                        max_spread,
+                    } => ExecuteMsg::SparrowSwap {
+                        pool_address,
+                        offer_asset_info,
+                        offer_amount,
+                        belief_price,
+                        max_spread,
+                        to,
+                    },
+                    SwapOperation::AstroportSwap {
+                        pool_address,
+                        offer_asset_info,
+                        ask_asset_info,
+                        belief_price,
+                        max_spread,
+                    } => ExecuteMsg::AstroportSwap {
+                        pool_address,
+                        offer_asset_info,
+                        offer_amount,
+                        ask_asset_info,
+                        belief_price,
+                        max_spread,
+                        to,
+                    },
+                    SwapOperation::Swap {
+                        dex,
+                        pool_address,
+                        offer_asset_info,
+                        ask_asset_info,
+                        belief_price,
+                        max_spread,
+                    } => ExecuteMsg::DexSwap {
+                        dex,
+                        pool_address,
+                        offer_asset_info,
+                        offer_amount,
+                        ask_asset_info,
+                        belief_price,
+                        max_spread,
+                        to,
+                    },
+                    SwapOperation::Rfq {
+                        maker,
+                        quote,
+                        signature,
+                    } => ExecuteMsg::RfqSwap {
+                        maker,
+                        quote,
+                        signature,
+                        offer_amount,
+                        to,
+                    },
+                };
+                Ok(CosmosMsg::Wasm(WasmMsg::Execute {
+                    contract_addr: env.contract.address.to_string(),
+                    funds: vec![],
+                    msg: to_binary(&msg)?,
+                }))
+            })
+            .collect::<StdResult<Vec<CosmosMsg>>>()?;
+
+        Ok(messages)
+    }
+
+    fn minimum_receive_message(
+        deps: Deps,
+        env: &Env,
+        target_asset_info: AssetInfo,
+        minimum_receive: Uint128,
+        to: &Addr,
+    ) -> StdResult<CosmosMsg> {
+        let receiver_balance = target_asset_info.query_pool(&deps.querier, to)?;
+        Ok(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: env.contract.address.to_string(),
+            funds: vec![],
+            msg: to_binary(&ExecuteMsg::AssertMinimumReceive {
+                asset_info: target_asset_info,
+                prev_balance: receiver_balance,
+                minimum_receive,
+                receiver: to.to_string(),
+            })?,
+        }))
+    }
+
+    fn operation_offer_asset_info(operation: &SwapOperation) -> AssetInfo {
+        match operation {
+            SwapOperation::SparrowSwap {
+                offer_asset_info, ..
+            } => AssetInfo::from(offer_asset_info),
+            SwapOperation::AstroportSwap {
+                offer_asset_info, ..
+            }
+            | SwapOperation::Swap {
+                offer_asset_info, ..
+            } => offer_asset_info.clone(),
+            SwapOperation::Rfq { quote, .. } => quote.offer_asset_info.clone(),
+        }
+    }
+
+    /// Returns the asset a hop swaps into, querying the pair if the operation doesn't specify it.
+    fn operation_ask_asset_info(
+        deps: Deps,
+        operation: &SwapOperation,
+    ) -> Result<AssetInfo, ContractError> {
+        let offer_asset_info = operation_offer_asset_info(operation);
+        match operation {
+            SwapOperation::SparrowSwap { pool_address, .. } => {
+                Ok(registered_adapter(SPARROWSWAP)?.ask_asset_info(
+                    &deps.querier,
+                    pool_address,
+                    &offer_asset_info,
+                )?)
+            }
+            SwapOperation::AstroportSwap {
+                ask_asset_info: Some(ask_asset_info),
+                ..
+            }
+            | SwapOperation::Swap {
+                ask_asset_info: Some(ask_asset_info),
+                ..
+            } => Ok(ask_asset_info.clone()),
+            SwapOperation::AstroportSwap {
+                pool_address,
+                ask_asset_info: None,
+                ..
+            } => Ok(registered_adapter(ASTROPORT)?.ask_asset_info(
+                &deps.querier,
+                pool_address,
+                &offer_asset_info,
+            )?),
+            SwapOperation::Swap {
+                dex,
+                pool_address,
+                ask_asset_info: None,
+                ..
+            } => Ok(registered_adapter(dex)?.ask_asset_info(
+                &deps.querier,
+                pool_address,
+                &offer_asset_info,
+            )?),
+            SwapOperation::Rfq { quote, .. } => Ok(quote.ask_asset_info.clone()),
+        }
+    }
+
+    /// Returns the adapter registered under a DEX id.
+    fn registered_adapter(dex: &str) -> Result<&'static dyn DexAdapter, ContractError> {
+        dex_adapter(dex).ok_or_else(|| ContractError::UnknownDex {
+            dex: dex.to_string(),
+        })
+    }
+
+    /// Checks that a route is not empty and starts with the offered asset.
+    fn assert_route(steps: &[SwapOperation], offer_asset_info: &AssetInfo) -> Result<(), ContractError> {
+        match steps.first() {
+            None => Err(ContractError::MustProvideOperations {}),
+            Some(operation) if !operation_offer_asset_info(operation).equal(offer_asset_info) => {
+                Err(ContractError::InvalidRouteOfferAsset {
+                    asset: offer_asset_info.to_string(),
+                })
+            }
+            Some(_) => Ok(()),
+        }
+    }
+
+    /// Intermediate hops swap the whole contract balance of their offer asset, so they must not
+    /// touch assets that are still held for other routes in the same message.
+    fn assert_no_route_overlap(
+        steps: &[SwapOperation],
+        offer_assets: &[AstroportAsset],
+    ) -> Result<(), ContractError> {
+        for operation in steps.iter().skip(1) {
+            let asset_info = operation_offer_asset_info(operation);
+            if offer_assets.iter().any(|asset| asset.info.equal(&asset_info)) {
+                return Err(ContractError::RouteOverlap {
+                    asset: asset_info.to_string(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that the attached native funds match the native offer assets exactly.
+    fn assert_sent_funds(info: &MessageInfo, offer_assets: &[AstroportAsset]) -> Result<(), ContractError> {
+        let mut totals: Vec<AstroportAsset> = vec![];
+        for asset in offer_assets.iter().filter(|asset| asset.is_native_token()) {
+            match totals.iter_mut().find(|total| total.info.equal(&asset.info)) {
+                Some(total) => total.amount = total.amount.checked_add(asset.amount)?,
+                None => totals.push(asset.clone()),
+            }
+        }
+
+        let asset_infos = totals.iter().map(|asset| asset.info.clone()).collect::<Vec<_>>();
+        info.funds.assert_coins_properly_sent(&totals, &asset_infos)?;
+        for total in &totals {
+            if !info.funds.iter().any(|coin| coin.denom == total.info.to_string()) {
+                return Err(ContractError::Std(StdError::generic_err(format!(
+                    "Must send reserve token '{}'",
+                    total.info
+                ))));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks the attached native offers and returns the messages that pull the CW20 offers from
+    /// the sender's allowance.
+    fn collect_offer(
+        info: &MessageInfo,
+        env: &Env,
+        offer_assets: &[AstroportAsset],
+    ) -> Result<Vec<CosmosMsg>, ContractError> {
+        assert_sent_funds(info, offer_assets)?;
+
+        let mut messages = vec![];
+        for asset in offer_assets {
+            if let AssetInfo::Token { contract_addr } = &asset.info {
+                messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
+                    contract_addr: contract_addr.to_string(),
+                    funds: vec![],
+                    msg: to_binary(&Cw20ExecuteMsg::TransferFrom {
+                        owner: info.sender.to_string(),
+                        recipient: env.contract.address.to_string(),
+                        amount: asset.amount,
+                    })?,
+                }));
+            }
+        }
+        Ok(messages)
+    }
+
+    pub fn set_pair_oracle(
+        deps: DepsMut,
+        info: MessageInfo,
+        asset_infos: [AssetInfo; 2],
+        oracle: String,
+        max_deviation: Decimal,
+    ) -> Result<Response, ContractError> {
+        assert_owner(deps.as_ref(), &info)?;
+
+        for asset_info in &asset_infos {
+            asset_info.check(deps.api)?;
+        }
+        if asset_infos[0].equal(&asset_infos[1]) {
+            return Err(ContractError::DoublingAssets {});
+        }
+        if max_deviation > Decimal::one() {
+            return Err(ContractError::InvalidMaxDeviation {});
+        }
+
+        let oracle = deps.api.addr_validate(&oracle)?;
+        PAIR_ORACLES.save(
+            deps.storage,
+            pair_key(&asset_infos[0], &asset_infos[1]),
+            &PairOracle {
+                oracle: oracle.clone(),
+                max_deviation,
+            },
+        )?;
+
+        Ok(Response::new()
+            .add_attribute("action", "set_pair_oracle")
+            .add_attribute("pair", format!("{}-{}", asset_infos[0], asset_infos[1]))
+            .add_attribute("oracle", oracle)
+            .add_attribute("max_deviation", max_deviation.to_string()))
+    }
+
+    pub fn remove_pair_oracle(
+        deps: DepsMut,
+        info: MessageInfo,
+        asset_infos: [AssetInfo; 2],
+    ) -> Result<Response, ContractError> {
+        assert_owner(deps.as_ref(), &info)?;
+
+        PAIR_ORACLES.remove(deps.storage, pair_key(&asset_infos[0], &asset_infos[1]));
+
+        Ok(Response::new()
+            .add_attribute("action", "remove_pair_oracle")
+            .add_attribute("pair", format!("{}-{}", asset_infos[0], asset_infos[1])))
+    }
+
+    pub fn set_keeper_tip(
+        deps: DepsMut,
+        info: MessageInfo,
+        tip_bps: u16,
+    ) -> Result<Response, ContractError> {
+        assert_owner(deps.as_ref(), &info)?;
+
+        if tip_bps > MAX_KEEPER_TIP_BPS {
+            return Err(ContractError::InvalidKeeperTip {
+                max_tip_bps: MAX_KEEPER_TIP_BPS,
+            });
+        }
+        KEEPER_TIP_BPS.save(deps.storage, &tip_bps)?;
+
+        Ok(Response::new()
+            .add_attribute("action", "set_keeper_tip")
+            .add_attribute("tip_bps", tip_bps.to_string()))
+    }
+
+    /// Escrows the offer asset of a new limit order.
+    pub fn place_limit_order(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        offer_asset: AstroportAsset,
+        steps: Vec<SwapOperation>,
+        min_price: Decimal,
+        expiry: Option<Expiration>,
+    ) -> Result<Response, ContractError> {
+        offer_asset.info.check(deps.api)?;
+        if offer_asset.amount.is_zero() {
+            return Err(ContractError::Std(StdError::generic_err(
+                "Offer amount must be positive",
+            )));
+        }
+        assert_route(&steps, &offer_asset.info)?;
+        if let Some(expiry) = &expiry {
+            if expiry.is_expired(&env.block) {
+                return Err(ContractError::Std(StdError::generic_err(
+                    "Expiry is already reached",
+                )));
+            }
+        }
+        let target_asset_info = operation_ask_asset_info(
+            deps.as_ref(),
+            steps.last().ok_or(ContractError::MustProvideOperations {})?,
+        )?;
+
+        let messages = collect_offer(&info, &env, slice::from_ref(&offer_asset))?;
+
+        let id = LAST_ORDER_ID.may_load(deps.storage)?.unwrap_or_default() + 1;
+        LAST_ORDER_ID.save(deps.storage, &id)?;
+        escrow(deps.storage, &offer_asset)?;
+        ORDERS_BY_OWNER.save(deps.storage, (&info.sender, id), &Empty {})?;
+        ORDERS_BY_PAIR.save(
+            deps.storage,
+            (pair_key(&offer_asset.info, &target_asset_info), id),
+            &Empty {},
+        )?;
+        LIMIT_ORDERS.save(
+            deps.storage,
+            id,
+            &LimitOrder {
+                id,
+                owner: info.sender.clone(),
+                offer_asset: offer_asset.clone(),
+                steps,
+                target_asset_info: target_asset_info.clone(),
+                min_price,
+                expiry,
+            },
+        )?;
+
+        Ok(Response::new()
+            .add_messages(messages)
+            .add_attribute("action", "place_limit_order")
+            .add_attribute("order_id", id.to_string())
+            .add_attribute("owner", info.sender)
+            .add_attribute("offer_asset", offer_asset.to_string())
+            .add_attribute("target_asset", target_asset_info.to_string())
+            .add_attribute("min_price", min_price.to_string()))
+    }
+
+    pub fn cancel_order(
+        deps: DepsMut,
+        info: MessageInfo,
+        id: u64,
+    ) -> Result<Response, ContractError> {
+        let order = LIMIT_ORDERS.load(deps.storage, id)?;
+        if order.owner != info.sender {
+            return Err(ContractError::Unauthorized {});
+        }
+        remove_order(deps.storage, &order)?;
+
+        Ok(Response::new()
+            .add_message(order.offer_asset.clone().into_msg(&order.owner)?)
+            .add_attribute("action", "cancel_order")
+            .add_attribute("order_id", id.to_string())
+            .add_attribute("refund", order.offer_asset.to_string()))
+    }
+
+    /// Executes a limit order if a simulation of its route meets the order price.
+    pub fn execute_order(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        id: u64,
+    ) -> Result<Response, ContractError> {
+        let order = LIMIT_ORDERS.load(deps.storage, id)?;
+        if let Some(expiry) = &order.expiry {
+            if expiry.is_expired(&env.block) {
+                return Err(ContractError::OrderExpired { id });
+            }
+        }
+
+        // The owner must receive the order price after the keeper tip
+        let minimum_receive = order.offer_asset.amount * order.min_price;
+        let simulated = simulate_route(deps.as_ref(), &order.steps, order.offer_asset.amount)?;
+        let tip_bps = KEEPER_TIP_BPS.may_load(deps.storage)?.unwrap_or_default();
+        let amount = simulated - simulated.multiply_ratio(tip_bps, MAX_WEIGHT_BPS);
+        if amount < minimum_receive {
+            return Err(ContractError::OrderPriceNotReached {
+                id,
+                receive: minimum_receive,
+                amount,
+            });
+        }
+        remove_order(deps.storage, &order)?;
+
+        let prev_balance = order
+            .target_asset_info
+            .query_pool(&deps.querier, &env.contract.address)?;
+        let mut messages = route_messages(
+            &env,
+            order.steps,
+            Some(order.offer_asset.amount),
+            &env.contract.address,
+            &order.owner,
+        )?;
+        messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: env.contract.address.to_string(),
+            funds: vec![],
+            msg: to_binary(&ExecuteMsg::SettleOrder {
+                id,
+                owner: order.owner.to_string(),
+                keeper: info.sender.to_string(),
+                target_asset_info: order.target_asset_info,
+                prev_balance,
+                minimum_receive,
+            })?,
+        }));
+
+        Ok(Response::new()
+            .add_messages(messages)
+            .add_attribute("action", "execute_order")
+            .add_attribute("order_id", id.to_string())
+            .add_attribute("keeper", info.sender))
+    }
+
+    /// This structure describes the output of an executed limit order and who it is paid to.
+    pub struct SettleOrderParams {
+        pub id: u64,
+        pub owner: String,
+        pub keeper: String,
+        pub target_asset_info: AssetInfo,
+        pub prev_balance: Uint128,
+        pub minimum_receive: Uint128,
+    }
+
+    /// Checks the return of an executed limit order against its price and splits it between the
+    /// owner and the keeper. Only the contract itself can execute it.
+    pub fn settle_order(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        params: SettleOrderParams,
+    ) -> Result<Response, ContractError> {
+        let SettleOrderParams {
+            id,
+            owner,
+            keeper,
+            target_asset_info,
+            prev_balance,
+            minimum_receive,
+        } = params;
+
+        if info.sender != env.contract.address {
+            return Err(ContractError::Unauthorized {});
+        }
+
+        let return_amount = target_asset_info
+            .query_pool(&deps.querier, &env.contract.address)?
+            .checked_sub(prev_balance)?;
+        let tip_bps = KEEPER_TIP_BPS.may_load(deps.storage)?.unwrap_or_default();
+        let tip = return_amount.multiply_ratio(tip_bps, MAX_WEIGHT_BPS);
+        if return_amount - tip < minimum_receive {
+            return Err(ContractError::AssertionMinimumReceive {
+                receive: minimum_receive,
+                amount: return_amount - tip,
+            });
+        }
+
+        let mut response = Response::new();
+        for (recipient, amount) in [(keeper, tip), (owner, return_amount - tip)] {
+            if !amount.is_zero() {
+                let asset = AstroportAsset {
+                    info: target_asset_info.clone(),
+                    amount,
+                };
+                response = response.add_message(asset.into_msg(deps.api.addr_validate(&recipient)?)?);
+            }
+        }
+
+        Ok(response
+            .add_attribute("action", "settle_order")
+            .add_attribute("order_id", id.to_string())
+            .add_attribute("return_amount", return_amount)
+            .add_attribute("keeper_tip", tip))
+    }
+
+    /// This structure describes a DCA schedule of an offer split into chunks.
+    pub struct DcaParams {
+        pub offer_asset: AstroportAsset,
+        pub steps: Vec<SwapOperation>,
+        pub chunk_amount: Uint128,
+        pub interval_seconds: u64,
+        pub max_price: Decimal,
+    }
+
+    /// Escrows the deposit of a new DCA schedule.
+    pub fn create_dca(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        params: DcaParams,
+    ) -> Result<Response, ContractError> {
+        let DcaParams {
+            offer_asset,
+            steps,
+            chunk_amount,
+            interval_seconds,
+            max_price,
+        } = params;
+
+        offer_asset.info.check(deps.api)?;
+        if chunk_amount.is_zero() || chunk_amount > offer_asset.amount {
+            return Err(ContractError::Std(StdError::generic_err(
+                "Chunk amount must be positive and not exceed the deposit",
+            )));
+        }
+        if interval_seconds == 0 {
+            return Err(ContractError::Std(StdError::generic_err(
+                "Interval must be positive",
+            )));
+        }
+        if max_price.is_zero() {
+            return Err(ContractError::Std(StdError::generic_err(
+                "Max price must be positive",
+            )));
+        }
+        assert_route(&steps, &offer_asset.info)?;
+        let target_asset_info = operation_ask_asset_info(
+            deps.as_ref(),
+            steps.last().ok_or(ContractError::MustProvideOperations {})?,
+        )?;
+
+        let messages = collect_offer(&info, &env, slice::from_ref(&offer_asset))?;
+
+        let id = LAST_DCA_ID.may_load(deps.storage)?.unwrap_or_default() + 1;
+        LAST_DCA_ID.save(deps.storage, &id)?;
+        escrow(deps.storage, &offer_asset)?;
+        DCA_SCHEDULES.save(
+            deps.storage,
+            id,
+            &DcaSchedule {
+                id,
+                owner: info.sender.clone(),
+                offer_asset: offer_asset.clone(),
+                steps,
+                target_asset_info: target_asset_info.clone(),
+                chunk_amount,
+                interval_seconds,
+                max_price,
+                status: DcaStatus::Active,
+                executions: 0,
+                last_execution: None,
+            },
+        )?;
+
+        Ok(Response::new()
+            .add_messages(messages)
+            .add_attribute("action", "create_dca")
+            .add_attribute("dca_id", id.to_string())
+            .add_attribute("owner", info.sender)
+            .add_attribute("offer_asset", offer_asset.to_string())
+            .add_attribute("target_asset", target_asset_info.to_string())
+            .add_attribute("chunk_amount", chunk_amount))
+    }
+
+    /// Swaps the next chunk of a DCA schedule.
+    pub fn execute_dca(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        id: u64,
+    ) -> Result<Response, ContractError> {
+        let mut schedule = DCA_SCHEDULES.load(deps.storage, id)?;
+        if schedule.status != DcaStatus::Active {
+            return Err(ContractError::DcaNotActive { id });
+        }
+        if let Some(last_execution) = schedule.last_execution {
+            let next_execution = last_execution.plus_seconds(schedule.interval_seconds);
+            if env.block.time < next_execution {
+                return Err(ContractError::DcaNotReady { id, next_execution });
+            }
+        }
+
+        let chunk = AstroportAsset {
+            info: schedule.offer_asset.info.clone(),
+            amount: schedule.chunk_amount.min(schedule.offer_asset.amount),
+        };
+        release(deps.storage, &chunk)?;
+        schedule.offer_asset.amount -= chunk.amount;
+        schedule.executions += 1;
+        schedule.last_execution = Some(env.block.time);
+        if schedule.offer_asset.amount.is_zero() {
+            schedule.status = DcaStatus::Completed;
+        }
+        DCA_SCHEDULES.save(deps.storage, id, &schedule)?;
+
+        // The return must not cost more than max price per unit of the target asset
+        let minimum_receive = chunk.amount.multiply_ratio(
+            DECIMAL_FRACTIONAL,
+            Uint128::new(DECIMAL_FRACTIONAL) * schedule.max_price,
+        );
+        let prev_balance = schedule
+            .target_asset_info
+            .query_pool(&deps.querier, &env.contract.address)?;
+        let mut messages = route_messages(
+            &env,
+            schedule.steps,
+            Some(chunk.amount),
+            &env.contract.address,
+            &schedule.owner,
+        )?;
+        messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: env.contract.address.to_string(),
+            funds: vec![],
+            msg: to_binary(&ExecuteMsg::SettleDca {
+                id,
+                index: schedule.executions,
+                offer_amount: chunk.amount,
+                prev_balance,
+                minimum_receive,
+            })?,
+        }));
+
+        Ok(Response::new()
+            .add_messages(messages)
+            .add_attribute("action", "execute_dca")
+            .add_attribute("dca_id", id.to_string())
+            .add_attribute("index", schedule.executions.to_string())
+            .add_attribute("offer_amount", chunk.amount)
+            .add_attribute("keeper", info.sender))
+    }
+
+    pub fn cancel_dca(
+        deps: DepsMut,
+        info: MessageInfo,
+        id: u64,
+    ) -> Result<Response, ContractError> {
+        let mut schedule = DCA_SCHEDULES.load(deps.storage, id)?;
+        if schedule.owner != info.sender {
+            return Err(ContractError::Unauthorized {});
+        }
+        if schedule.status != DcaStatus::Active {
+            return Err(ContractError::DcaNotActive { id });
+        }
+
+        let remainder = schedule.offer_asset.clone();
+        release(deps.storage, &remainder)?;
+        schedule.offer_asset.amount = Uint128::zero();
+        schedule.status = DcaStatus::Cancelled;
+        DCA_SCHEDULES.save(deps.storage, id, &schedule)?;
+
+        Ok(Response::new()
+            .add_message(remainder.clone().into_msg(&schedule.owner)?)
+            .add_attribute("action", "cancel_dca")
+            .add_attribute("dca_id", id.to_string())
+            .add_attribute("refund", remainder.to_string()))
+    }
+
+    /// This structure describes the output of an executed DCA chunk.
+    pub struct SettleDcaParams {
+        pub id: u64,
+        pub index: u64,
+        pub offer_amount: Uint128,
+        pub prev_balance: Uint128,
+        pub minimum_receive: Uint128,
+    }
+
+    /// Checks the return of a DCA chunk against the max price, pays it to the owner and records
+    /// it. Only the contract itself can execute it.
+    pub fn settle_dca(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        params: SettleDcaParams,
+    ) -> Result<Response, ContractError> {
+        let SettleDcaParams {
+            id,
+            index,
+            offer_amount,
+            prev_balance,
+            minimum_receive,
+        } = params;
+
+        if info.sender != env.contract.address {
+            return Err(ContractError::Unauthorized {});
+        }
+
+        let schedule = DCA_SCHEDULES.load(deps.storage, id)?;
+        let return_amount = schedule
+            .target_asset_info
+            .query_pool(&deps.querier, &env.contract.address)?
+            .checked_sub(prev_balance)?;
+        if return_amount < minimum_receive {
+            return Err(ContractError::AssertionMinimumReceive {
+                receive: minimum_receive,
+                amount: return_amount,
+            });
+        }
+        DCA_EXECUTIONS.save(
+            deps.storage,
+            (id, index),
+            &DcaExecution {
+                index,
+                offer_amount,
+                return_amount,
+                timestamp: env.block.time,
+            },
+        )?;
+
+        let mut response = Response::new();
+        if !return_amount.is_zero() {
+            let asset = AstroportAsset {
+                info: schedule.target_asset_info,
+                amount: return_amount,
+            };
+            response = response.add_message(asset.into_msg(&schedule.owner)?);
+        }
+
+        Ok(response
+            .add_attribute("action", "settle_dca")
+            .add_attribute("dca_id", id.to_string())
+            .add_attribute("index", index.to_string())
+            .add_attribute("return_amount", return_amount))
+    }
+
+    /// This structure describes a route executed once the price of a pool meets a condition.
+    pub struct ConditionalOrderParams {
+        pub offer_asset: AstroportAsset,
+        pub steps: Vec<SwapOperation>,
+        pub minimum_receive: Option<Uint128>,
+        pub price_pool: String,
+        pub dex: String,
+        pub condition: PriceCondition,
+        pub twap_window_seconds: u64,
+    }
+
+    /// Escrows the offer asset of a new conditional order and starts its first TWAP window.
+    pub fn place_conditional_order(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        params: ConditionalOrderParams,
+    ) -> Result<Response, ContractError> {
+        let ConditionalOrderParams {
+            offer_asset,
+            steps,
+            minimum_receive,
+            price_pool,
+            dex,
+            condition,
+            twap_window_seconds,
+        } = params;
+
+        offer_asset.info.check(deps.api)?;
+        if offer_asset.amount.is_zero() {
+            return Err(ContractError::Std(StdError::generic_err(
+                "Offer amount must be positive",
+            )));
+        }
+        if twap_window_seconds == 0 {
+            return Err(ContractError::Std(StdError::generic_err(
+                "TWAP window must be positive",
+            )));
+        }
+        assert_route(&steps, &offer_asset.info)?;
+        let price_pool = deps.api.addr_validate(&price_pool)?;
+        let cumulative_price =
+            query_cumulative_price(deps.as_ref(), &price_pool, &dex, &offer_asset.info)?;
+
+        let messages = collect_offer(&info, &env, slice::from_ref(&offer_asset))?;
+
+        let id = LAST_CONDITIONAL_ORDER_ID
+            .may_load(deps.storage)?
+            .unwrap_or_default()
+            + 1;
+        LAST_CONDITIONAL_ORDER_ID.save(deps.storage, &id)?;
+        escrow(deps.storage, &offer_asset)?;
+        CONDITIONAL_ORDERS.save(
+            deps.storage,
+            id,
+            &ConditionalOrder {
+                id,
+                owner: info.sender.clone(),
+                offer_asset: offer_asset.clone(),
+                steps,
+                minimum_receive,
+                price_pool: price_pool.clone(),
+                dex,
+                condition,
+                twap_window_seconds,
+                observation: PriceObservation {
+                    cumulative_price,
+                    timestamp: env.block.time,
+                },
+            },
+        )?;
+
+        Ok(Response::new()
+            .add_messages(messages)
+            .add_attribute("action", "place_conditional_order")
+            .add_attribute("order_id", id.to_string())
+            .add_attribute("owner", info.sender)
+            .add_attribute("offer_asset", offer_asset.to_string())
+            .add_attribute("price_pool", price_pool))
+    }
+
+    pub fn cancel_conditional_order(
+        deps: DepsMut,
+        info: MessageInfo,
+        id: u64,
+    ) -> Result<Response, ContractError> {
+        let order = CONDITIONAL_ORDERS.load(deps.storage, id)?;
+        if order.owner != info.sender {
+            return Err(ContractError::Unauthorized {});
+        }
+        CONDITIONAL_ORDERS.remove(deps.storage, id);
+        release(deps.storage, &order.offer_asset)?;
+
+        Ok(Response::new()
+            .add_message(order.offer_asset.clone().into_msg(&order.owner)?)
+            .add_attribute("action", "cancel_conditional_order")
+            .add_attribute("order_id", id.to_string())
+            .add_attribute("refund", order.offer_asset.to_string()))
+    }
+
+    /// Routes a conditional order to its owner if the TWAP over the elapsed window meets the
+    /// condition. Otherwise the window is restarted.
+    pub fn trigger(deps: DepsMut, env: Env, id: u64) -> Result<Response, ContractError> {
+        let mut order = CONDITIONAL_ORDERS.load(deps.storage, id)?;
+        let window_end = order
+            .observation
+            .timestamp
+            .plus_seconds(order.twap_window_seconds);
+        if env.block.time < window_end {
+            return Err(ContractError::TwapWindowNotElapsed { id, window_end });
+        }
+
+        // Cumulative prices wrap around on overflow
+        let cumulative_price = query_cumulative_price(
+            deps.as_ref(),
+            &order.price_pool,
+            &order.dex,
+            &order.offer_asset.info,
+        )?;
+        let elapsed = env.block.time.seconds() - order.observation.timestamp.seconds();
+        let twap = Decimal::from_ratio(
+            cumulative_price.wrapping_sub(order.observation.cumulative_price),
+            Uint128::from(elapsed) * Uint128::from(10u128.pow(TWAP_PRECISION.into())),
+        );
+        let triggered = match &order.condition {
+            PriceCondition::Below { price } => twap <= *price,
+            PriceCondition::Above { price } => twap >= *price,
+        };
+
+        let response = Response::new()
+            .add_attribute("action", "trigger")
+            .add_attribute("order_id", id.to_string())
+            .add_attribute("twap", twap.to_string())
+            .add_attribute("triggered", triggered.to_string());
+        if !triggered {
+            order.observation = PriceObservation {
+                cumulative_price,
+                timestamp: env.block.time,
+            };
+            CONDITIONAL_ORDERS.save(deps.storage, id, &order)?;
+            return Ok(response);
+        }
+
+        CONDITIONAL_ORDERS.remove(deps.storage, id);
+        release(deps.storage, &order.offer_asset)?;
+
+        let target_asset_info = operation_ask_asset_info(
+            deps.as_ref(),
+            order
+                .steps
+                .last()
+                .ok_or(ContractError::MustProvideOperations {})?,
+        )?;
+        let mut messages = route_messages(
+            &env,
+            order.steps,
+            Some(order.offer_asset.amount),
+            &order.owner,
+            &order.owner,
+        )?;
+        if let Some(minimum_receive) = order.minimum_receive {
+            messages.push(minimum_receive_message(
+                deps.as_ref(),
+                &env,
+                target_asset_info,
+                minimum_receive,
+                &order.owner,
+            )?);
+        }
+
+        Ok(response.add_messages(messages))
+    }
+
+    /// Escrows an offer asset behind a swap commitment hash.
+    pub fn commit_swap(
+        deps: DepsMut,
+        env: Env,
+        owner: Addr,
+        offer_asset: AstroportAsset,
+        hash: Binary,
+    ) -> Result<Response, ContractError> {
+        if offer_asset.amount.is_zero() {
+            return Err(ContractError::Std(StdError::generic_err(
+                "Offer amount must be positive",
+            )));
+        }
+        if SWAP_COMMITMENTS.has(deps.storage, (&owner, hash.as_slice())) {
+            return Err(ContractError::CommitmentExists {});
+        }
+
+        escrow(deps.storage, &offer_asset)?;
+        SWAP_COMMITMENTS.save(
+            deps.storage,
+            (&owner, hash.as_slice()),
+            &SwapCommitment {
+                owner: owner.clone(),
+                offer_asset: offer_asset.clone(),
+                height: env.block.height,
+            },
+        )?;
+
+        Ok(Response::new()
+            .add_attribute("action", "commit_swap")
+            .add_attribute("owner", owner)
+            .add_attribute("hash", hash.to_base64())
+            .add_attribute("offer_asset", offer_asset.to_string()))
+    }
+
+    /// Commits the single native coin attached to the message.
+    pub fn commit_native_swap(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        hash: Binary,
+    ) -> Result<Response, ContractError> {
+        let coin = match info.funds.as_slice() {
+            [coin] => coin,
+            _ => {
+                return Err(ContractError::Std(StdError::generic_err(
+                    "Exactly one native coin must be attached",
+                )))
+            }
+        };
+        let offer_asset = AstroportAsset {
+            info: AssetInfo::NativeToken {
+                denom: coin.denom.clone(),
+            },
+            amount: coin.amount,
+        };
+        commit_swap(deps, env, info.sender, offer_asset, hash)
+    }
+
+    /// Routes a committed offer once the revealed parameters match its hash.
+    pub fn reveal_swap(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        steps: Vec<SwapOperation>,
+        minimum_receive: Option<Uint128>,
+        salt: Binary,
+    ) -> Result<Response, ContractError> {
+        let reveal = SwapReveal {
+            steps,
+            minimum_receive,
+            salt,
+        };
+        let hash = Sha256::digest(to_binary(&reveal)?.as_slice());
+        let key = (&info.sender, &hash[..]);
+        let commitment = SWAP_COMMITMENTS
+            .may_load(deps.storage, key)?
+            .ok_or(ContractError::CommitmentNotFound {})?;
+
+        if env.block.height <= commitment.height {
+            return Err(ContractError::RevealTooEarly {});
+        }
+        let deadline = commitment.height + COMMIT_REVEAL_TIMEOUT_BLOCKS;
+        if env.block.height > deadline {
+            return Err(ContractError::CommitmentExpired { deadline });
+        }
+        SWAP_COMMITMENTS.remove(deps.storage, key);
+        release(deps.storage, &commitment.offer_asset)?;
+
+        assert_route(&reveal.steps, &commitment.offer_asset.info)?;
+        let target_asset_info = operation_ask_asset_info(
+            deps.as_ref(),
+            reveal
+                .steps
+                .last()
+                .ok_or(ContractError::MustProvideOperations {})?,
+        )?;
+        let mut messages = route_messages(
+            &env,
+            reveal.steps,
+            Some(commitment.offer_asset.amount),
+            &info.sender,
+            &info.sender,
+        )?;
+        if let Some(minimum_receive) = reveal.minimum_receive {
+            messages.push(minimum_receive_message(
+                deps.as_ref(),
+                &env,
+                target_asset_info,
+                minimum_receive,
+                &info.sender,
+            )?);
+        }
+
+        Ok(Response::new()
+            .add_messages(messages)
+            .add_attribute("action", "reveal_swap")
+            .add_attribute("owner", info.sender)
+            .add_attribute("offer_asset", commitment.offer_asset.to_string()))
+    }
+
+    pub fn refund_commit(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        hash: Binary,
+    ) -> Result<Response, ContractError> {
+        let key = (&info.sender, hash.as_slice());
+        let commitment = SWAP_COMMITMENTS
+            .may_load(deps.storage, key)?
+            .ok_or(ContractError::CommitmentNotFound {})?;
+        let deadline = commitment.height + COMMIT_REVEAL_TIMEOUT_BLOCKS;
+        if env.block.height <= deadline {
+            return Err(ContractError::CommitmentNotExpired { deadline });
+        }
+        SWAP_COMMITMENTS.remove(deps.storage, key);
+        release(deps.storage, &commitment.offer_asset)?;
+
+        Ok(Response::new()
+            .add_message(commitment.offer_asset.clone().into_msg(&info.sender)?)
+            .add_attribute("action", "refund_commit")
+            .add_attribute("owner", info.sender)
+            .add_attribute("refund", commitment.offer_asset.to_string()))
+    }
+
+    pub fn recover(deps: DepsMut, info: MessageInfo) -> Result<Response, ContractError> {
+        let assets = RECOVERIES
+            .may_load(deps.storage, &info.sender)?
+            .unwrap_or_default();
+        if assets.is_empty() {
+            return Err(ContractError::Std(StdError::generic_err(
+                "Nothing to recover",
+            )));
+        }
+        RECOVERIES.remove(deps.storage, &info.sender);
+
+        let mut messages: Vec<CosmosMsg> = vec![];
+        for asset in &assets {
+            release(deps.storage, asset)?;
+            messages.push(asset.clone().into_msg(&info.sender)?);
+        }
+
+        Ok(Response::new()
+            .add_messages(messages)
+            .add_attribute("action", "recover")
+            .add_attribute("recovery_addr", info.sender)
+            .add_attribute(
+                "assets",
+                assets
+                    .iter()
+                    .map(|asset| asset.to_string())
+                    .collect::<Vec<_>>()
+                    .join(","),
+            ))
+    }
+
+    /// This structure describes an asset locked behind a hashlock and a timelock.
+    pub struct LockSwapParams {
+        pub hashlock: Binary,
+        pub timelock: Expiration,
+        pub recipient: String,
+        pub asset: AstroportAsset,
+        pub route: Option<Vec<SwapOperation>>,
+        pub minimum_receive: Option<Uint128>,
+    }
+
+    /// Locks an asset behind a hashlock until its timelock expires.
+    pub fn lock_swap(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        params: LockSwapParams,
+    ) -> Result<Response, ContractError> {
+        let LockSwapParams {
+            hashlock,
+            timelock,
+            recipient,
+            asset,
+            route,
+            minimum_receive,
+        } = params;
+
+        asset.info.check(deps.api)?;
+        if asset.amount.is_zero() {
+            return Err(ContractError::Std(StdError::generic_err(
+                "Lock amount must be positive",
+            )));
+        }
+        if hashlock.len() != 32 {
+            return Err(ContractError::Std(StdError::generic_err(
+                "Hashlock must be a SHA-256 hash",
+            )));
+        }
+        if matches!(timelock, Expiration::Never {}) || timelock.is_expired(&env.block) {
+            return Err(ContractError::Std(StdError::generic_err(
+                "Timelock must expire in the future",
+            )));
+        }
+        if HASHED_TIMELOCKS.has(deps.storage, (&info.sender, hashlock.as_slice())) {
+            return Err(ContractError::HashlockExists {});
+        }
+        let recipient = deps.api.addr_validate(&recipient)?;
+        if let Some(route) = &route {
+            assert_route(route, &asset.info)?;
+        }
+
+        let messages = collect_offer(&info, &env, slice::from_ref(&asset))?;
+
+        escrow(deps.storage, &asset)?;
+        HASHED_TIMELOCKS.save(
+            deps.storage,
+            (&info.sender, hashlock.as_slice()),
+            &HashedTimelock {
+                sender: info.sender.clone(),
+                recipient: recipient.clone(),
+                asset: asset.clone(),
+                timelock,
+                route,
+                minimum_receive,
+            },
+        )?;
+
+        Ok(Response::new()
+            .add_messages(messages)
+            .add_attribute("action", "lock_swap")
+            .add_attribute("hashlock", hashlock.to_base64())
+            .add_attribute("sender", info.sender)
+            .add_attribute("recipient", recipient)
+            .add_attribute("asset", asset.to_string())
+            .add_attribute("timelock", timelock.to_string()))
+    }
+
+    /// Delivers a locked asset to its recipient, routing it first if the lock has a route. The
+    /// route runs as a submessage, so a failed route delivers the locked asset as is instead of
+    /// reverting the claim. The preimage is published in the attributes so the counterparty can
+    /// claim on the other chain.
+    pub fn claim(
+        deps: DepsMut,
+        env: Env,
+        sender: String,
+        preimage: Binary,
+    ) -> Result<Response, ContractError> {
+        let sender = deps.api.addr_validate(&sender)?;
+        let hashlock = Sha256::digest(preimage.as_slice());
+        let key = (&sender, &hashlock[..]);
+        let lock = HASHED_TIMELOCKS
+            .may_load(deps.storage, key)?
+            .ok_or(ContractError::HashedTimelockNotFound {})?;
+        if lock.timelock.is_expired(&env.block) {
+            return Err(ContractError::TimelockExpired {});
+        }
+        HASHED_TIMELOCKS.remove(deps.storage, key);
+        release(deps.storage, &lock.asset)?;
+
+        let response = match lock.route {
+            Some(_) => {
+                PENDING_CLAIM.save(deps.storage, &lock)?;
+                Response::new().add_submessage(SubMsg::reply_on_error(
+                    WasmMsg::Execute {
+                        contract_addr: env.contract.address.to_string(),
+                        funds: vec![],
+                        msg: to_binary(&ExecuteMsg::ExecuteClaimRoute {})?,
+                    },
+                    CLAIM_ROUTE_REPLY_ID,
+                ))
+            }
+            None => Response::new().add_message(lock.asset.clone().into_msg(&lock.recipient)?),
+        };
+
+        Ok(response
+            .add_attribute("action", "claim")
+            .add_attribute("hashlock", Binary::from(&hashlock[..]).to_base64())
+            .add_attribute("preimage", preimage.to_base64())
+            .add_attribute("sender", sender)
+            .add_attribute("recipient", lock.recipient)
+            .add_attribute("asset", lock.asset.to_string()))
+    }
+
+    /// Routes the asset of a claimed hash time-locked swap. Only the contract itself can execute
+    /// it.
+    pub fn execute_claim_route(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+    ) -> Result<Response, ContractError> {
+        if info.sender != env.contract.address {
+            return Err(ContractError::Unauthorized {});
+        }
+
+        let lock = PENDING_CLAIM.load(deps.storage)?;
+        PENDING_CLAIM.remove(deps.storage);
+        let route = lock.route.ok_or(ContractError::MustProvideOperations {})?;
+        let target_asset_info = operation_ask_asset_info(
+            deps.as_ref(),
+            route.last().ok_or(ContractError::MustProvideOperations {})?,
+        )?;
+        let mut messages = route_messages(
+            &env,
+            route,
+            Some(lock.asset.amount),
+            &lock.recipient,
+            &lock.recipient,
+        )?;
+        if let Some(minimum_receive) = lock.minimum_receive {
+            messages.push(minimum_receive_message(
+                deps.as_ref(),
+                &env,
+                target_asset_info,
+                minimum_receive,
+                &lock.recipient,
+            )?);
+        }
+
+        Ok(Response::new()
+            .add_messages(messages)
+            .add_attribute("action", "execute_claim_route"))
+    }
+
+    /// Returns a locked asset to its sender once its timelock has expired.
+    pub fn refund(
+        deps: DepsMut,
+        env: Env,
+        sender: String,
+        hashlock: Binary,
+    ) -> Result<Response, ContractError> {
+        let sender = deps.api.addr_validate(&sender)?;
+        let key = (&sender, hashlock.as_slice());
+        let lock = HASHED_TIMELOCKS
+            .may_load(deps.storage, key)?
+            .ok_or(ContractError::HashedTimelockNotFound {})?;
+        if !lock.timelock.is_expired(&env.block) {
+            return Err(ContractError::TimelockNotExpired {});
+        }
+        HASHED_TIMELOCKS.remove(deps.storage, key);
+        release(deps.storage, &lock.asset)?;
+
+        Ok(Response::new()
+            .add_message(lock.asset.clone().into_msg(&lock.sender)?)
+            .add_attribute("action", "refund")
+            .add_attribute("hashlock", hashlock.to_base64())
+            .add_attribute("sender", lock.sender)
+            .add_attribute("refund", lock.asset.to_string()))
+    }
+
+    /// Adds an escrowed order to the current batch auction of a pool.
+    pub fn submit_auction_order(
+        deps: DepsMut,
+        env: Env,
+        owner: Addr,
+        offer_asset: AstroportAsset,
+        pool: String,
+        dex: String,
+        max_spread: Option<Decimal>,
+    ) -> Result<Response, ContractError> {
+        if offer_asset.amount.is_zero() {
+            return Err(ContractError::Std(StdError::generic_err(
+                "Offer amount must be positive",
+            )));
+        }
+        let pool = deps.api.addr_validate(&pool)?;
+        let round = env.block.height / AUCTION_PERIOD_BLOCKS;
+        let prices = mid_prices(deps.as_ref(), &pool, &dex)?;
+
+        let mut batch = match AUCTION_BATCHES.may_load(deps.storage, (&pool, round))? {
+            Some(batch) => batch,
+            None => {
+                let asset_infos = query_pool_assets(deps.as_ref(), &pool, &dex)?
+                    .into_iter()
+                    .map(|asset| asset.info)
+                    .collect::<Vec<_>>();
+                AuctionBatch {
+                    dex: dex.clone(),
+                    totals: vec![Uint128::zero(); asset_infos.len()],
+                    asset_infos,
+                    orders: 0,
+                    next_index: 0,
+                }
+            }
+        };
+        if batch.dex != dex {
+            return Err(ContractError::Std(StdError::generic_err(
+                "The pool belongs to another DEX",
+            )));
+        }
+        if batch.orders >= MAX_AUCTION_ORDERS {
+            return Err(ContractError::AuctionFull {});
+        }
+        let side = batch
+            .asset_infos
+            .iter()
+            .position(|asset_info| asset_info.equal(&offer_asset.info))
+            .ok_or_else(|| ContractError::AssetNotInPool {
+                asset: offer_asset.info.to_string(),
+            })?;
+        // Dust orders would only take up room in the batch and fill to nothing
+        let min_value = Uint128::new(MIN_AUCTION_ORDER_VALUE);
+        let value = offer_asset
+            .amount
+            .multiply_ratio(prices[1 - side], prices[side]);
+        if offer_asset.amount < min_value || value < min_value {
+            return Err(ContractError::AuctionOrderTooSmall { min_value });
+        }
+
+        let index = batch.next_index;
+        batch.totals[side] = batch.totals[side].checked_add(offer_asset.amount)?;
+        batch.orders += 1;
+        batch.next_index += 1;
+        AUCTION_BATCHES.save(deps.storage, (&pool, round), &batch)?;
+        AUCTION_ORDERS.save(
+            deps.storage,
+            (&pool, round, index),
+            &AuctionOrder {
+                owner: owner.clone(),
+                offer_asset: offer_asset.clone(),
+                max_spread,
+            },
+        )?;
+        escrow(deps.storage, &offer_asset)?;
+
+        Ok(Response::new()
+            .add_attribute("action", "submit_auction_order")
+            .add_attribute("pool", pool)
+            .add_attribute("round", round.to_string())
+            .add_attribute("index", index.to_string())
+            .add_attribute("owner", owner)
+            .add_attribute("offer_asset", offer_asset.to_string()))
+    }
+
+    /// Submits the single native coin attached to the message.
+    pub fn submit_native_auction_order(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        pool: String,
+        dex: String,
+        max_spread: Option<Decimal>,
+    ) -> Result<Response, ContractError> {
+        let coin = match info.funds.as_slice() {
+            [coin] => coin,
+            _ => {
+                return Err(ContractError::Std(StdError::generic_err(
+                    "Exactly one native coin must be attached",
+                )))
+            }
+        };
+        let offer_asset = AstroportAsset {
+            info: AssetInfo::NativeToken {
+                denom: coin.denom.clone(),
+            },
+            amount: coin.amount,
+        };
+        submit_auction_order(deps, env, info.sender, offer_asset, pool, dex, max_spread)
+    }
+
+    pub fn cancel_auction_order(
+        deps: DepsMut,
+        info: MessageInfo,
+        pool: String,
+        round: u64,
+        index: u64,
+    ) -> Result<Response, ContractError> {
+        let pool = deps.api.addr_validate(&pool)?;
+        let order = AUCTION_ORDERS.load(deps.storage, (&pool, round, index))?;
+        if order.owner != info.sender {
+            return Err(ContractError::Unauthorized {});
+        }
+
+        let mut batch = AUCTION_BATCHES.load(deps.storage, (&pool, round))?;
+        let side = batch
+            .asset_infos
+            .iter()
+            .position(|asset_info| asset_info.equal(&order.offer_asset.info))
+            .ok_or_else(|| StdError::generic_err("Order asset is not in the batch"))?;
+        batch.totals[side] = batch.totals[side].checked_sub(order.offer_asset.amount)?;
+        batch.orders -= 1;
+        if batch.orders == 0 {
+            AUCTION_BATCHES.remove(deps.storage, (&pool, round));
+        } else {
+            AUCTION_BATCHES.save(deps.storage, (&pool, round), &batch)?;
+        }
+        AUCTION_ORDERS.remove(deps.storage, (&pool, round, index));
+        release(deps.storage, &order.offer_asset)?;
+
+        Ok(Response::new()
+            .add_message(order.offer_asset.clone().into_msg(&order.owner)?)
+            .add_attribute("action", "cancel_auction_order")
+            .add_attribute("pool", pool)
+            .add_attribute("round", round.to_string())
+            .add_attribute("index", index.to_string())
+            .add_attribute("refund", order.offer_asset.to_string()))
+    }
+
+    /// Matches the opposing orders of an ended batch auction at the pool mid price and swaps
+    /// the net imbalance through the pool. Orders worth nothing at that price and orders whose
+    /// `max_spread` the fill would exceed are refunded instead.
+    pub fn settle_auction(
+        deps: DepsMut,
+        env: Env,
+        pool: String,
+        round: u64,
+    ) -> Result<Response, ContractError> {
+        let pool = deps.api.addr_validate(&pool)?;
+        let settle_height = (round + 1) * AUCTION_PERIOD_BLOCKS;
+        if env.block.height < settle_height {
+            return Err(ContractError::AuctionNotEnded {
+                round,
+                settle_height,
+            });
+        }
+        let mut batch = AUCTION_BATCHES.load(deps.storage, (&pool, round))?;
+        let adapter = registered_adapter(&batch.dex)?;
+
+        let prices = mid_prices(deps.as_ref(), &pool, &batch.dex)?;
+        let prev_balances = batch
+            .asset_infos
+            .iter()
+            .map(|asset_info| asset_info.query_pool(&deps.querier, &env.contract.address))
+            .collect::<StdResult<Vec<_>>>()?;
+
+        // The escrow is spent by the net swap and the distribution, or refunded
+        for (asset_info, total) in batch.asset_infos.iter().zip(&batch.totals) {
+            if !total.is_zero() {
+                release(
+                    deps.storage,
+                    &AstroportAsset {
+                        info: asset_info.clone(),
+                        amount: *total,
+                    },
+                )?;
+            }
+        }
+
+        let mut orders = vec![];
+        for item in AUCTION_ORDERS
+            .prefix((&pool, round))
+            .range(deps.storage, None, None, Order::Ascending)
+        {
+            let (index, order) = item?;
+            let side = batch
+                .asset_infos
+                .iter()
+                .position(|asset_info| asset_info.equal(&order.offer_asset.info))
+                .ok_or_else(|| StdError::generic_err("Order asset is not in the batch"))?;
+            orders.push((index, order, side));
+        }
+        let (mut orders, mut refunds): (Vec<_>, Vec<_>) =
+            orders.into_iter().partition(|(_, order, side)| {
+                !order
+                    .offer_asset
+                    .amount
+                    .multiply_ratio(prices[1 - side], prices[*side])
+                    .is_zero()
+            });
+
+        // The strictest order of the net side that rejects the spread of the fill is refunded
+        // and the rest is matched again
+        let (side, excess) = loop {
+            let mut totals = vec![Uint128::zero(); batch.asset_infos.len()];
+            for (_, order, side) in &orders {
+                totals[*side] = totals[*side].checked_add(order.offer_asset.amount)?;
+            }
+            batch.totals = totals;
+            let (side, excess) = net_imbalance(&batch.totals, &prices);
+            if excess.is_zero() {
+                break (side, excess);
+            }
+
+            let swap_return = adapter
+                .simulate(
+                    &deps.querier,
+                    pool.as_str(),
+                    &AstroportAsset {
+                        info: batch.asset_infos[side].clone(),
+                        amount: excess,
+                    },
+                    Some(batch.asset_infos[1 - side].clone()),
+                )?
+                .return_amount;
+            let value = batch.totals[side].multiply_ratio(prices[1 - side], prices[side]);
+            let filled = batch.totals[1 - side].checked_add(swap_return)?;
+            let spread = if filled >= value {
+                Decimal::zero()
+            } else {
+                Decimal::one() - Decimal::from_ratio(filled, value)
+            };
+            let strictest = orders
+                .iter()
+                .enumerate()
+                .filter(|(_, (_, order, order_side))| {
+                    *order_side == side
+                        && matches!(order.max_spread, Some(max_spread) if max_spread < spread)
+                })
+                .min_by_key(|(_, (_, order, _))| order.max_spread)
+                .map(|(i, _)| i);
+            match strictest {
+                Some(i) => refunds.push(orders.remove(i)),
+                None => break (side, excess),
+            }
+        };
+
+        let mut messages: Vec<CosmosMsg> = vec![];
+        for (index, order, _) in &refunds {
+            AUCTION_ORDERS.remove(deps.storage, (&pool, round, *index));
+            messages.push(order.offer_asset.clone().into_msg(&order.owner)?);
+        }
+        if orders.is_empty() {
+            AUCTION_BATCHES.remove(deps.storage, (&pool, round));
+            return Ok(Response::new()
+                .add_messages(messages)
+                .add_attribute("action", "settle_auction")
+                .add_attribute("pool", pool)
+                .add_attribute("round", round.to_string())
+                .add_attribute("refunded", refunds.len().to_string()));
+        }
+        batch.orders = orders.len() as u64;
+        AUCTION_BATCHES.save(deps.storage, (&pool, round), &batch)?;
+
+        if !excess.is_zero() {
+            messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: env.contract.address.to_string(),
+                funds: vec![],
+                msg: to_binary(&ExecuteMsg::DexSwap {
+                    dex: batch.dex.clone(),
+                    pool_address: pool.to_string(),
+                    offer_asset_info: batch.asset_infos[side].clone(),
+                    offer_amount: Some(excess),
+                    ask_asset_info: Some(batch.asset_infos[1 - side].clone()),
+                    belief_price: None,
+                    max_spread: Some(Decimal::percent(AUCTION_NET_SWAP_MAX_SPREAD)),
+                    to: None,
+                })?,
+            }));
+        }
+        messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: env.contract.address.to_string(),
+            funds: vec![],
+            msg: to_binary(&ExecuteMsg::DistributeAuction {
+                pool: pool.to_string(),
+                round,
+                prices,
+                prev_balances,
+            })?,
+        }));
+
+        Ok(Response::new()
+            .add_messages(messages)
+            .add_attribute("action", "settle_auction")
+            .add_attribute("pool", pool)
+            .add_attribute("round", round.to_string())
+            .add_attribute("refunded", refunds.len().to_string())
+            .add_attribute("net_offer_asset", batch.asset_infos[side].to_string())
+            .add_attribute("net_amount", excess))
+    }
+
+    /// Pays out every order of a settled batch auction. The side with the imbalance shares the
+    /// matched amount and the net swap return pro rata, the other side is filled at the clearing
+    /// price. Only the contract itself can execute it.
+    pub fn distribute_auction(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        pool: String,
+        round: u64,
+        prices: Vec<Uint128>,
+        prev_balances: Vec<Uint128>,
+    ) -> Result<Response, ContractError> {
+        if info.sender != env.contract.address {
+            return Err(ContractError::Unauthorized {});
+        }
+
+        let pool = deps.api.addr_validate(&pool)?;
+        let batch = AUCTION_BATCHES.load(deps.storage, (&pool, round))?;
+        let (side, excess) = net_imbalance(&batch.totals, &prices);
+
+        // The balance of the imbalanced asset dropped by the swapped excess
+        let other = 1 - side;
+        let swap_return = batch.asset_infos[other]
+            .query_pool(&deps.querier, &env.contract.address)?
+            .checked_sub(prev_balances[other])?;
+
+        let orders = AUCTION_ORDERS
+            .prefix((&pool, round))
+            .range(deps.storage, None, None, Order::Ascending)
+            .collect::<StdResult<Vec<_>>>()?;
+        let mut messages: Vec<CosmosMsg> = vec![];
+        for (index, order) in &orders {
+            let (return_amount, return_asset_info) =
+                if order.offer_asset.info.equal(&batch.asset_infos[side]) {
+                    let available = batch.totals[other].checked_add(swap_return)?;
+                    (
+                        available.multiply_ratio(order.offer_asset.amount, batch.totals[side]),
+                        batch.asset_infos[other].clone(),
+                    )
+                } else {
+                    (
+                        order
+                            .offer_asset
+                            .amount
+                            .multiply_ratio(prices[side], prices[other]),
+                        batch.asset_infos[side].clone(),
+                    )
+                };
+            // Only a net side share lost to the swap spread can round to zero here
+            if !return_amount.is_zero() {
+                let asset = AstroportAsset {
+                    info: return_asset_info,
+                    amount: return_amount,
+                };
+                messages.push(asset.into_msg(&order.owner)?);
+            }
+            AUCTION_ORDERS.remove(deps.storage, (&pool, round, *index));
+        }
+        AUCTION_BATCHES.remove(deps.storage, (&pool, round));
+
+        Ok(Response::new()
+            .add_messages(messages)
+            .add_attribute("action", "distribute_auction")
+            .add_attribute("pool", pool)
+            .add_attribute("round", round.to_string())
+            .add_attribute("orders", orders.len().to_string())
+            .add_attribute("net_amount", excess)
+            .add_attribute("swap_return", swap_return))
+    }
+
+    /// Verifies a signed swap intent, pulls its offer asset from the signer and routes it.
+    pub fn execute_signed_swap(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        intent: SwapIntent,
+        signature: Binary,
+        pubkey: Binary,
+    ) -> Result<Response, ContractError> {
+        if env.block.time.seconds() > intent.deadline {
+            return Err(ContractError::IntentExpired {});
+        }
+
+        let payload = SignedSwapPayload {
+            chain_id: env.block.chain_id.clone(),
+            contract: env.contract.address.to_string(),
+            intent,
+        };
+        let hash = Sha256::digest(to_binary(&payload)?.as_slice());
+        if !deps
+            .api
+            .secp256k1_verify(&hash, &signature, &pubkey)
+            .map_err(StdError::from)?
+        {
+            return Err(ContractError::InvalidSignature {});
+        }
+        let intent = payload.intent;
+
+        // The signer address is derived from the public key
+        let signer = deps.api.addr_validate(&intent.signer)?;
+        let key_hash = Ripemd160::digest(&Sha256::digest(pubkey.as_slice())[..]);
+        if deps.api.addr_canonicalize(signer.as_str())?.as_slice() != &key_hash[..] {
+            return Err(ContractError::InvalidSignature {});
+        }
+
+        let expected = SIGNER_NONCES
+            .may_load(deps.storage, &signer)?
+            .unwrap_or_default();
+        if intent.nonce != expected {
+            return Err(ContractError::InvalidNonce {
+                expected,
+                nonce: intent.nonce,
+            });
+        }
+        SIGNER_NONCES.save(deps.storage, &signer, &(expected + 1))?;
+
+        let contract_addr = match &intent.offer_asset.info {
+            AssetInfo::Token { contract_addr } => contract_addr,
+            AssetInfo::NativeToken { .. } => {
+                return Err(ContractError::Std(StdError::generic_err(
+                    "Signed swaps only support CW20 offer assets",
+                )))
+            }
+        };
+        assert_route(&intent.steps, &intent.offer_asset.info)?;
+        let target_asset_info = operation_ask_asset_info(
+            deps.as_ref(),
+            intent
+                .steps
+                .last()
+                .ok_or(ContractError::MustProvideOperations {})?,
+        )?;
+        let receiver = match &intent.receiver {
+            Some(receiver) => deps.api.addr_validate(receiver)?,
+            None => signer.clone(),
+        };
+
+        let mut messages = vec![CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: contract_addr.to_string(),
+            funds: vec![],
+            msg: to_binary(&Cw20ExecuteMsg::TransferFrom {
+                owner: signer.to_string(),
+                recipient: env.contract.address.to_string(),
+                amount: intent.offer_asset.amount,
+            })?,
+        })];
+        messages.extend(route_messages(
+            &env,
+            intent.steps,
+            Some(intent.offer_asset.amount),
+            &env.contract.address,
+            &signer,
+        )?);
+        messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: env.contract.address.to_string(),
+            funds: vec![],
+            msg: to_binary(&ExecuteMsg::SettleSignedSwap {
+                prev_balance: target_asset_info
+                    .query_pool(&deps.querier, &env.contract.address)?,
+                target_asset_info,
+                minimum_receive: intent.minimum_receive,
+                max_relayer_fee: intent.max_relayer_fee,
+                receiver: receiver.to_string(),
+                relayer: info.sender.to_string(),
+            })?,
+        }));
+
+        Ok(Response::new()
+            .add_messages(messages)
+            .add_attribute("action", "execute_signed_swap")
+            .add_attribute("signer", signer)
+            .add_attribute("nonce", intent.nonce.to_string())
+            .add_attribute("relayer", info.sender))
+    }
+
+    /// This structure describes the output of a signed swap and how the relayer is paid from it.
+    pub struct SettleSignedSwapParams {
+        pub target_asset_info: AssetInfo,
+        pub prev_balance: Uint128,
+        pub minimum_receive: Uint128,
+        pub max_relayer_fee: Uint128,
+        pub receiver: String,
+        pub relayer: String,
+    }
+
+    /// Pays the relayer fee out of the output of a signed swap and sends the rest to the
+    /// receiver. Only the contract itself can execute it.
+    pub fn settle_signed_swap(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        params: SettleSignedSwapParams,
+    ) -> Result<Response, ContractError> {
+        let SettleSignedSwapParams {
+            target_asset_info,
+            prev_balance,
+            minimum_receive,
+            max_relayer_fee,
+            receiver,
+            relayer,
+        } = params;
+
+        if info.sender != env.contract.address {
+            return Err(ContractError::Unauthorized {});
+        }
+
+        let return_amount = target_asset_info
+            .query_pool(&deps.querier, &env.contract.address)?
+            .checked_sub(prev_balance)?;
+        if return_amount < minimum_receive {
+            return Err(ContractError::AssertionMinimumReceive {
+                receive: minimum_receive,
+                amount: return_amount,
+            });
+        }
+
+        // The fee never pushes the receiver below the signed minimum
+        let relayer_fee = max_relayer_fee.min(return_amount - minimum_receive);
+        let mut response = Response::new();
+        let payouts = [
+            (relayer, relayer_fee),
+            (receiver, return_amount - relayer_fee),
+        ];
+        for (recipient, amount) in payouts {
+            if !amount.is_zero() {
+                let asset = AstroportAsset {
+                    info: target_asset_info.clone(),
+                    amount,
+                };
+                let recipient = deps.api.addr_validate(&recipient)?;
+                response = response.add_message(asset.into_msg(recipient)?);
+            }
+        }
+
+        Ok(response
+            .add_attribute("action", "settle_signed_swap")
+            .add_attribute("return_amount", return_amount)
+            .add_attribute("relayer_fee", relayer_fee))
+    }
+
+    /// This structure describes a fill of a maker's signed quote.
+    pub struct RfqSwapParams {
+        pub maker: String,
+        pub quote: RfqQuote,
+        pub signature: Binary,
+        pub offer_amount: Option<Uint128>,
+        pub to: Option<String>,
+    }
+
+    /// Fills a signed market maker quote: the offer asset is paid to the maker and the ask asset
+    /// is pulled from the maker's allowance. Only the contract itself can execute it.
+    pub fn rfq_swap(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        params: RfqSwapParams,
+    ) -> Result<Response, ContractError> {
+        let RfqSwapParams {
+            maker,
+            quote,
+            signature,
+            offer_amount,
+            to,
+        } = params;
+
+        if info.sender != env.contract.address {
+            return Err(ContractError::Unauthorized {});
+        }
+
+        let maker = deps.api.addr_validate(&maker)?;
+        let pubkey = RFQ_MAKERS
+            .may_load(deps.storage, &maker)?
+            .ok_or_else(|| ContractError::RfqMakerNotWhitelisted {
+                maker: maker.to_string(),
+            })?;
+        if env.block.time.seconds() > quote.expiry {
+            return Err(ContractError::RfqQuoteExpired {});
+        }
+        let payload = RfqQuotePayload {
+            chain_id: env.block.chain_id.clone(),
+            contract: env.contract.address.to_string(),
+            maker: maker.to_string(),
+            quote,
+        };
+        let hash = Sha256::digest(to_binary(&payload)?.as_slice());
+        if !deps
+            .api
+            .secp256k1_verify(&hash, &signature, &pubkey)
+            .map_err(StdError::from)?
+        {
+            return Err(ContractError::InvalidSignature {});
+        }
+        let quote = payload.quote;
+
+        let ask_token = match &quote.ask_asset_info {
+            AssetInfo::Token { contract_addr } => contract_addr,
+            AssetInfo::NativeToken { .. } => {
+                return Err(ContractError::Std(StdError::generic_err(
+                    "RFQ ask assets must be CW20 tokens",
+                )))
+            }
+        };
+        // The quote can be filled in several parts until its offer amount is reached
+        let filled = RFQ_FILLED
+            .may_load(deps.storage, (&maker, quote.nonce))?
+            .unwrap_or_default();
+        let remaining = quote.offer_amount.saturating_sub(filled);
+        if remaining.is_zero() {
+            return Err(ContractError::RfqQuoteFilled {
+                maker: maker.to_string(),
+                nonce: quote.nonce,
+            });
+        }
+        // A fill beyond the remaining amount would strand the excess offer in the router
+        let fill_amount = spendable_amount(
+            deps.as_ref(),
+            &env,
+            &quote.offer_asset_info,
+            offer_amount,
+        )?;
+        if fill_amount > remaining {
+            return Err(ContractError::RfqFillExceedsQuote { remaining });
+        }
+        if fill_amount.is_zero() {
+            return Err(ContractError::Std(StdError::generic_err("Nothing to fill")));
+        }
+        RFQ_FILLED.save(deps.storage, (&maker, quote.nonce), &(filled + fill_amount))?;
+        let return_amount = quote
+            .ask_amount
+            .multiply_ratio(fill_amount, quote.offer_amount);
+
+        let offer_asset = AstroportAsset {
+            info: quote.offer_asset_info.clone(),
+            amount: fill_amount,
+        };
+        let recipient = match to {
+            Some(to) => deps.api.addr_validate(&to)?,
+            None => env.contract.address.clone(),
+        };
+
+        Ok(Response::new()
+            .add_message(offer_asset.clone().into_msg(&maker)?)
+            .add_message(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: ask_token.to_string(),
+                funds: vec![],
+                msg: to_binary(&Cw20ExecuteMsg::TransferFrom {
+                    owner: maker.to_string(),
+                    recipient: recipient.to_string(),
+                    amount: return_amount,
+                })?,
+            }))
+            .add_attribute("action", "rfq_swap")
+            .add_attribute("maker", maker)
+            .add_attribute("nonce", quote.nonce.to_string())
+            .add_attribute("offer_asset", offer_asset.to_string())
+            .add_attribute("return_amount", return_amount))
+    }
+
+    pub fn set_rfq_maker(
+        deps: DepsMut,
+        info: MessageInfo,
+        maker: String,
+        pubkey: Binary,
+    ) -> Result<Response, ContractError> {
+        assert_owner(deps.as_ref(), &info)?;
+
+        let maker = deps.api.addr_validate(&maker)?;
+        RFQ_MAKERS.save(deps.storage, &maker, &pubkey)?;
+
+        Ok(Response::new()
+            .add_attribute("action", "set_rfq_maker")
+            .add_attribute("maker", maker))
+    }
+
+    pub fn remove_rfq_maker(
+        deps: DepsMut,
+        info: MessageInfo,
+        maker: String,
+    ) -> Result<Response, ContractError> {
+        assert_owner(deps.as_ref(), &info)?;
+
+        let maker = deps.api.addr_validate(&maker)?;
+        RFQ_MAKERS.remove(deps.storage, &maker);
+
+        Ok(Response::new()
+            .add_attribute("action", "remove_rfq_maker")
+            .add_attribute("maker", maker))
+    }
+
+    pub fn set_generator(
+        deps: DepsMut,
+        info: MessageInfo,
+        generator: String,
+    ) -> Result<Response, ContractError> {
+        assert_owner(deps.as_ref(), &info)?;
+
+        let generator = deps.api.addr_validate(&generator)?;
+        GENERATOR.save(deps.storage, &generator)?;
+
+        Ok(Response::new()
+            .add_attribute("action", "set_generator")
+            .add_attribute("generator", generator))
+    }
+
+    /// Provides liquidity to an Astroport pair. The minted LP tokens are staked in the generator
+    /// for the receiver once the provision replies.
+    pub fn provide_and_stake(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        pool: String,
+        assets: Vec<AstroportAsset>,
+        slippage_tolerance: Option<Decimal>,
+        receiver: Option<String>,
+    ) -> Result<Response, ContractError> {
+        let generator = GENERATOR
+            .may_load(deps.storage)?
+            .ok_or(ContractError::GeneratorNotSet {})?;
+
+        let pool = deps.api.addr_validate(&pool)?;
+        let receiver = match receiver {
+            Some(receiver) => deps.api.addr_validate(&receiver)?,
+            None => info.sender.clone(),
+        };
+        for asset in &assets {
+            asset.info.check(deps.api)?;
+        }
+        let mut messages = collect_offer(&info, &env, &assets)?;
+
+        let pair_info = registered_adapter(ASTROPORT)?.pair_info(&deps.querier, pool.as_str())?;
+        assert_generator_pool(deps.as_ref(), &generator, &pair_info.liquidity_token)?;
+        let prev_deposit: Uint128 = deps.querier.query_wasm_smart(
+            &generator,
+            &GeneratorQueryMsg::Deposit {
+                lp_token: pair_info.liquidity_token.to_string(),
+                user: receiver.to_string(),
+            },
+        )?;
+
+        // Replies find their provision by the id they carry, so provisions can nest
+        let stake_id = LAST_STAKE_ID
+            .may_load(deps.storage)?
+            .unwrap_or_default()
+            .wrapping_add(1);
+        LAST_STAKE_ID.save(deps.storage, &stake_id)?;
+        PENDING_STAKES.save(
+            deps.storage,
+            stake_id,
+            &PendingStake {
+                lp_token: pair_info.liquidity_token.clone(),
+                prev_lp_balance: query_token_balance(
+                    &deps.querier,
+                    &pair_info.liquidity_token,
+                    &env.contract.address,
+                )?,
+                receiver: receiver.clone(),
+                prev_deposit,
+            },
+        )?;
+
+        messages.extend(provide_liquidity_messages(
+            &pool,
+            ASTROPORT,
+            assets,
+            slippage_tolerance,
+            None,
+            None,
+        )?);
+        // The provision itself is the last message
+        let provide_msg = messages
+            .pop()
+            .ok_or_else(|| StdError::generic_err("Missing provide liquidity message"))?;
+
+        Ok(Response::new()
+            .add_messages(messages)
+            .add_submessage(SubMsg::reply_on_success(
+                provide_msg,
+                PROVIDE_LIQUIDITY_REPLY_ID + u64::from(stake_id),
+            ))
+            .add_attribute("action", "provide_and_stake")
+            .add_attribute("pool", pool)
+            .add_attribute("receiver", receiver))
+    }
+
+    /// Sets the pool and reward routes that compound an LP token.
+    pub fn set_compound_config(
+        deps: DepsMut,
+        info: MessageInfo,
+        lp_token: String,
+        pool: String,
+        reward_routes: Vec<CompoundRoute>,
+        max_spread: Option<Decimal>,
+    ) -> Result<Response, ContractError> {
+        assert_owner(deps.as_ref(), &info)?;
+
+        let lp_token = deps.api.addr_validate(&lp_token)?;
+        let pool = deps.api.addr_validate(&pool)?;
+        let pair_info = registered_adapter(ASTROPORT)?.pair_info(&deps.querier, pool.as_str())?;
+        if pair_info.liquidity_token != lp_token {
+            return Err(ContractError::Std(StdError::generic_err(
+                "The LP token doesn't belong to the pool",
+            )));
+        }
+
+        let reward_assets = reward_routes
+            .iter()
+            .map(|route| AstroportAsset {
+                info: route.reward_asset_info.clone(),
+                amount: Uint128::zero(),
+            })
+            .collect::<Vec<_>>();
+        let mut asset_info: Option<AssetInfo> = None;
+        for route in &reward_routes {
+            let ask_asset_info = match route.steps.last() {
+                None => route.reward_asset_info.clone(),
+                Some(operation) => {
+                    assert_route(&route.steps, &route.reward_asset_info)?;
+                    assert_no_route_overlap(&route.steps, &reward_assets)?;
+                    operation_ask_asset_info(deps.as_ref(), operation)?
+                }
+            };
+            match &asset_info {
+                None => asset_info = Some(ask_asset_info),
+                Some(asset_info) if !asset_info.equal(&ask_asset_info) => {
+                    return Err(ContractError::Std(StdError::generic_err(
+                        "Reward routes must end in the same pool asset",
+                    )));
+                }
+                Some(_) => {}
+            }
+        }
+        let asset_info = asset_info.ok_or(ContractError::MustProvideOperations {})?;
+        if !pair_info
+            .asset_infos
+            .iter()
+            .any(|pool_asset_info| pool_asset_info.equal(&asset_info))
+        {
+            return Err(ContractError::AssetNotInPool {
+                asset: asset_info.to_string(),
+            });
+        }
+
+        COMPOUND_CONFIGS.save(
+            deps.storage,
+            &lp_token,
+            &CompoundConfig {
+                pool: pool.clone(),
+                pool_asset_infos: pair_info.asset_infos,
+                asset_info,
+                reward_routes,
+                max_spread,
+            },
+        )?;
+
+        Ok(Response::new()
+            .add_attribute("action", "set_compound_config")
+            .add_attribute("lp_token", lp_token)
+            .add_attribute("pool", pool))
+    }
+
+    /// Stakes LP tokens in the generator and credits the sender with compounding shares.
+    pub fn deposit_compound(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        sender: String,
+        amount: Uint128,
+    ) -> Result<Response, ContractError> {
+        let generator = GENERATOR
+            .may_load(deps.storage)?
+            .ok_or(ContractError::GeneratorNotSet {})?;
+        let owner = deps.api.addr_validate(&sender)?;
+        let lp_token = info.sender;
+        let config = load_compound_config(deps.storage, &lp_token)?;
+
+        let total_lp = query_compound_deposit(deps.as_ref(), &env, &generator, &lp_token)?;
+        let total_shares = COMPOUND_TOTAL_SHARES
+            .may_load(deps.storage, &lp_token)?
+            .unwrap_or_default();
+        let shares = amount.multiply_ratio(
+            total_shares + Uint128::new(VIRTUAL_COMPOUND_SHARES),
+            total_lp + Uint128::new(VIRTUAL_COMPOUND_LP),
+        );
+        if shares.is_zero() {
+            return Err(ContractError::Std(StdError::generic_err(
+                "Deposit is too small to mint shares",
+            )));
+        }
+
+        COMPOUND_TOTAL_SHARES.save(deps.storage, &lp_token, &(total_shares + shares))?;
+        COMPOUND_SHARES.update(
+            deps.storage,
+            (&lp_token, &owner),
+            |balance| -> StdResult<_> { Ok(balance.unwrap_or_default().checked_add(shares)?) },
+        )?;
+
+        // The deposit pays out the pending rewards of the contract
+        let prev_balances = compound_balances(deps.as_ref(), &env, &config, &[])?;
+
+        Ok(Response::new()
+            .add_message(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: lp_token.to_string(),
+                funds: vec![],
+                msg: to_binary(&Cw20ExecuteMsg::Send {
+                    contract: generator.to_string(),
+                    amount,
+                    msg: to_binary(&GeneratorCw20HookMsg::Deposit {})?,
+                })?,
+            }))
+            .add_message(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: env.contract.address.to_string(),
+                funds: vec![],
+                msg: to_binary(&ExecuteMsg::EscrowCompoundLeftovers {
+                    lp_token: lp_token.to_string(),
+                    prev_balances,
+                })?,
+            }))
+            .add_attribute("action", "deposit_compound")
+            .add_attribute("lp_token", lp_token)
+            .add_attribute("owner", owner)
+            .add_attribute("amount", amount)
+            .add_attribute("shares", shares))
+    }
+
+    /// Burns compounding shares of the sender and returns the LP tokens they are worth.
+    pub fn withdraw_compound(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        lp_token: String,
+        shares: Uint128,
+    ) -> Result<Response, ContractError> {
+        let generator = GENERATOR
+            .may_load(deps.storage)?
+            .ok_or(ContractError::GeneratorNotSet {})?;
+        let lp_token = deps.api.addr_validate(&lp_token)?;
+        let config = load_compound_config(deps.storage, &lp_token)?;
+
+        let balance = COMPOUND_SHARES
+            .may_load(deps.storage, (&lp_token, &info.sender))?
+            .unwrap_or_default();
+        let total_shares = COMPOUND_TOTAL_SHARES
+            .may_load(deps.storage, &lp_token)?
+            .unwrap_or_default();
+        let total_lp = query_compound_deposit(deps.as_ref(), &env, &generator, &lp_token)?;
+        let amount = compound_lp_amount(shares, total_shares, total_lp);
+
+        let balance = balance.checked_sub(shares)?;
+        if balance.is_zero() {
+            COMPOUND_SHARES.remove(deps.storage, (&lp_token, &info.sender));
+        } else {
+            COMPOUND_SHARES.save(deps.storage, (&lp_token, &info.sender), &balance)?;
+        }
+        COMPOUND_TOTAL_SHARES.save(deps.storage, &lp_token, &(total_shares - shares))?;
+
+        // The withdrawal pays out the pending rewards of the contract
+        let prev_balances = compound_balances(deps.as_ref(), &env, &config, &[])?;
+
+        Ok(Response::new()
+            .add_message(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: generator.to_string(),
+                funds: vec![],
+                msg: to_binary(&GeneratorExecuteMsg::Withdraw {
+                    lp_token: lp_token.to_string(),
+                    amount,
+                })?,
+            }))
+            .add_message(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: lp_token.to_string(),
+                funds: vec![],
+                msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                    recipient: info.sender.to_string(),
+                    amount,
+                })?,
+            }))
+            .add_message(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: env.contract.address.to_string(),
+                funds: vec![],
+                msg: to_binary(&ExecuteMsg::EscrowCompoundLeftovers {
+                    lp_token: lp_token.to_string(),
+                    prev_balances,
+                })?,
+            }))
+            .add_attribute("action", "withdraw_compound")
+            .add_attribute("lp_token", lp_token)
+            .add_attribute("owner", info.sender)
+            .add_attribute("shares", shares)
+            .add_attribute("amount", amount))
+    }
+
+    /// Claims the generator rewards of an LP token and compounds the ones above their dust
+    /// threshold, together with the escrowed leftovers, back into the staked position. Anyone
+    /// can execute it.
+    pub fn compound(deps: DepsMut, env: Env, lp_token: String) -> Result<Response, ContractError> {
+        let generator = GENERATOR
+            .may_load(deps.storage)?
+            .ok_or(ContractError::GeneratorNotSet {})?;
+        let lp_token = deps.api.addr_validate(&lp_token)?;
+        let config = load_compound_config(deps.storage, &lp_token)?;
+        let idle_assets = COMPOUND_IDLE
+            .may_load(deps.storage, &lp_token)?
+            .unwrap_or_default();
+
+        let generator_config: GeneratorConfig = deps
+            .querier
+            .query_wasm_smart(&generator, &GeneratorQueryMsg::Config {})?;
+        let pending: PendingTokenResponse = deps.querier.query_wasm_smart(
+            &generator,
+            &GeneratorQueryMsg::PendingToken {
+                lp_token: lp_token.to_string(),
+                user: env.contract.address.to_string(),
+            },
+        )?;
+        let pending_amount = |asset_info: &AssetInfo| {
+            if asset_info.equal(&generator_config.astro_token) {
+                return pending.pending;
+            }
+            pending
+                .pending_on_proxy
+                .iter()
+                .flatten()
+                .find(|asset| asset.info.equal(asset_info))
+                .map(|asset| asset.amount)
+                .unwrap_or_default()
+        };
+
+        // Dust rewards keep accruing until a later harvest
+        let reward_routes = config
+            .reward_routes
+            .iter()
+            .filter(|route| {
+                let amount = pending_amount(&route.reward_asset_info)
+                    + asset_amount(&idle_assets, &route.reward_asset_info);
+                !amount.is_zero() && amount >= route.min_harvest
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        if reward_routes.is_empty() {
+            return Ok(Response::new()
+                .add_attribute("action", "compound")
+                .add_attribute("lp_token", lp_token)
+                .add_attribute("skipped", "dust"));
+        }
+
+        // The leftovers are compounded with the claimed rewards and escrowed again if unused
+        for asset in &idle_assets {
+            release(deps.storage, asset)?;
+        }
+        COMPOUND_IDLE.remove(deps.storage, &lp_token);
+        let prev_balances = compound_balances(deps.as_ref(), &env, &config, &idle_assets)?;
+
+        Ok(Response::new()
+            .add_message(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: generator.to_string(),
+                funds: vec![],
+                msg: to_binary(&GeneratorExecuteMsg::ClaimRewards {
+                    lp_tokens: vec![lp_token.to_string()],
+                })?,
+            }))
+            .add_message(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: env.contract.address.to_string(),
+                funds: vec![],
+                msg: to_binary(&ExecuteMsg::CompoundSwap {
+                    lp_token: lp_token.to_string(),
+                    reward_routes,
+                    prev_balances: prev_balances.clone(),
+                })?,
+            }))
+            .add_message(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: env.contract.address.to_string(),
+                funds: vec![],
+                msg: to_binary(&ExecuteMsg::EscrowCompoundLeftovers {
+                    lp_token: lp_token.to_string(),
+                    prev_balances,
+                })?,
+            }))
+            .add_attribute("action", "compound")
+            .add_attribute("lp_token", lp_token)
+            .add_attribute("pool", config.pool))
+    }
+
+    /// Routes the claimed rewards into the pool asset and zaps them back into the staked
+    /// position. Only the contract itself can execute it.
+    pub fn compound_swap(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        lp_token: String,
+        reward_routes: Vec<CompoundRoute>,
+        prev_balances: Vec<AstroportAsset>,
+    ) -> Result<Response, ContractError> {
+        if info.sender != env.contract.address {
+            return Err(ContractError::Unauthorized {});
+        }
+        let lp_token = deps.api.addr_validate(&lp_token)?;
+        let config = load_compound_config(deps.storage, &lp_token)?;
+
+        let mut response = Response::new();
+        for route in reward_routes {
+            let claimed = route
+                .reward_asset_info
+                .query_pool(&deps.querier, &env.contract.address)?
+                .checked_sub(asset_amount(&prev_balances, &route.reward_asset_info))?;
+            response = response.add_attribute(
+                "claimed",
+                AstroportAsset {
+                    info: route.reward_asset_info.clone(),
+                    amount: claimed,
+                }
+                .to_string(),
+            );
+
+            // Rewards paid in the pool asset are zapped without a route
+            if !claimed.is_zero() && !route.steps.is_empty() {
+                response = response.add_messages(route_messages(
+                    &env,
+                    route.steps,
+                    Some(claimed),
+                    &env.contract.address,
+                    &env.contract.address,
+                )?);
+            }
+        }
+
+        Ok(response
+            .add_message(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: env.contract.address.to_string(),
+                funds: vec![],
+                msg: to_binary(&ExecuteMsg::ZapInSwap {
+                    pool: config.pool.to_string(),
+                    dex: ASTROPORT.to_string(),
+                    prev_balance: asset_amount(&prev_balances, &config.asset_info),
+                    asset_info: config.asset_info,
+                    belief_price: None,
+                    slippage_tolerance: config.max_spread,
+                    auto_stake: Some(true),
+                    receiver: env.contract.address.to_string(),
+                    refund_to: env.contract.address.to_string(),
+                })?,
+            }))
+            .add_attribute("action", "compound_swap"))
+    }
+
+    /// Escrows the assets of the compounding positions that arrived since `prev_balances`, so
+    /// that swap hops can't spend them. Only the contract itself can execute it.
+    pub fn escrow_compound_leftovers(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        lp_token: String,
+        prev_balances: Vec<AstroportAsset>,
+    ) -> Result<Response, ContractError> {
+        if info.sender != env.contract.address {
+            return Err(ContractError::Unauthorized {});
+        }
+        let lp_token = deps.api.addr_validate(&lp_token)?;
+
+        let mut idle_assets = COMPOUND_IDLE
+            .may_load(deps.storage, &lp_token)?
+            .unwrap_or_default();
+        let mut response = Response::new();
+        for prev in prev_balances {
+            let leftover = AstroportAsset {
+                amount: prev
+                    .info
+                    .query_pool(&deps.querier, &env.contract.address)?
+                    .saturating_sub(prev.amount),
+                info: prev.info,
+            };
+            if leftover.amount.is_zero() {
+                continue;
+            }
+
+            escrow(deps.storage, &leftover)?;
+            response = response.add_attribute("leftover", leftover.to_string());
+            match idle_assets
+                .iter_mut()
+                .find(|asset| asset.info.equal(&leftover.info))
+            {
+                Some(asset) => asset.amount = asset.amount.checked_add(leftover.amount)?,
+                None => idle_assets.push(leftover),
+            }
+        }
+        COMPOUND_IDLE.save(deps.storage, &lp_token, &idle_assets)?;
+
+        Ok(response
+            .add_attribute("action", "escrow_compound_leftovers")
+            .add_attribute("lp_token", lp_token))
+    }
+
+    fn load_compound_config(
+        storage: &dyn Storage,
+        lp_token: &Addr,
+    ) -> Result<CompoundConfig, ContractError> {
+        COMPOUND_CONFIGS
+            .may_load(storage, lp_token)?
+            .ok_or_else(|| ContractError::CompoundConfigNotSet {
+                lp_token: lp_token.to_string(),
+            })
+    }
+
+    /// Returns the contract balances of the reward and pool assets of a compound config, without
+    /// the given leftovers.
+    fn compound_balances(
+        deps: Deps,
+        env: &Env,
+        config: &CompoundConfig,
+        idle_assets: &[AstroportAsset],
+    ) -> StdResult<Vec<AstroportAsset>> {
+        let mut balances: Vec<AstroportAsset> = vec![];
+        let asset_infos = config
+            .reward_routes
+            .iter()
+            .map(|route| &route.reward_asset_info)
+            .chain(&config.pool_asset_infos);
+        for asset_info in asset_infos {
+            if balances.iter().any(|balance| balance.info.equal(asset_info)) {
+                continue;
+            }
+            let balance = asset_info.query_pool(&deps.querier, &env.contract.address)?;
+            balances.push(AstroportAsset {
+                info: asset_info.clone(),
+                amount: balance.saturating_sub(asset_amount(idle_assets, asset_info)),
+            });
+        }
+        Ok(balances)
+    }
+
+    fn asset_amount(assets: &[AstroportAsset], asset_info: &AssetInfo) -> Uint128 {
+        assets
+            .iter()
+            .find(|asset| asset.info.equal(asset_info))
+            .map(|asset| asset.amount)
+            .unwrap_or_default()
+    }
+
+    /// Returns the LP amount compounding shares are worth.
+    pub fn compound_lp_amount(
+        shares: Uint128,
+        total_shares: Uint128,
+        total_lp: Uint128,
+    ) -> Uint128 {
+        shares
+            .multiply_ratio(
+                total_lp + Uint128::new(VIRTUAL_COMPOUND_LP),
+                total_shares + Uint128::new(VIRTUAL_COMPOUND_SHARES),
+            )
+            .min(total_lp)
+    }
+
+    /// Returns the LP amount the contract has staked in the generator.
+    pub fn query_compound_deposit(
+        deps: Deps,
+        env: &Env,
+        generator: &Addr,
+        lp_token: &Addr,
+    ) -> StdResult<Uint128> {
+        deps.querier.query_wasm_smart(
+            generator,
+            &GeneratorQueryMsg::Deposit {
+                lp_token: lp_token.to_string(),
+                user: env.contract.address.to_string(),
+            },
+        )
+    }
+
+    /// Returns the amount a hop may swap: `offer_amount` or the whole contract balance, without
+    /// the funds escrowed for open orders.
+    fn spendable_amount(
+        deps: Deps,
+        env: &Env,
+        asset_info: &AssetInfo,
+        offer_amount: Option<Uint128>,
+    ) -> Result<Uint128, ContractError> {
+        let balance = asset_info.query_pool(&deps.querier, &env.contract.address)?;
+        let escrowed = ESCROWED
+            .may_load(deps.storage, asset_info.to_string())?
+            .unwrap_or_default();
+        let available = balance.saturating_sub(escrowed);
+        match offer_amount {
+            Some(offer_amount) if offer_amount > available => Err(ContractError::InsufficientFunds {
+                available,
+                required: offer_amount,
+            }),
+            Some(offer_amount) => Ok(offer_amount),
+            None => Ok(available),
+        }
+    }
+
+    fn escrow(storage: &mut dyn Storage, asset: &AstroportAsset) -> StdResult<()> {
+        ESCROWED.update(storage, asset.info.to_string(), |escrowed| -> StdResult<_> {
+            Ok(escrowed.unwrap_or_default().checked_add(asset.amount)?)
+        })?;
+        Ok(())
+    }
+
+    fn release(storage: &mut dyn Storage, asset: &AstroportAsset) -> StdResult<()> {
+        let escrowed = ESCROWED
+            .load(storage, asset.info.to_string())?
+            .checked_sub(asset.amount)?;
+        if escrowed.is_zero() {
+            ESCROWED.remove(storage, asset.info.to_string());
+        } else {
+            ESCROWED.save(storage, asset.info.to_string(), &escrowed)?;
+        }
+        Ok(())
+    }
+
+    /// Escrows an asset that returned to the contract and credits it to a recovery address.
+    pub(crate) fn credit_recovery(
+        storage: &mut dyn Storage,
+        recovery_addr: &Addr,
+        asset: &AstroportAsset,
+    ) -> StdResult<()> {
+        escrow(storage, asset)?;
+        let mut assets = RECOVERIES
+            .may_load(storage, recovery_addr)?
+            .unwrap_or_default();
+        match assets.iter_mut().find(|credit| credit.info.equal(&asset.info)) {
+            Some(credit) => credit.amount = credit.amount.checked_add(asset.amount)?,
+            None => assets.push(asset.clone()),
+        }
+        RECOVERIES.save(storage, recovery_addr, &assets)
+    }
+
+    /// Removes a limit order with its indexes and releases its escrow.
+    fn remove_order(storage: &mut dyn Storage, order: &LimitOrder) -> StdResult<()> {
+        LIMIT_ORDERS.remove(storage, order.id);
+        ORDERS_BY_OWNER.remove(storage, (&order.owner, order.id));
+        ORDERS_BY_PAIR.remove(
+            storage,
+            (
+                pair_key(&order.offer_asset.info, &order.target_asset_info),
+                order.id,
+            ),
+        );
+        release(storage, &order.offer_asset)
+    }
+
+    /// Simulates every hop of a route and returns the amount of its ask asset.
+    fn simulate_route(
+        deps: Deps,
+        steps: &[SwapOperation],
+        offer_amount: Uint128,
+    ) -> Result<Uint128, ContractError> {
+        let mut amount = offer_amount;
+        for operation in steps {
+            let offer_asset = AstroportAsset {
+                info: operation_offer_asset_info(operation),
+                amount,
+            };
+            amount = match operation {
+                SwapOperation::SparrowSwap { pool_address, .. } => {
+                    registered_adapter(SPARROWSWAP)?
+                        .simulate(&deps.querier, pool_address, &offer_asset, None)?
+                        .return_amount
+                }
+                SwapOperation::AstroportSwap {
+                    pool_address,
+                    ask_asset_info,
+                    ..
+                } => {
+                    registered_adapter(ASTROPORT)?
+                        .simulate(
+                            &deps.querier,
+                            pool_address,
+                            &offer_asset,
+                            ask_asset_info.clone(),
+                        )?
+                        .return_amount
+                }
+                SwapOperation::Swap {
+                    dex,
+                    pool_address,
+                    ask_asset_info,
+                    ..
+                } => {
+                    registered_adapter(dex)?
+                        .simulate(
+                            &deps.querier,
+                            pool_address,
+                            &offer_asset,
+                            ask_asset_info.clone(),
+                        )?
+                        .return_amount
+                }
+                SwapOperation::Rfq { maker, quote, .. } => {
+                    let filled = RFQ_FILLED
+                        .may_load(deps.storage, (&deps.api.addr_validate(maker)?, quote.nonce))?
+                        .unwrap_or_default();
+                    let remaining = quote.offer_amount.saturating_sub(filled);
+                    quote
+                        .ask_amount
+                        .multiply_ratio(amount.min(remaining), quote.offer_amount)
+                }
+            };
+        }
+        Ok(amount)
+    }
+
+    /// Returns the cumulative price of an asset in the other asset of a two-asset pool.
+    fn query_cumulative_price(
+        deps: Deps,
+        pool: &Addr,
+        dex: &str,
+        asset_info: &AssetInfo,
+    ) -> Result<Uint128, ContractError> {
+        registered_adapter(dex)?
+            .cumulative_price(&deps.querier, pool.as_str(), asset_info)?
+            .ok_or_else(|| ContractError::AssetNotInPool {
+                asset: asset_info.to_string(),
+            })
+    }
+
+    /// Returns the mid price of a pool as equivalent amounts of its two assets, its reserves.
+    fn mid_prices(deps: Deps, pool: &Addr, dex: &str) -> Result<Vec<Uint128>, ContractError> {
+        let prices = query_pool_assets(deps, pool, dex)?
+            .into_iter()
+            .map(|asset| asset.amount)
+            .collect::<Vec<_>>();
+        if prices.iter().any(|price| price.is_zero()) {
+            return Err(ContractError::Std(StdError::generic_err(
+                "The pool has no liquidity",
+            )));
+        }
+        Ok(prices)
+    }
+
+    /// Returns the side of a batch whose total exceeds the other side valued at the clearing
+    /// price, and the exceeding amount.
+    fn net_imbalance(totals: &[Uint128], prices: &[Uint128]) -> (usize, Uint128) {
+        let matched = totals[1].multiply_ratio(prices[0], prices[1]);
+        if totals[0] >= matched {
+            (0, totals[0] - matched)
+        } else {
+            let matched = totals[0].multiply_ratio(prices[1], prices[0]);
+            (1, totals[1].saturating_sub(matched))
+        }
+    }
+
+    /// Checks that the generator has a pool for the LP token, so that a deposit can't fail after
+    /// the liquidity is provided.
+    fn assert_generator_pool(
+        deps: Deps,
+        generator: &Addr,
+        lp_token: &Addr,
+    ) -> Result<(), ContractError> {
+        deps.querier
+            .query_wasm_smart::<PoolInfoResponse>(
+                generator,
+                &GeneratorQueryMsg::PoolInfo {
+                    lp_token: lp_token.to_string(),
+                },
+            )
+            .map_err(|_| ContractError::GeneratorPoolNotFound {
+                lp_token: lp_token.to_string(),
+            })?;
+        Ok(())
+    }
+
+    fn assert_owner(deps: Deps, info: &MessageInfo) -> Result<(), ContractError> {
+        if STATE.load(deps.storage)?.owner != info.sender {
+            return Err(ContractError::Unauthorized {});
+        }
+        Ok(())
+    }
+
+    /// Returns whether an oracle is configured for any pair that includes the asset.
+    fn has_pair_oracle(storage: &dyn Storage, asset_info: &AssetInfo) -> StdResult<bool> {
+        let asset = asset_info.to_string();
+        for key in PAIR_ORACLES.keys(storage, None, None, Order::Ascending) {
+            let (a, b) = key?;
+            if a == asset || b == asset {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Returns the amount of the ask asset the oracle quotes for the offer asset.
+    fn oracle_quote(
+        deps: Deps,
+        oracle: &Addr,
+        offer_asset: &AstroportAsset,
+        ask_asset_info: &AssetInfo,
+    ) -> StdResult<Uint128> {
+        let prices: Vec<(AssetInfo, Uint256)> = deps.querier.query_wasm_smart(
+            oracle,
+            &OracleQueryMsg::Consult {
+                token: offer_asset.info.clone(),
+                amount: offer_asset.amount,
+            },
+        )?;
+        prices
+            .into_iter()
+            .find(|(asset_info, _)| asset_info.equal(ask_asset_info))
+            .map(|(_, amount)| Uint128::try_from(amount).map_err(StdError::from))
+            .transpose()?
+            .ok_or_else(|| {
+                StdError::generic_err(format!("The oracle has no price for {ask_asset_info}"))
+            })
+    }
+
+    /// Checks a hop's return amount against the oracle configured for the pair, if there is one.
+    /// The simulation is only queried when an oracle is configured.
+    fn assert_oracle_price(
+        deps: Deps,
+        offer_asset: &AstroportAsset,
+        ask_asset_info: &AssetInfo,
+        simulate_return: impl FnOnce() -> StdResult<Uint128>,
+    ) -> Result<(), ContractError> {
+        let pair_oracle = match PAIR_ORACLES
+            .may_load(deps.storage, pair_key(&offer_asset.info, ask_asset_info))?
+        {
+            Some(pair_oracle) => pair_oracle,
+            None => return Ok(()),
+        };
+
+        let expected = oracle_quote(deps, &pair_oracle.oracle, offer_asset, ask_asset_info)?;
+        let amount = simulate_return()?;
+
+        let deviation = if expected.is_zero() {
+            Decimal::one()
+        } else {
+            let diff = if amount > expected {
+                amount - expected
+            } else {
+                expected - amount
+            };
+            Decimal::from_ratio(diff, expected)
+        };
+        if deviation > pair_oracle.max_deviation {
+            return Err(ContractError::OraclePriceDeviation {
+                expected,
+                amount,
+                max_deviation: pair_oracle.max_deviation,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+pub mod reply {
+    use super::*;
+
+    /// Reports the result of a single batch swap and refunds its offer asset if it failed.
+    pub fn batch_swap(deps: DepsMut, result: SubMsgResult) -> Result<Response, ContractError> {
+        let mut pending = PENDING_BATCH_SWAPS.load(deps.storage)?;
+        if pending.is_empty() {
+            return Err(ContractError::Std(StdError::generic_err(
+                "No pending batch swap",
+            )));
+        }
+        let swap = pending.remove(0);
+        if pending.is_empty() {
+            PENDING_BATCH_SWAPS.remove(deps.storage);
+        } else {
+            PENDING_BATCH_SWAPS.save(deps.storage, &pending)?;
+        }
+
+        let event = Event::new("batch_swap")
+            .add_attribute("index", swap.index.to_string())
+            .add_attribute("offer_asset", swap.offer_asset.to_string())
+            .add_attribute("target_asset", swap.target_asset_info.to_string())
+            .add_attribute("receiver", swap.receiver.to_string());
+
+        match result {
+            SubMsgResult::Ok(_) => {
+                let receiver_balance = swap
+                    .target_asset_info
+                    .query_pool(&deps.querier, &swap.receiver)?;
+                let return_amount = receiver_balance.saturating_sub(swap.prev_balance);
+                Ok(Response::new().add_event(
+                    event
+                        .add_attribute("status", "success")
+                        .add_attribute("return_amount", return_amount),
+                ))
+            }
+            SubMsgResult::Err(err) => Ok(Response::new()
+                .add_message(swap.offer_asset.into_msg(swap.refund_to)?)
+                .add_event(
+                    event
+                        .add_attribute("status", "failed")
+                        .add_attribute("error", err),
+                )),
+        }
+    }
+
+    /// Credits the offer of a failed recoverable route to its recovery address.
+    pub fn recoverable_swap(deps: DepsMut, result: SubMsgResult) -> Result<Response, ContractError> {
+        let pending = PENDING_RECOVERY.load(deps.storage)?;
+        PENDING_RECOVERY.remove(deps.storage);
+
+        let event = Event::new("recoverable_swap")
+            .add_attribute("offer_asset", pending.offer_asset.to_string())
+            .add_attribute("recovery_addr", pending.recovery_addr.to_string());
+        match result {
+            SubMsgResult::Ok(_) => {
+                Ok(Response::new().add_event(event.add_attribute("status", "success")))
+            }
+            SubMsgResult::Err(err) => {
+                if !pending.offer_asset.amount.is_zero() {
+                    execute::credit_recovery(
+                        deps.storage,
+                        &pending.recovery_addr,
+                        &pending.offer_asset,
+                    )?;
+                }
+                Ok(Response::new().add_event(
+                    event
+                        .add_attribute("status", "failed")
+                        .add_attribute("error", err),
+                ))
+            }
+        }
+    }
+
+    /// Delivers the asset of a claimed hash time-locked swap as is when its route failed.
+    pub fn claim_route(deps: DepsMut, result: SubMsgResult) -> Result<Response, ContractError> {
+        let err = match result {
+            SubMsgResult::Ok(_) => {
+                return Err(ContractError::Std(StdError::generic_err(
+                    "Claim routes only reply on error",
+                )))
+            }
+            SubMsgResult::Err(err) => err,
+        };
+        // The failed route reverted its removal of the pending claim
+        let lock = PENDING_CLAIM.load(deps.storage)?;
+        PENDING_CLAIM.remove(deps.storage);
+
+        Ok(Response::new()
+            .add_message(lock.asset.clone().into_msg(&lock.recipient)?)
+            .add_event(
+                Event::new("claim_route")
+                    .add_attribute("status", "failed")
+                    .add_attribute("error", err)
+                    .add_attribute("recipient", lock.recipient.to_string())
+                    .add_attribute("asset", lock.asset.to_string()),
+            ))
+    }
+
+    /// Records an outgoing IBC transfer under the packet sequence returned by the transfer module.
+    pub fn ibc_transfer(deps: DepsMut, result: SubMsgResult) -> Result<Response, ContractError> {
+        let response = result.into_result().map_err(StdError::generic_err)?;
+        let data = response
+            .data
+            .ok_or_else(|| StdError::generic_err("Missing MsgTransferResponse"))?;
+        let sequence = decode_transfer_sequence(data.as_slice())?;
+
+        let transfer = PENDING_IBC_TRANSFER.load(deps.storage)?;
+        PENDING_IBC_TRANSFER.remove(deps.storage);
+        IBC_TRANSFERS.save(deps.storage, (&transfer.channel, sequence), &transfer)?;
+        IBC_TRANSFERS_BY_OWNER.save(
+            deps.storage,
+            (&transfer.owner, &transfer.channel, sequence),
+            &Empty {},
+        )?;
+
+        Ok(Response::new().add_event(
+            Event::new("ibc_transfer")
+                .add_attribute("channel", transfer.channel)
+                .add_attribute("sequence", sequence.to_string())
+                .add_attribute("owner", transfer.owner.to_string())
+                .add_attribute("asset", transfer.asset.to_string()),
+        ))
+    }
+
+    /// Forwards the swap result of the callback as the data of the `Unxswap` response.
+    pub fn swap_callback(result: SubMsgResult) -> Result<Response, ContractError> {
+        let response = result
+            .into_result()
+            .map_err(StdError::generic_err)?;
+
+        let mut res = Response::new();
+        if let Some(data) = response.data {
+            res = res.set_data(data);
+        }
+        Ok(res)
+    }
+    /// Stakes the LP tokens minted by a `ProvideAndStake` provision in the generator.
+    pub fn provide_liquidity(
+        deps: DepsMut,
+        env: Env,
+        stake_id: u32,
+        result: SubMsgResult,
+    ) -> Result<Response, ContractError> {
+        result.into_result().map_err(StdError::generic_err)?;
+
+        let pending = PENDING_STAKES.load(deps.storage, stake_id)?;
+        let generator = GENERATOR.load(deps.storage)?;
+        let lp_amount = query_token_balance(
+            &deps.querier,
+            &pending.lp_token,
+            &env.contract.address,
+        )?
+        .checked_sub(pending.prev_lp_balance)?;
+
+        let deposit_msg = CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: pending.lp_token.to_string(),
+            funds: vec![],
+            msg: to_binary(&Cw20ExecuteMsg::Send {
+                contract: generator.to_string(),
+                amount: lp_amount,
+                msg: to_binary(&GeneratorCw20HookMsg::DepositFor(
+                    pending.receiver.to_string(),
+                ))?,
+            })?,
+        });
+
+        Ok(Response::new()
+            .add_submessage(SubMsg::reply_on_success(
+                deposit_msg,
+                STAKE_REPLY_ID + u64::from(stake_id),
+            ))
+            .add_event(
+                Event::new("provide_liquidity")
+                    .add_attribute("lp_token", pending.lp_token.to_string())
+                    .add_attribute("lp_amount", lp_amount),
+            ))
+    }
+
+    /// Reports the amount staked for the receiver of a `ProvideAndStake` provision.
+    pub fn stake(
+        deps: DepsMut,
+        stake_id: u32,
+        result: SubMsgResult,
+    ) -> Result<Response, ContractError> {
+        result.into_result().map_err(StdError::generic_err)?;
+
+        let pending = PENDING_STAKES.load(deps.storage, stake_id)?;
+        PENDING_STAKES.remove(deps.storage, stake_id);
+        let generator = GENERATOR.load(deps.storage)?;
+        let deposit: Uint128 = deps.querier.query_wasm_smart(
+            &generator,
+            &GeneratorQueryMsg::Deposit {
+                lp_token: pending.lp_token.to_string(),
+                user: pending.receiver.to_string(),
+            },
+        )?;
+
+        Ok(Response::new().add_event(
+            Event::new("stake")
+                .add_attribute("generator", generator.to_string())
+                .add_attribute("lp_token", pending.lp_token.to_string())
+                .add_attribute("receiver", pending.receiver.to_string())
+                .add_attribute("staked_amount", deposit.saturating_sub(pending.prev_deposit))
+                .add_attribute("deposit", deposit),
+        ))
+    }
+}
+
+pub mod sudo {
+    use super::*;
+
+    /// Settles an outgoing IBC transfer once its packet is acknowledged or times out. The funds
+    /// of a failed transfer are back in the contract and are credited to its recovery address.
+    pub fn ibc_lifecycle_complete(
+        deps: DepsMut,
+        channel: String,
+        sequence: u64,
+        success: bool,
+    ) -> Result<Response, ContractError> {
+        let transfer = match IBC_TRANSFERS.may_load(deps.storage, (&channel, sequence))? {
+            Some(transfer) => transfer,
+            None => {
+                return Ok(Response::new()
+                    .add_attribute("action", "ibc_lifecycle_complete")
+                    .add_attribute("channel", channel)
+                    .add_attribute("sequence", sequence.to_string())
+                    .add_attribute("status", "unknown"))
+            }
+        };
+        IBC_TRANSFERS.remove(deps.storage, (&channel, sequence));
+        IBC_TRANSFERS_BY_OWNER.remove(deps.storage, (&transfer.owner, &channel, sequence));
+        if !success {
+            execute::credit_recovery(deps.storage, &transfer.recovery_addr, &transfer.asset)?;
+        }
+
+        Ok(Response::new()
+            .add_attribute("action", "ibc_lifecycle_complete")
+            .add_attribute("channel", channel)
+            .add_attribute("sequence", sequence.to_string())
+            .add_attribute("status", if success { "success" } else { "failed" })
+            .add_attribute("owner", transfer.owner)
+            .add_attribute("asset", transfer.asset.to_string())
+            .add_attribute("recovery_addr", transfer.recovery_addr))
+    }
+}
+
+pub mod query {
+    use super::*;
+
+    pub fn count(deps: Deps) -> StdResult<GetCountResponse> {
+        let state = STATE.load(deps.storage)?;
+        Ok(GetCountResponse { count: state.count })
+    }
+
+    pub fn pair_oracle(
+        deps: Deps,
+        asset_infos: [AssetInfo; 2],
+    ) -> StdResult<Option<PairOracleResponse>> {
+        let pair_oracle =
+            PAIR_ORACLES.may_load(deps.storage, pair_key(&asset_infos[0], &asset_infos[1]))?;
+        Ok(pair_oracle.map(|pair_oracle| PairOracleResponse {
+            oracle: pair_oracle.oracle,
+            max_deviation: pair_oracle.max_deviation,
+        }))
+    }
+
+    pub fn compound_position(
+        deps: Deps,
+        env: Env,
+        lp_token: String,
+        owner: String,
+    ) -> StdResult<CompoundPositionResponse> {
+        let lp_token = deps.api.addr_validate(&lp_token)?;
+        let owner = deps.api.addr_validate(&owner)?;
+        let shares = COMPOUND_SHARES
+            .may_load(deps.storage, (&lp_token, &owner))?
+            .unwrap_or_default();
+        let total_shares = COMPOUND_TOTAL_SHARES
+            .may_load(deps.storage, &lp_token)?
+            .unwrap_or_default();
+
+        let lp_amount = match GENERATOR.may_load(deps.storage)? {
+            Some(generator) if !total_shares.is_zero() => {
+                let total_lp = execute::query_compound_deposit(deps, &env, &generator, &lp_token)?;
+                execute::compound_lp_amount(shares, total_shares, total_lp)
+            }
+            _ => Uint128::zero(),
+        };
+
+        Ok(CompoundPositionResponse { shares, lp_amount })
+    }
+
+    pub fn compound_config(
+        deps: Deps,
+        lp_token: String,
+    ) -> StdResult<Option<CompoundConfigResponse>> {
+        let lp_token = deps.api.addr_validate(&lp_token)?;
+        let config = match COMPOUND_CONFIGS.may_load(deps.storage, &lp_token)? {
+            Some(config) => config,
+            None => return Ok(None),
+        };
+        let idle_assets = COMPOUND_IDLE
+            .may_load(deps.storage, &lp_token)?
+            .unwrap_or_default();
+
+        Ok(Some(CompoundConfigResponse {
+            pool: config.pool,
+            asset_info: config.asset_info,
+            reward_routes: config.reward_routes,
+            max_spread: config.max_spread,
+            idle_assets,
+        }))
+    }
+
+    pub fn order(deps: Deps, id: u64) -> StdResult<OrderResponse> {
+        LIMIT_ORDERS.load(deps.storage, id).map(order_response)
+    }
+
+    pub fn orders_by_owner(
+        deps: Deps,
+        owner: String,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    ) -> StdResult<OrdersResponse> {
+        let owner = deps.api.addr_validate(&owner)?;
+        let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+        let orders = ORDERS_BY_OWNER
+            .prefix(&owner)
+            .keys(
+                deps.storage,
+                start_after.map(Bound::exclusive),
+                None,
+                Order::Ascending,
+            )
+            .take(limit)
+            .map(|id| order(deps, id?))
+            .collect::<StdResult<Vec<_>>>()?;
+        Ok(OrdersResponse { orders })
+    }
+
+    pub fn orders_by_pair(
+        deps: Deps,
+        asset_infos: [AssetInfo; 2],
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    ) -> StdResult<OrdersResponse> {
+        let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+        let orders = ORDERS_BY_PAIR
+            .prefix(pair_key(&asset_infos[0], &asset_infos[1]))
+            .keys(
+                deps.storage,
+                start_after.map(Bound::exclusive),
+                None,
+                Order::Ascending,
+            )
+            .take(limit)
+            .map(|id| order(deps, id?))
+            .collect::<StdResult<Vec<_>>>()?;
+        Ok(OrdersResponse { orders })
+    }
+
+    fn order_response(order: LimitOrder) -> OrderResponse {
+        OrderResponse {
+            id: order.id,
+            owner: order.owner,
+            offer_asset: order.offer_asset,
+            steps: order.steps,
+            target_asset_info: order.target_asset_info,
+            min_price: order.min_price,
+            expiry: order.expiry,
+        }
+    }
+
+    pub fn dca(deps: Deps, id: u64) -> StdResult<DcaResponse> {
+        let schedule = DCA_SCHEDULES.load(deps.storage, id)?;
+        let next_execution = match (&schedule.status, schedule.last_execution) {
+            (DcaStatus::Active, Some(last_execution)) => {
+                Some(last_execution.plus_seconds(schedule.interval_seconds))
+            }
+            _ => None,
+        };
+        Ok(DcaResponse {
+            id: schedule.id,
+            owner: schedule.owner,
+            offer_asset: schedule.offer_asset,
+            steps: schedule.steps,
+            target_asset_info: schedule.target_asset_info,
+            chunk_amount: schedule.chunk_amount,
+            interval_seconds: schedule.interval_seconds,
+            max_price: schedule.max_price,
+            status: schedule.status,
+            executions: schedule.executions,
+            last_execution: schedule.last_execution,
+            next_execution,
+        })
+    }
+
+    pub fn dca_history(
+        deps: Deps,
+        id: u64,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    ) -> StdResult<DcaHistoryResponse> {
+        let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+        let executions = DCA_EXECUTIONS
+            .prefix(id)
+            .range(
+                deps.storage,
+                start_after.map(Bound::exclusive),
+                None,
+                Order::Ascending,
+            )
+            .take(limit)
+            .map(|item| {
+                let (_, execution) = item?;
+                Ok(DcaExecutionResponse {
+                    index: execution.index,
+                    offer_amount: execution.offer_amount,
+                    return_amount: execution.return_amount,
+                    timestamp: execution.timestamp,
+                })
+            })
+            .collect::<StdResult<Vec<_>>>()?;
+        Ok(DcaHistoryResponse { executions })
+    }
+
+    pub fn conditional_order(deps: Deps, id: u64) -> StdResult<ConditionalOrderResponse> {
+        CONDITIONAL_ORDERS
+            .load(deps.storage, id)
+            .map(conditional_order_response)
+    }
+
+    pub fn conditional_orders(
+        deps: Deps,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    ) -> StdResult<ConditionalOrdersResponse> {
+        let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+        let orders = CONDITIONAL_ORDERS
+            .range(
+                deps.storage,
+                start_after.map(Bound::exclusive),
+                None,
+                Order::Ascending,
+            )
+            .take(limit)
+            .map(|item| item.map(|(_, order)| conditional_order_response(order)))
+            .collect::<StdResult<Vec<_>>>()?;
+        Ok(ConditionalOrdersResponse { orders })
+    }
+
+    fn conditional_order_response(order: ConditionalOrder) -> ConditionalOrderResponse {
+        ConditionalOrderResponse {
+            id: order.id,
+            owner: order.owner,
+            offer_asset: order.offer_asset,
+            steps: order.steps,
+            minimum_receive: order.minimum_receive,
+            price_pool: order.price_pool,
+            dex: order.dex,
+            condition: order.condition,
+            twap_window_seconds: order.twap_window_seconds,
+            window_start: order.observation.timestamp,
+        }
+    }
+
+    pub fn signer_nonce(deps: Deps, signer: String) -> StdResult<u64> {
+        let signer = deps.api.addr_validate(&signer)?;
+        Ok(SIGNER_NONCES
+            .may_load(deps.storage, &signer)?
+            .unwrap_or_default())
+    }
+
+    pub fn rfq_maker(deps: Deps, maker: String) -> StdResult<Option<Binary>> {
+        let maker = deps.api.addr_validate(&maker)?;
+        RFQ_MAKERS.may_load(deps.storage, &maker)
+    }
+
+    pub fn commitment(
+        deps: Deps,
+        owner: String,
+        hash: Binary,
+    ) -> StdResult<Option<CommitmentResponse>> {
+        let owner = deps.api.addr_validate(&owner)?;
+        let commitment = SWAP_COMMITMENTS.may_load(deps.storage, (&owner, hash.as_slice()))?;
+        Ok(commitment.map(|commitment| CommitmentResponse {
+            owner: commitment.owner,
+            offer_asset: commitment.offer_asset,
+            height: commitment.height,
+            reveal_deadline: commitment.height + COMMIT_REVEAL_TIMEOUT_BLOCKS,
+        }))
+    }
+
+    pub fn auction_batch(
+        deps: Deps,
+        pool: String,
+        round: u64,
+    ) -> StdResult<Option<AuctionBatchResponse>> {
+        let pool = deps.api.addr_validate(&pool)?;
+        let batch = match AUCTION_BATCHES.may_load(deps.storage, (&pool, round))? {
+            Some(batch) => batch,
+            None => return Ok(None),
+        };
+        let orders = AUCTION_ORDERS
+            .prefix((&pool, round))
+            .range(deps.storage, None, None, Order::Ascending)
+            .map(|item| {
+                let (index, order) = item?;
+                Ok(AuctionOrderResponse {
+                    index,
+                    owner: order.owner,
+                    offer_asset: order.offer_asset,
+                    max_spread: order.max_spread,
+                })
+            })
+            .collect::<StdResult<Vec<_>>>()?;
+
+        Ok(Some(AuctionBatchResponse {
+            dex: batch.dex,
+            asset_infos: batch.asset_infos,
+            totals: batch.totals,
+            orders,
+            settle_height: (round + 1) * AUCTION_PERIOD_BLOCKS,
+        }))
+    }
+
+    pub fn hashed_timelock(
+        deps: Deps,
+        sender: String,
+        hashlock: Binary,
+    ) -> StdResult<Option<HashedTimelockResponse>> {
+        let sender = deps.api.addr_validate(&sender)?;
+        let lock = HASHED_TIMELOCKS.may_load(deps.storage, (&sender, hashlock.as_slice()))?;
+        Ok(lock.map(|lock| HashedTimelockResponse {
+            sender: lock.sender,
+            recipient: lock.recipient,
+            asset: lock.asset,
+            timelock: lock.timelock,
+            route: lock.route,
+            minimum_receive: lock.minimum_receive,
+        }))
+    }
+
+    pub fn recoveries(deps: Deps, addr: String) -> StdResult<Vec<AstroportAsset>> {
+        let addr = deps.api.addr_validate(&addr)?;
+        Ok(RECOVERIES.may_load(deps.storage, &addr)?.unwrap_or_default())
+    }
+
+    pub fn ibc_transfers_by_owner(
+        deps: Deps,
+        owner: String,
+        start_after: Option<(String, u64)>,
+        limit: Option<u32>,
+    ) -> StdResult<Vec<IbcTransferResponse>> {
+        let owner = deps.api.addr_validate(&owner)?;
+        let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+        let start = start_after
+            .as_ref()
+            .map(|(channel, sequence)| Bound::exclusive((channel.as_str(), *sequence)));
+        IBC_TRANSFERS_BY_OWNER
+            .sub_prefix(&owner)
+            .keys(deps.storage, start, None, Order::Ascending)
+            .take(limit)
+            .map(|key| {
+                let (channel, sequence) = key?;
+                let transfer = IBC_TRANSFERS.load(deps.storage, (&channel, sequence))?;
+                Ok(IbcTransferResponse {
+                    channel,
+                    sequence,
+                    owner: transfer.owner,
+                    asset: transfer.asset,
+                    recovery_addr: transfer.recovery_addr,
+                })
+            })
+            .collect()
     }
 }
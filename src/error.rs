@@ -1,5 +1,5 @@
 use thiserror::Error;
-use cosmwasm_std::{StdError, Uint128, OverflowError};
+use cosmwasm_std::{StdError, Uint128, OverflowError, Decimal, Timestamp};
 
 
 #[derive(Error, Debug, PartialEq)]
@@ -16,12 +16,156 @@ pub enum ContractError {
     #[error("Assertion failed; minimum receive amount: {receive}, swap amount: {amount}")]
     AssertionMinimumReceive { receive: Uint128, amount: Uint128 },
 
+    #[error("Assertion failed; minimum profit: {min_profit}, offer amount: {offer_amount}, return amount: {return_amount}")]
+    AssertionMinimumProfit {
+        min_profit: Uint128,
+        offer_amount: Uint128,
+        return_amount: Uint128,
+    },
+
+    #[error("The route must end in the offer asset {asset}")]
+    NotCyclicRoute { asset: String },
+
     #[error("The swap operation limit was exceeded!")]
     SwapLimitExceeded {},
 
     #[error("Native swap operations are not supported!")]
     NativeSwapNotSupported {},
 
+    #[error("Oracle price check failed; oracle amount: {expected}, swap amount: {amount}, max deviation: {max_deviation}")]
+    OraclePriceDeviation {
+        expected: Uint128,
+        amount: Uint128,
+        max_deviation: Decimal,
+    },
+
+    #[error("Max deviation must be lower than or equal to 1")]
+    InvalidMaxDeviation {},
+
+    #[error("Doubling assets in asset infos")]
+    DoublingAssets {},
+
+    #[error("Only native offer assets are supported!")]
+    NativeOfferRequired {},
+
+    #[error("The route must start with the offer asset {asset}")]
+    InvalidRouteOfferAsset { asset: String },
+
+    #[error("The route swaps {asset} in an intermediate hop while it is offered by another route")]
+    RouteOverlap { asset: String },
+
+    #[error("Leg weights must be positive and sum up to 10000 bps")]
+    InvalidWeights {},
+
+    #[error("A post action can't be combined with a receiver")]
+    PostActionWithReceiver {},
+
+    #[error("Asset {asset} is not in the pool")]
+    AssetNotInPool { asset: String },
+
+    #[error("The generator is not set")]
+    GeneratorNotSet {},
+
+    #[error("The generator has no pool for {lp_token}")]
+    GeneratorPoolNotFound { lp_token: String },
+
+    #[error("No compound config is set for {lp_token}")]
+    CompoundConfigNotSet { lp_token: String },
+
+    #[error("Insufficient funds; available: {available}, required: {required}")]
+    InsufficientFunds { available: Uint128, required: Uint128 },
+
+    #[error("Keeper tip must be lower than or equal to {max_tip_bps} bps")]
+    InvalidKeeperTip { max_tip_bps: u16 },
+
+    #[error("Order {id} has expired")]
+    OrderExpired { id: u64 },
+
+    #[error("Order {id} price is not reached; minimum receive: {receive}, simulated amount: {amount}")]
+    OrderPriceNotReached {
+        id: u64,
+        receive: Uint128,
+        amount: Uint128,
+    },
+
+    #[error("DCA schedule {id} is not active")]
+    DcaNotActive { id: u64 },
+
+    #[error("DCA schedule {id} can't be executed before {next_execution}")]
+    DcaNotReady { id: u64, next_execution: Timestamp },
+
+    #[error("Conditional order {id} can't be checked before {window_end}")]
+    TwapWindowNotElapsed { id: u64, window_end: Timestamp },
+
+    #[error("Invalid signature")]
+    InvalidSignature {},
+
+    #[error("Invalid nonce; expected: {expected}, got: {nonce}")]
+    InvalidNonce { expected: u64, nonce: u64 },
+
+    #[error("Swap intent has expired")]
+    IntentExpired {},
+
+    #[error("Market maker {maker} is not whitelisted")]
+    RfqMakerNotWhitelisted { maker: String },
+
+    #[error("Quote nonce {nonce} of {maker} is already filled")]
+    RfqQuoteFilled { maker: String, nonce: u64 },
+
+    #[error("Quote only has {remaining} left to fill")]
+    RfqFillExceedsQuote { remaining: Uint128 },
+
+    #[error("Quote is not made out to {taker}")]
+    RfqTakerMismatch { taker: String },
+
+    #[error("Quote has expired")]
+    RfqQuoteExpired {},
+
+    #[error("Commitment already exists")]
+    CommitmentExists {},
+
+    #[error("Commitment not found")]
+    CommitmentNotFound {},
+
+    #[error("Commitment can't be revealed in the block it was made in")]
+    RevealTooEarly {},
+
+    #[error("Commitment timed out at block {deadline}")]
+    CommitmentExpired { deadline: u64 },
+
+    #[error("Commitment can't be refunded before block {deadline}")]
+    CommitmentNotExpired { deadline: u64 },
+
+    #[error("Batch auction round {round} can't be settled before block {settle_height}")]
+    AuctionNotEnded { round: u64, settle_height: u64 },
+
+    #[error("Batch auction is full")]
+    AuctionFull {},
+
+    #[error("Batch auction orders must be worth at least {min_value} of each pool asset")]
+    AuctionOrderTooSmall { min_value: Uint128 },
+
+    #[error("Hashlock is already in use")]
+    HashlockExists {},
+
+    #[error("Hash time-locked swap not found")]
+    HashedTimelockNotFound {},
+
+    #[error("Hash time-locked swap has expired")]
+    TimelockExpired {},
+
+    #[error("Hash time-locked swap can't be refunded before its timelock expires")]
+    TimelockNotExpired {},
+
+    #[error("An IBC forward can't be combined with a receiver or a post action")]
+    IbcForwardWithReceiver {},
+
+    #[error("Only native assets can be forwarded over IBC")]
+    IbcForwardNotNative {},
+
+    #[error("No adapter is registered for DEX {dex}")]
+    UnknownDex { dex: String },
+
     #[error("Contract can't be migrated!")]
     MigrationError {},
 }
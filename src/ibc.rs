@@ -0,0 +1,130 @@
+use cosmwasm_std::{from_slice, to_vec, Binary, Coin, StdError, StdResult};
+use serde::de::IgnoredAny;
+use serde::Deserialize;
+
+pub const MSG_TRANSFER_TYPE_URL: &str = "/ibc.applications.transfer.v1.MsgTransfer";
+
+/// This structure describes an ICS-20 `MsgTransfer`. It is sent as a stargate message because
+/// `IbcMsg::Transfer` can't carry the memo that requests the ibc-hooks callback.
+pub struct MsgTransfer {
+    pub source_port: String,
+    pub source_channel: String,
+    pub token: Coin,
+    pub sender: String,
+    pub receiver: String,
+    /// The timeout as a UNIX timestamp in nanoseconds
+    pub timeout_timestamp: u64,
+    pub memo: String,
+}
+
+impl MsgTransfer {
+    /// Returns the protobuf encoding of the message.
+    pub fn encode(&self) -> Binary {
+        let mut token = vec![];
+        encode_string(&mut token, 1, &self.token.denom);
+        encode_string(&mut token, 2, &self.token.amount.to_string());
+
+        let mut buf = vec![];
+        encode_string(&mut buf, 1, &self.source_port);
+        encode_string(&mut buf, 2, &self.source_channel);
+        encode_bytes(&mut buf, 3, &token);
+        encode_string(&mut buf, 4, &self.sender);
+        encode_string(&mut buf, 5, &self.receiver);
+        if self.timeout_timestamp != 0 {
+            encode_varint(&mut buf, 7 << 3);
+            encode_varint(&mut buf, self.timeout_timestamp);
+        }
+        encode_string(&mut buf, 8, &self.memo);
+        Binary::from(buf)
+    }
+}
+
+/// Adds the ibc-hooks `ibc_callback` entry for `contract` to a JSON object memo. The memo is only
+/// checked to be an object and spliced as text, because a full JSON value parser would pull float
+/// instructions into the wasm.
+pub fn ibc_callback_memo(memo: Option<&str>, contract: &str) -> StdResult<String> {
+    let callback = format!(
+        "\"ibc_callback\":{}",
+        String::from_utf8(to_vec(contract)?).map_err(StdError::from)?
+    );
+    let memo = match memo {
+        Some(memo) => memo.trim(),
+        None => return Ok(format!("{{{callback}}}")),
+    };
+    let parsed: MemoCallback = from_slice(memo.as_bytes())
+        .map_err(|_| StdError::generic_err("IBC memo must be a JSON object"))?;
+    if parsed.ibc_callback.is_some() {
+        return Err(StdError::generic_err("IBC memo can't set ibc_callback"));
+    }
+
+    // The memo parsed as an object, so it ends with `}`. The callback goes last so it wins over a
+    // `null` entry of the same key.
+    let fields = memo[..memo.len() - 1].trim_end();
+    if fields.ends_with('{') {
+        Ok(format!("{{{callback}}}"))
+    } else {
+        Ok(format!("{fields},{callback}}}"))
+    }
+}
+
+/// The `ibc_callback` entry of a memo. Every other entry is skipped without being parsed.
+#[derive(Deserialize)]
+struct MemoCallback {
+    ibc_callback: Option<IgnoredAny>,
+}
+
+/// Returns the packet sequence of an encoded `MsgTransferResponse`.
+pub fn decode_transfer_sequence(data: &[u8]) -> StdResult<u64> {
+    let mut data = data;
+    while !data.is_empty() {
+        let key = decode_varint(&mut data)?;
+        match (key >> 3, key & 7) {
+            (1, 0) => return decode_varint(&mut data),
+            (_, 0) => {
+                decode_varint(&mut data)?;
+            }
+            (_, 2) => {
+                let len = decode_varint(&mut data)? as usize;
+                if len > data.len() {
+                    return Err(StdError::parse_err("MsgTransferResponse", "truncated field"));
+                }
+                data = &data[len..];
+            }
+            _ => return Err(StdError::parse_err("MsgTransferResponse", "unexpected wire type")),
+        }
+    }
+    Err(StdError::parse_err("MsgTransferResponse", "missing sequence"))
+}
+
+fn encode_varint(buf: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buf.push((value as u8 & 0x7f) | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+fn encode_bytes(buf: &mut Vec<u8>, field: u64, bytes: &[u8]) {
+    if bytes.is_empty() {
+        return;
+    }
+    encode_varint(buf, field << 3 | 2);
+    encode_varint(buf, bytes.len() as u64);
+    buf.extend_from_slice(bytes);
+}
+
+fn encode_string(buf: &mut Vec<u8>, field: u64, value: &str) {
+    encode_bytes(buf, field, value.as_bytes());
+}
+
+fn decode_varint(data: &mut &[u8]) -> StdResult<u64> {
+    let mut value = 0u64;
+    for (index, byte) in data.iter().enumerate().take(10) {
+        value |= u64::from(byte & 0x7f) << (7 * index);
+        if byte & 0x80 == 0 {
+            *data = &data[index + 1..];
+            return Ok(value);
+        }
+    }
+    Err(StdError::parse_err("MsgTransferResponse", "invalid varint"))
+}
@@ -1,6 +1,13 @@
+pub mod adapter;
 pub mod contract;
 mod error;
+mod ibc;
 pub mod msg;
 pub mod state;
 
 pub use crate::error::ContractError;
+
+#[cfg(test)]
+mod mock_querier;
+#[cfg(test)]
+mod testing;
@@ -0,0 +1,278 @@
+use cosmwasm_std::testing::{MockApi, MockQuerier, MockStorage, MOCK_CONTRACT_ADDR};
+use cosmwasm_std::{
+    from_slice, to_binary, Addr, Binary, Coin, ContractResult, Decimal, Empty, OwnedDeps, Querier,
+    QuerierResult, QueryRequest, SystemError, SystemResult, Uint128, Uint256, WasmQuery,
+};
+use serde::Serialize;
+use serde_json::Value;
+
+use std::collections::HashMap;
+
+use astroport_lib::asset::{Asset, AssetInfo, PairInfo};
+use astroport_lib::factory::PairType;
+use astroport_lib::pair::{
+    CumulativePricesResponse, PoolResponse, ReverseSimulationResponse, SimulationResponse,
+};
+use cw20::BalanceResponse;
+
+/// mock_dependencies is a drop-in replacement for cosmwasm_std::testing::mock_dependencies
+/// This uses a querier that mocks cw20 tokens, XYK pairs, oracles and the generator.
+pub fn mock_dependencies(
+    contract_balance: &[Coin],
+) -> OwnedDeps<MockStorage, MockApi, WasmMockQuerier> {
+    let custom_querier: WasmMockQuerier =
+        WasmMockQuerier::new(MockQuerier::new(&[(MOCK_CONTRACT_ADDR, contract_balance)]));
+
+    OwnedDeps {
+        storage: MockStorage::default(),
+        api: MockApi::default(),
+        querier: custom_querier,
+        custom_query_type: Default::default(),
+    }
+}
+
+/// This structure describes a constant product pair without fees.
+#[derive(Clone)]
+pub struct MockPair {
+    pub asset_infos: Vec<AssetInfo>,
+    pub reserves: Vec<Uint128>,
+    pub liquidity_token: Addr,
+    pub total_share: Uint128,
+    pub cumulative_prices: Vec<(AssetInfo, AssetInfo, Uint128)>,
+}
+
+impl MockPair {
+    fn pair_info(&self, contract_addr: &str) -> PairInfo {
+        PairInfo {
+            asset_infos: self.asset_infos.clone(),
+            contract_addr: Addr::unchecked(contract_addr),
+            liquidity_token: self.liquidity_token.clone(),
+            pair_type: PairType::Xyk {},
+        }
+    }
+
+    fn assets(&self) -> Vec<Asset> {
+        self.asset_infos
+            .iter()
+            .zip(&self.reserves)
+            .map(|(info, amount)| Asset {
+                info: info.clone(),
+                amount: *amount,
+            })
+            .collect()
+    }
+
+    fn reserve_of(&self, asset_info: &AssetInfo) -> Option<usize> {
+        self.asset_infos
+            .iter()
+            .position(|info| info.equal(asset_info))
+    }
+
+    fn simulate(&self, offer_asset: &Asset) -> Option<Uint128> {
+        let offer = self.reserve_of(&offer_asset.info)?;
+        let (offer_pool, ask_pool) = (self.reserves[offer], self.reserves[1 - offer]);
+        Some(ask_pool.multiply_ratio(offer_asset.amount, offer_pool + offer_asset.amount))
+    }
+
+    fn reverse_simulate(&self, ask_asset: &Asset) -> Option<Uint128> {
+        let ask = self.reserve_of(&ask_asset.info)?;
+        let (offer_pool, ask_pool) = (self.reserves[1 - ask], self.reserves[ask]);
+        let remaining = ask_pool.checked_sub(ask_asset.amount).ok()?;
+        if remaining.is_zero() {
+            return None;
+        }
+        let product = offer_pool.full_mul(ask_asset.amount);
+        let offer_amount =
+            (product + Uint256::from(remaining) - Uint256::from(1u8)) / Uint256::from(remaining);
+        offer_amount.try_into().ok()
+    }
+}
+
+pub struct WasmMockQuerier {
+    base: MockQuerier<Empty>,
+    token_balances: HashMap<String, HashMap<String, Uint128>>,
+    pairs: HashMap<String, MockPair>,
+    oracle_prices: HashMap<String, Vec<(AssetInfo, AssetInfo, Decimal)>>,
+    smart_responses: HashMap<(String, String), Binary>,
+}
+
+impl Querier for WasmMockQuerier {
+    fn raw_query(&self, bin_request: &[u8]) -> QuerierResult {
+        // MockQuerier doesn't support Custom, so we ignore it completely here
+        let request: QueryRequest<Empty> = match from_slice(bin_request) {
+            Ok(v) => v,
+            Err(e) => {
+                return SystemResult::Err(SystemError::InvalidRequest {
+                    error: format!("Parsing query request: {:?}", e),
+                    request: bin_request.into(),
+                });
+            }
+        };
+        self.handle_query(&request)
+    }
+}
+
+impl WasmMockQuerier {
+    pub fn new(base: MockQuerier<Empty>) -> Self {
+        WasmMockQuerier {
+            base,
+            token_balances: HashMap::new(),
+            pairs: HashMap::new(),
+            oracle_prices: HashMap::new(),
+            smart_responses: HashMap::new(),
+        }
+    }
+
+    pub fn handle_query(&self, request: &QueryRequest<Empty>) -> QuerierResult {
+        match &request {
+            QueryRequest::Wasm(WasmQuery::Smart { contract_addr, msg }) => {
+                let msg: Value = match serde_json::from_slice(msg) {
+                    Ok(msg) => msg,
+                    Err(_) => return SystemResult::Err(SystemError::Unknown {}),
+                };
+                let (name, params) = match msg.as_object().and_then(|msg| msg.iter().next()) {
+                    Some((name, params)) => (name.as_str(), params),
+                    None => return SystemResult::Err(SystemError::Unknown {}),
+                };
+
+                if let Some(response) = self
+                    .smart_responses
+                    .get(&(contract_addr.clone(), name.to_string()))
+                {
+                    return SystemResult::Ok(ContractResult::Ok(response.clone()));
+                }
+                if let Some(balances) = self.token_balances.get(contract_addr) {
+                    if name == "balance" {
+                        let address = params["address"].as_str().unwrap_or_default();
+                        return respond(&BalanceResponse {
+                            balance: balances.get(address).copied().unwrap_or_default(),
+                        });
+                    }
+                }
+                if let Some(pair) = self.pairs.get(contract_addr) {
+                    return handle_pair_query(contract_addr, pair, name, params);
+                }
+                if let Some(prices) = self.oracle_prices.get(contract_addr) {
+                    if name == "consult" {
+                        let token: AssetInfo = serde_json::from_value(params["token"].clone())
+                            .expect("invalid consult token");
+                        let amount: Uint128 = serde_json::from_value(params["amount"].clone())
+                            .expect("invalid consult amount");
+                        let quotes: Vec<(AssetInfo, Uint256)> = prices
+                            .iter()
+                            .filter(|(offer, _, _)| offer.equal(&token))
+                            .map(|(_, ask, price)| (ask.clone(), (amount * *price).into()))
+                            .collect();
+                        return respond(&quotes);
+                    }
+                }
+
+                SystemResult::Err(SystemError::NoSuchContract {
+                    addr: contract_addr.clone(),
+                })
+            }
+            _ => self.base.handle_query(request),
+        }
+    }
+
+    /// Sets the native balances of an address.
+    pub fn with_balance(&mut self, addr: &str, balance: &[Coin]) {
+        self.base.update_balance(addr, balance.to_vec());
+    }
+
+    /// Sets the balances of a cw20 token.
+    pub fn with_token_balances(&mut self, token: &str, balances: &[(&str, Uint128)]) {
+        self.token_balances.insert(
+            token.to_string(),
+            balances
+                .iter()
+                .map(|(addr, balance)| (addr.to_string(), *balance))
+                .collect(),
+        );
+    }
+
+    /// Adds a constant product pair holding the given reserves.
+    pub fn with_pair(&mut self, pair: &str, reserves: &[(AssetInfo, Uint128)], lp_token: &str) {
+        self.pairs.insert(
+            pair.to_string(),
+            MockPair {
+                asset_infos: reserves.iter().map(|(info, _)| info.clone()).collect(),
+                reserves: reserves.iter().map(|(_, amount)| *amount).collect(),
+                liquidity_token: Addr::unchecked(lp_token),
+                total_share: reserves[0].1,
+                cumulative_prices: vec![],
+            },
+        );
+    }
+
+    /// Sets the cumulative prices reported by a pair.
+    pub fn with_cumulative_prices(
+        &mut self,
+        pair: &str,
+        cumulative_prices: &[(AssetInfo, AssetInfo, Uint128)],
+    ) {
+        self.pairs
+            .get_mut(pair)
+            .expect("unknown pair")
+            .cumulative_prices = cumulative_prices.to_vec();
+    }
+
+    /// Adds an oracle quoting the given prices of ask assets in offer assets.
+    pub fn with_oracle(&mut self, oracle: &str, prices: &[(AssetInfo, AssetInfo, Decimal)]) {
+        self.oracle_prices
+            .insert(oracle.to_string(), prices.to_vec());
+    }
+    /// Answers every query of a variant to a contract with a fixed response.
+    pub fn with_smart_response<T: Serialize>(&mut self, contract: &str, query: &str, response: &T) {
+        self.smart_responses.insert(
+            (contract.to_string(), query.to_string()),
+            to_binary(response).unwrap(),
+        );
+    }
+}
+
+fn respond<T: Serialize>(response: &T) -> QuerierResult {
+    SystemResult::Ok(ContractResult::Ok(to_binary(response).unwrap()))
+}
+
+fn handle_pair_query(
+    contract_addr: &str,
+    pair: &MockPair,
+    name: &str,
+    params: &Value,
+) -> QuerierResult {
+    let asset = |key: &str| -> Asset {
+        serde_json::from_value(params[key].clone()).expect("invalid asset")
+    };
+    match name {
+        "pair" => respond(&pair.pair_info(contract_addr)),
+        "pool" => respond(&PoolResponse {
+            assets: pair.assets(),
+            total_share: pair.total_share,
+        }),
+        "cumulative_prices" => respond(&CumulativePricesResponse {
+            assets: pair.assets(),
+            total_share: pair.total_share,
+            cumulative_prices: pair.cumulative_prices.clone(),
+        }),
+        "simulation" => match pair.simulate(&asset("offer_asset")) {
+            Some(return_amount) => respond(&SimulationResponse {
+                return_amount,
+                spread_amount: Uint128::zero(),
+                commission_amount: Uint128::zero(),
+            }),
+            None => SystemResult::Ok(ContractResult::Err("Invalid offer asset".to_string())),
+        },
+        "reverse_simulation" => match pair.reverse_simulate(&asset("ask_asset")) {
+            Some(offer_amount) => respond(&ReverseSimulationResponse {
+                offer_amount,
+                spread_amount: Uint128::zero(),
+                commission_amount: Uint128::zero(),
+            }),
+            None => SystemResult::Ok(ContractResult::Err("Invalid ask asset".to_string())),
+        },
+        _ => SystemResult::Err(SystemError::UnsupportedRequest {
+            kind: name.to_string(),
+        }),
+    }
+}
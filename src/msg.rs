@@ -1,11 +1,28 @@
+//! Messages of the router. An execute message that takes offer assets from the sender expects
+//! the native ones attached as funds and pulls the CW20 ones from the sender's allowance.
+
 use cosmwasm_schema::{cw_serde, QueryResponses};
-use cosmwasm_std::{Decimal, Uint128, Addr};
+use cosmwasm_std::{Decimal, Uint128, Addr, Binary, Timestamp};
+use cw20::Cw20ReceiveMsg;
+use cw_utils::Expiration;
 
 // Sparrow Swap
 use sparrowswap_lib::asset::{AssetInfo as SparrowSwapAssetInfo};
 
 // Astroport Swap
-use astroport_lib::asset::{AssetInfo};
+use astroport_lib::asset::{Asset, AssetInfo};
+
+#[cw_serde]
+pub struct InstantiateMsg {
+    pub count: i32,
+}
+
+/// This structure describes a migration of the router.
+#[cw_serde]
+pub struct MigrateMsg {
+    /// The new owner of the router, if it changes
+    pub owner: Option<String>,
+}
 
 /// This enum describes a swap operation.
 #[cw_serde]
@@ -22,14 +39,191 @@ pub enum SwapOperation {
         ask_asset_info: Option<AssetInfo>,
         belief_price: Option<Decimal>,
         max_spread: Option<Decimal>,
-    }
+    },
+    /// Swaps through the adapter registered under the `dex` id
+    Swap {
+        dex: String,
+        pool_address: String,
+        offer_asset_info: AssetInfo,
+        ask_asset_info: Option<AssetInfo>,
+        belief_price: Option<Decimal>,
+        max_spread: Option<Decimal>,
+    },
+    /// Settles a signed quote of a whitelisted market maker
+    Rfq {
+        maker: String,
+        quote: RfqQuote,
+        signature: Binary,
+    },
+}
+
+/// This structure describes a market maker quote. It can be filled in parts at the quoted price
+/// until `offer_amount` is reached, only by routes run on behalf of `taker`. A fill larger than
+/// the amount left on the quote fails.
+#[cw_serde]
+pub struct RfqQuote {
+    /// The address the quote is made out to
+    pub taker: Addr,
+    pub offer_asset_info: AssetInfo,
+    /// The asset the maker pays, pulled from the maker's CW20 allowance to the router
+    pub ask_asset_info: AssetInfo,
+    pub offer_amount: Uint128,
+    pub ask_amount: Uint128,
+    /// The last block time in seconds the quote can be filled at
+    pub expiry: u64,
+    pub nonce: u64,
+}
+
+/// This structure describes the signed payload of a quote. Signatures are made over the SHA-256
+/// hash of its JSON encoding.
+#[cw_serde]
+pub struct RfqQuotePayload {
+    pub chain_id: String,
+    /// The router address
+    pub contract: String,
+    pub maker: String,
+    pub quote: RfqQuote,
+}
+
+/// This structure describes a contract call that receives the output of a route.
+/// Native output is attached as funds, CW20 output is delivered with `Cw20ExecuteMsg::Send`.
+#[cw_serde]
+pub struct PostAction {
+    pub contract: String,
+    pub msg: Binary,
+}
+
+/// This structure describes an ICS-20 transfer of the route output.
+#[cw_serde]
+pub struct IbcForward {
+    pub channel: String,
+    /// The receiver on the counterparty chain
+    pub receiver: String,
+    pub timeout_seconds: u64,
+    /// A JSON object memo, the ibc-hooks callback is added to it
+    pub memo: Option<String>,
+}
+
+/// This enum describes the message sent to a calling contract once its route finishes.
+#[cw_serde]
+pub enum CallbackMsg {
+    SwapCallback {
+        offer_asset: Asset,
+        return_asset: Asset,
+        /// The `callback` binary passed to `Unxswap`
+        original_msg: Binary,
+    },
+}
+
+/// This structure describes the swap result set as response data when a callback is requested.
+#[cw_serde]
+pub struct SwapResponse {
+    pub offer_asset: Asset,
+    pub return_asset: Asset,
+}
+
+/// This structure describes a single route of a batch swap.
+#[cw_serde]
+pub struct UnxswapParams {
+    pub offer_asset: Asset,
+    pub steps: Vec<SwapOperation>,
+    pub minimum_receive: Option<Uint128>,
+    pub to: Option<Addr>,
+    pub target_asset_info: AssetInfo,
+}
+
+/// This structure describes one input of a consolidation and its route to the target asset.
+#[cw_serde]
+pub struct ConsolidateInput {
+    pub asset: Asset,
+    pub steps: Vec<SwapOperation>,
+}
+
+/// This structure describes one leg of a distribution.
+#[cw_serde]
+pub struct DistributeLeg {
+    /// The share of the offer asset routed through this leg, in basis points
+    pub weight_bps: u16,
+    pub steps: Vec<SwapOperation>,
+    pub minimum_receive: Option<Uint128>,
+    pub target_asset_info: AssetInfo,
+}
+
+/// This structure describes how a generator reward is converted into a pool asset.
+#[cw_serde]
+pub struct CompoundRoute {
+    pub reward_asset_info: AssetInfo,
+    /// The route into a pool asset, empty if the reward is a pool asset
+    pub steps: Vec<SwapOperation>,
+    /// Pending rewards below this amount are not harvested
+    pub min_harvest: Uint128,
+}
+
+/// This enum describes the lifecycle of a DCA schedule.
+#[cw_serde]
+pub enum DcaStatus {
+    Active,
+    Completed,
+    Cancelled,
+}
+
+/// This enum describes the TWAP condition of a conditional order.
+#[cw_serde]
+pub enum PriceCondition {
+    /// Triggers when the TWAP is lower than or equal to the price, e.g. a stop-loss
+    Below { price: Decimal },
+    /// Triggers when the TWAP is greater than or equal to the price, e.g. a take-profit
+    Above { price: Decimal },
+}
+
+/// This structure describes a swap signed off-chain by the owner of the offer asset.
+#[cw_serde]
+pub struct SwapIntent {
+    /// The signer address; it must belong to the signing public key
+    pub signer: String,
+    /// The CW20 offer asset, pulled from the signer's allowance to the router
+    pub offer_asset: Asset,
+    pub steps: Vec<SwapOperation>,
+    /// The minimum amount received after the relayer fee
+    pub minimum_receive: Uint128,
+    /// The receiver of the output, the signer if not set
+    pub receiver: Option<String>,
+    /// The maximum fee paid to the relayer out of the output
+    pub max_relayer_fee: Uint128,
+    /// Must match the next nonce of the signer
+    pub nonce: u64,
+    /// The last block time in seconds the intent can be executed at
+    pub deadline: u64,
+}
+
+/// This structure describes the signed payload of a swap intent. Signatures are made over the
+/// SHA-256 hash of its JSON encoding.
+#[cw_serde]
+pub struct SignedSwapPayload {
+    pub chain_id: String,
+    /// The router address
+    pub contract: String,
+    pub intent: SwapIntent,
+}
+
+/// This structure describes the revealed parameters of a committed swap. The commitment hash is
+/// the SHA-256 hash of its JSON encoding.
+#[cw_serde]
+pub struct SwapReveal {
+    pub steps: Vec<SwapOperation>,
+    pub minimum_receive: Option<Uint128>,
+    pub salt: Binary,
 }
 
 #[cw_serde]
 pub enum ExecuteMsg {
+    /// Receives a message of type [`Cw20ReceiveMsg`]
+    Receive(Cw20ReceiveMsg),
     SparrowSwap  {
         pool_address: String,
         offer_asset_info: SparrowSwapAssetInfo,
+        /// The amount to swap, the whole contract balance of the offer asset if not set
+        offer_amount: Option<Uint128>,
         belief_price: Option<Decimal>,
         max_spread: Option<Decimal>,
         to: Option<String>,
@@ -37,6 +231,20 @@ pub enum ExecuteMsg {
     AstroportSwap {
         pool_address: String,
         offer_asset_info: AssetInfo,
+        /// The amount to swap, the whole contract balance of the offer asset if not set
+        offer_amount: Option<Uint128>,
+        ask_asset_info: Option<AssetInfo>,
+        belief_price: Option<Decimal>,
+        max_spread: Option<Decimal>,
+        to: Option<String>,
+    },
+    /// Swaps in a pair of any DEX with a registered adapter
+    DexSwap {
+        dex: String,
+        pool_address: String,
+        offer_asset_info: AssetInfo,
+        /// The amount to swap, the whole contract balance of the offer asset if not set
+        offer_amount: Option<Uint128>,
         ask_asset_info: Option<AssetInfo>,
         belief_price: Option<Decimal>,
         max_spread: Option<Decimal>,
@@ -47,6 +255,138 @@ pub enum ExecuteMsg {
         minimum_receive: Option<Uint128>,
         to: Option<Addr>,
         target_asset_info: AssetInfo,
+        /// Delivers the output to a contract instead of `to`
+        post_action: Option<PostAction>,
+        /// Calls `SwapCallback` on the sender with this message once the route finishes
+        callback: Option<Binary>,
+        /// Credits the offer to this address instead of reverting if the route fails. Swaps
+        /// arriving through an ibc-hooks `wasm` memo set it together with `to`, since the
+        /// ibc-hooks sender is not controlled by anyone.
+        recovery_addr: Option<String>,
+        /// Sends the native output over IBC once the minimum receive assertion passes. A
+        /// transfer that fails or times out is credited to the recovery address, or the sender
+        /// if none is set.
+        ibc_forward: Option<IbcForward>,
+    },
+    /// Reports the result of a route to the sender. Only the contract itself can execute it.
+    ExecuteSwapCallback {
+        offer_asset: Asset,
+        target_asset_info: AssetInfo,
+        prev_balance: Uint128,
+        receiver: String,
+        sender: String,
+        callback: Binary,
+    },
+    /// Sends the output of a route to the post action contract. Only the contract itself can
+    /// execute it.
+    ExecutePostAction {
+        asset_info: AssetInfo,
+        prev_balance: Uint128,
+        post_action: PostAction,
+    },
+    /// Pulls `amount` of a CW20 `token` from the owner's allowance and routes it like `Unxswap`.
+    /// The owner must be the message sender and is the default receiver.
+    UnxswapFrom {
+        owner: String,
+        token: String,
+        amount: Uint128,
+        steps: Vec<SwapOperation>,
+        minimum_receive: Option<Uint128>,
+        to: Option<String>,
+        target_asset_info: AssetInfo,
+    },
+    /// Routes several inputs into one target asset. `minimum_receive` applies to the total.
+    Consolidate {
+        inputs: Vec<ConsolidateInput>,
+        target_asset_info: AssetInfo,
+        minimum_receive: Option<Uint128>,
+        to: Option<String>,
+    },
+    /// Splits one offer asset between several legs whose weights sum up to 10000 bps.
+    Distribute {
+        offer_asset: Asset,
+        legs: Vec<DistributeLeg>,
+        to: Option<String>,
+    },
+    /// Provides liquidity to a two-asset pool from a single asset. The offer is routed into one
+    /// of the pool assets with `swap_steps` unless it is a pool asset already.
+    ZapIn {
+        pool: String,
+        dex: String,
+        offer_asset: Asset,
+        swap_steps: Vec<SwapOperation>,
+        /// The expected price of the swap into the other pool asset, in the zapped pool asset
+        /// per unit of the other pool asset
+        belief_price: Option<Decimal>,
+        /// Used as the maximum spread of the swap into the other pool asset and as the
+        /// slippage tolerance of the provision
+        slippage_tolerance: Option<Decimal>,
+        auto_stake: Option<bool>,
+        receiver: Option<String>,
+    },
+    /// Swaps the optimal part of a zap into the other pool asset. Only the contract itself can
+    /// execute it.
+    ZapInSwap {
+        pool: String,
+        dex: String,
+        asset_info: AssetInfo,
+        prev_balance: Uint128,
+        belief_price: Option<Decimal>,
+        slippage_tolerance: Option<Decimal>,
+        auto_stake: Option<bool>,
+        receiver: String,
+        refund_to: String,
+    },
+    /// Provides the zap amounts to the pool and refunds the dust. Only the contract itself can
+    /// execute it.
+    ZapInProvide {
+        pool: String,
+        dex: String,
+        asset: Asset,
+        other_asset_info: AssetInfo,
+        other_prev_balance: Uint128,
+        slippage_tolerance: Option<Decimal>,
+        auto_stake: Option<bool>,
+        receiver: String,
+        refund_to: String,
+    },
+    /// Routes the assets withdrawn by a zap-out to the target asset. Only the contract itself can
+    /// execute it.
+    ZapOutRoute {
+        /// The contract balances of the pool assets right before the withdrawal
+        prev_balances: Vec<Asset>,
+        target_asset_info: AssetInfo,
+        steps_per_asset: Vec<Vec<SwapOperation>>,
+        to: String,
+        /// The zap-out sender, which takes the RFQ quotes of the routes
+        sender: String,
+    },
+    /// Executes several independent routes. Attached funds must match the sum of all native offers.
+    /// If `atomic` is false, a failed route refunds its offer asset instead of reverting the batch.
+    BatchUnxswap {
+        swaps: Vec<UnxswapParams>,
+        atomic: bool,
+    },
+    /// Executes a single route of a batch. Only the contract itself can execute it.
+    ExecuteRoute {
+        offer_asset: Asset,
+        steps: Vec<SwapOperation>,
+        minimum_receive: Option<Uint128>,
+        to: String,
+        target_asset_info: AssetInfo,
+    },
+    /// Runs a route that starts and ends in the attached native asset and reverts unless the
+    /// sender receives at least the offered amount plus `min_profit`.
+    Arbitrage {
+        steps: Vec<SwapOperation>,
+        min_profit: Uint128,
+    },
+    AssertMinimumProfit {
+        asset_info: AssetInfo,
+        prev_balance: Uint128,
+        offer_amount: Uint128,
+        min_profit: Uint128,
+        receiver: String,
     },
     AssertMinimumReceive {
         asset_info: AssetInfo,
@@ -54,6 +394,267 @@ pub enum ExecuteMsg {
         minimum_receive: Uint128,
         receiver: String,
     },
+    /// Anchors every hop between two assets to an Astroport oracle contract. Only the owner can execute it.
+    SetPairOracle {
+        asset_infos: [AssetInfo; 2],
+        oracle: String,
+        /// The maximum relative deviation between a hop's return amount and the oracle's quote
+        max_deviation: Decimal,
+    },
+    /// Removes the oracle configured for a pair of assets. Only the owner can execute it.
+    RemovePairOracle {
+        asset_infos: [AssetInfo; 2],
+    },
+    /// Sets the share of a limit order's return paid to its keeper, at most 500 bps. Only the
+    /// owner can execute it.
+    SetKeeperTip { tip_bps: u16 },
+    /// Escrows the offer asset until the route returns at least `min_price` per unit of it.
+    PlaceLimitOrder {
+        offer_asset: Asset,
+        steps: Vec<SwapOperation>,
+        /// The minimum amount of the target asset received per unit of the offer asset
+        min_price: Decimal,
+        expiry: Option<Expiration>,
+    },
+    /// Cancels a limit order and refunds its offer asset. Only the order owner can execute it.
+    CancelOrder { id: u64 },
+    /// Executes a limit order whose route meets its price. Anyone can execute it and the
+    /// sender earns the keeper tip.
+    ExecuteOrder { id: u64 },
+    /// Pays out the return of an executed limit order. Only the contract itself can execute it.
+    SettleOrder {
+        id: u64,
+        owner: String,
+        keeper: String,
+        target_asset_info: AssetInfo,
+        prev_balance: Uint128,
+        minimum_receive: Uint128,
+    },
+    /// Escrows a deposit that is swapped in chunks of `chunk_amount` at most every
+    /// `interval_seconds`.
+    CreateDca {
+        offer_asset: Asset,
+        steps: Vec<SwapOperation>,
+        chunk_amount: Uint128,
+        interval_seconds: u64,
+        /// The maximum amount of the offer asset paid per unit of the target asset
+        max_price: Decimal,
+    },
+    /// Swaps the next chunk of a schedule once its interval has elapsed. Anyone can execute it.
+    ExecuteDca { id: u64 },
+    /// Cancels a schedule and refunds its remainder. Only the schedule owner can execute it.
+    CancelDca { id: u64 },
+    /// Pays out and records the return of a chunk. Only the contract itself can execute it.
+    SettleDca {
+        id: u64,
+        index: u64,
+        offer_amount: Uint128,
+        prev_balance: Uint128,
+        minimum_receive: Uint128,
+    },
+    /// Escrows the offer asset until the TWAP of the offer asset in `price_pool`, measured over
+    /// `twap_window_seconds`, meets the condition. The TWAP is priced in the other pool asset.
+    PlaceConditionalOrder {
+        offer_asset: Asset,
+        /// The route to the safe asset
+        steps: Vec<SwapOperation>,
+        minimum_receive: Option<Uint128>,
+        price_pool: String,
+        dex: String,
+        condition: PriceCondition,
+        twap_window_seconds: u64,
+    },
+    /// Cancels a conditional order and refunds its offer asset. Only the order owner can execute
+    /// it.
+    CancelConditionalOrder { id: u64 },
+    /// Checks the TWAP of a conditional order once its window has elapsed. The order is routed
+    /// if the condition is met, otherwise a new window starts. Anyone can execute it.
+    Trigger { id: u64 },
+    /// Fills a market maker quote with the contract balance of the offer asset. The return is
+    /// sent to `to` or kept by the contract. Only the contract itself can execute it.
+    RfqSwap {
+        maker: String,
+        quote: RfqQuote,
+        signature: Binary,
+        /// The amount to fill, the whole contract balance of the offer asset if not set
+        offer_amount: Option<Uint128>,
+        to: Option<String>,
+    },
+    /// Whitelists a market maker and the secp256k1 public key it signs quotes with. Only the
+    /// owner can execute it.
+    SetRfqMaker { maker: String, pubkey: Binary },
+    /// Removes a market maker from the whitelist. Only the owner can execute it.
+    RemoveRfqMaker { maker: String },
+    /// Escrows the attached native coin behind the hash of a [`SwapReveal`]. CW20 tokens are
+    /// committed with the `CommitSwap` hook.
+    CommitSwap { hash: Binary },
+    /// Routes a committed offer to the sender. It must be executed in a later block than the
+    /// commitment and before it times out.
+    RevealSwap {
+        steps: Vec<SwapOperation>,
+        minimum_receive: Option<Uint128>,
+        salt: Binary,
+    },
+    /// Refunds a commitment that timed out without being revealed.
+    RefundCommit { hash: Binary },
+    /// Adds the attached native coin to the current batch auction of a two-asset pool. The order
+    /// must be worth a minimum of each pool asset at the pool mid price. CW20 tokens are submitted
+    /// with the `SubmitAuctionOrder` hook.
+    SubmitAuctionOrder {
+        pool: String,
+        dex: String,
+        /// The maximum spread of the order fill below the pool mid price. The order is refunded at
+        /// settlement if the net swap would exceed it.
+        max_spread: Option<Decimal>,
+    },
+    /// Removes an order from an unsettled batch auction and refunds it. Only the order owner can
+    /// execute it.
+    CancelAuctionOrder { pool: String, round: u64, index: u64 },
+    /// Settles a batch auction once its round has ended. Opposing orders are matched at the pool
+    /// mid price and only the net imbalance is swapped through the pool. Orders worth nothing at
+    /// that price are refunded. Anyone can execute it.
+    SettleAuction { pool: String, round: u64 },
+    /// Pays out the orders of a settled batch auction. Only the contract itself can execute it.
+    DistributeAuction {
+        pool: String,
+        round: u64,
+        /// Equivalent amounts of the pool assets at the clearing price
+        prices: Vec<Uint128>,
+        /// The contract balances of the pool assets right before the net swap
+        prev_balances: Vec<Uint128>,
+    },
+    /// Locks an asset behind the SHA-256 `hashlock` of a secret preimage until `timelock`.
+    LockSwap {
+        hashlock: Binary,
+        timelock: Expiration,
+        recipient: String,
+        asset: Asset,
+        /// The route the asset is swapped through on claim, delivered as is if not set
+        route: Option<Vec<SwapOperation>>,
+        minimum_receive: Option<Uint128>,
+    },
+    /// Delivers an asset locked by `sender` to its recipient once the preimage of its hashlock is
+    /// revealed. If the route of the lock fails, the asset is delivered as is. Anyone can execute
+    /// it before the timelock expires.
+    Claim { sender: String, preimage: Binary },
+    /// Routes the asset of a claimed hash time-locked swap to its recipient. Only the contract
+    /// itself can execute it.
+    ExecuteClaimRoute {},
+    /// Returns an asset locked by `sender` to it once the timelock has expired. Anyone can
+    /// execute it.
+    Refund { sender: String, hashlock: Binary },
+    /// Sends the output of a route over IBC. Only the contract itself can execute it.
+    ExecuteIbcForward {
+        asset_info: AssetInfo,
+        prev_balance: Uint128,
+        ibc_forward: IbcForward,
+        owner: String,
+        recovery_addr: String,
+    },
+    /// Sends the assets credited to the sender by failed swaps and IBC transfers.
+    Recover {},
+    /// Executes a swap intent signed with secp256k1. The sender relays it and earns a fee of at
+    /// most `max_relayer_fee` without pushing the output below `minimum_receive`.
+    ExecuteSignedSwap {
+        intent: SwapIntent,
+        signature: Binary,
+        pubkey: Binary,
+    },
+    /// Splits the output of a signed swap between the receiver and the relayer. Only the
+    /// contract itself can execute it.
+    SettleSignedSwap {
+        target_asset_info: AssetInfo,
+        prev_balance: Uint128,
+        minimum_receive: Uint128,
+        max_relayer_fee: Uint128,
+        receiver: String,
+        relayer: String,
+    },
+    /// Sets the Astroport generator used by `ProvideAndStake`. Only the owner can execute it.
+    SetGenerator { generator: String },
+    /// Provides liquidity to an Astroport pair and stakes the minted LP tokens in the generator
+    /// for the receiver.
+    ProvideAndStake {
+        pool: String,
+        assets: Vec<Asset>,
+        slippage_tolerance: Option<Decimal>,
+        receiver: Option<String>,
+    },
+    /// Withdraws LP tokens from the compounding position of the sender.
+    WithdrawCompound { lp_token: String, shares: Uint128 },
+    /// Sets the pool and reward routes that compound an LP token. Only the owner can execute
+    /// it.
+    SetCompoundConfig {
+        lp_token: String,
+        pool: String,
+        reward_routes: Vec<CompoundRoute>,
+        /// The slippage tolerance of the zap back into the pool
+        max_spread: Option<Decimal>,
+    },
+    /// Claims the generator rewards of the compounding positions of an LP token, routes them
+    /// into a pool asset and zaps them back into the staked position.
+    Compound { lp_token: String },
+    /// Routes the claimed rewards of a compound and zaps them into the pool. Only the contract
+    /// itself can execute it.
+    CompoundSwap {
+        lp_token: String,
+        reward_routes: Vec<CompoundRoute>,
+        /// The contract balances of the compounded assets right before the claim, without the
+        /// assets of the compounding positions
+        prev_balances: Vec<Asset>,
+    },
+    /// Escrows the assets of the compounding positions that arrived since `prev_balances` until
+    /// the next compound. Only the contract itself can execute it.
+    EscrowCompoundLeftovers {
+        lp_token: String,
+        prev_balances: Vec<Asset>,
+    },
+}
+
+/// This enum describes the CW20 hook messages of the router.
+#[cw_serde]
+pub enum Cw20HookMsg {
+    /// Withdraws the sent LP tokens from their pair and routes every returned asset to
+    /// `target_asset_info`. Each route in `steps_per_asset` starts with the pool asset it
+    /// converts; the target asset itself needs no route.
+    ZapOut {
+        dex: String,
+        target_asset_info: AssetInfo,
+        steps_per_asset: Vec<Vec<SwapOperation>>,
+        minimum_receive: Option<Uint128>,
+        to: Option<String>,
+    },
+    /// Stakes the sent LP tokens in the generator as a compounding position of the sender.
+    DepositCompound {},
+    /// Escrows the sent tokens behind the hash of a [`SwapReveal`].
+    CommitSwap { hash: Binary },
+    /// Adds the sent tokens to the current batch auction of a two-asset pool.
+    SubmitAuctionOrder {
+        pool: String,
+        dex: String,
+        max_spread: Option<Decimal>,
+    },
+}
+
+/// This enum describes the messages the chain sends through the `sudo` entry point.
+#[cw_serde]
+pub enum SudoMsg {
+    /// The ibc-hooks callback for a transfer the contract sent with an `ibc_callback` memo
+    IbcLifecycleComplete(IbcLifecycleComplete),
+}
+
+#[cw_serde]
+pub enum IbcLifecycleComplete {
+    IbcAck {
+        channel: String,
+        sequence: u64,
+        ack: String,
+        success: bool,
+    },
+    IbcTimeout {
+        channel: String,
+        sequence: u64,
+    },
 }
 
 #[cw_serde]
@@ -62,6 +663,83 @@ pub enum QueryMsg {
     // GetCount returns the current count as a json-encoded number
     #[returns(GetCountResponse)]
     GetCount {},
+    // PairOracle returns the oracle configured for a pair of assets, if any
+    #[returns(Option<PairOracleResponse>)]
+    PairOracle { asset_infos: [AssetInfo; 2] },
+    // Generator returns the Astroport generator used by ProvideAndStake and Compound, if any
+    #[returns(Option<Addr>)]
+    Generator {},
+    // CompoundConfig returns how an LP token is compounded, if it is
+    #[returns(Option<CompoundConfigResponse>)]
+    CompoundConfig { lp_token: String },
+    // CompoundPosition returns the compounding position of an owner
+    #[returns(CompoundPositionResponse)]
+    CompoundPosition { lp_token: String, owner: String },
+    // KeeperTip returns the share of a limit order's return paid to its keeper in bps
+    #[returns(u16)]
+    KeeperTip {},
+    // Order returns a limit order by id
+    #[returns(OrderResponse)]
+    Order { id: u64 },
+    // OrdersByOwner returns the limit orders of an owner
+    #[returns(OrdersResponse)]
+    OrdersByOwner {
+        owner: String,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    // OrdersByPair returns the limit orders between two assets in either direction
+    #[returns(OrdersResponse)]
+    OrdersByPair {
+        asset_infos: [AssetInfo; 2],
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    // ConditionalOrder returns a conditional order by id
+    #[returns(ConditionalOrderResponse)]
+    ConditionalOrder { id: u64 },
+    // ConditionalOrders returns the open conditional orders
+    #[returns(ConditionalOrdersResponse)]
+    ConditionalOrders {
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    // AuctionBatch returns an unsettled batch auction of a pool, if any
+    #[returns(Option<AuctionBatchResponse>)]
+    AuctionBatch { pool: String, round: u64 },
+    // Commitment returns a pending swap commitment, if any
+    #[returns(Option<CommitmentResponse>)]
+    Commitment { owner: String, hash: Binary },
+    // HashedTimelock returns a pending hash time-locked swap, if any
+    #[returns(Option<HashedTimelockResponse>)]
+    HashedTimelock { sender: String, hashlock: Binary },
+    // IbcTransfersByOwner returns the outgoing IBC transfers of an owner waiting for their
+    // callback
+    #[returns(Vec<IbcTransferResponse>)]
+    IbcTransfersByOwner {
+        owner: String,
+        start_after: Option<(String, u64)>,
+        limit: Option<u32>,
+    },
+    // Recoveries returns the assets an address can recover
+    #[returns(Vec<Asset>)]
+    Recoveries { addr: String },
+    // RfqMaker returns the public key of a whitelisted market maker, if any
+    #[returns(Option<Binary>)]
+    RfqMaker { maker: String },
+    // SignerNonce returns the next nonce of a swap intent signer
+    #[returns(u64)]
+    SignerNonce { signer: String },
+    // Dca returns the status of a DCA schedule
+    #[returns(DcaResponse)]
+    Dca { id: u64 },
+    // DcaHistory returns the executed chunks of a DCA schedule
+    #[returns(DcaHistoryResponse)]
+    DcaHistory {
+        id: u64,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
 }
 
 // We define a custom struct for each query response
@@ -69,3 +747,142 @@ pub enum QueryMsg {
 pub struct GetCountResponse {
     pub count: i32,
 }
+
+#[cw_serde]
+pub struct PairOracleResponse {
+    pub oracle: Addr,
+    pub max_deviation: Decimal,
+}
+
+#[cw_serde]
+pub struct CompoundConfigResponse {
+    pub pool: Addr,
+    /// The pool asset the reward routes end in
+    pub asset_info: AssetInfo,
+    pub reward_routes: Vec<CompoundRoute>,
+    pub max_spread: Option<Decimal>,
+    /// The escrowed leftovers compounded by the next compound
+    pub idle_assets: Vec<Asset>,
+}
+
+#[cw_serde]
+pub struct CompoundPositionResponse {
+    pub shares: Uint128,
+    /// The staked LP amount the shares are currently worth
+    pub lp_amount: Uint128,
+}
+
+#[cw_serde]
+pub struct OrderResponse {
+    pub id: u64,
+    pub owner: Addr,
+    pub offer_asset: Asset,
+    pub steps: Vec<SwapOperation>,
+    pub target_asset_info: AssetInfo,
+    pub min_price: Decimal,
+    pub expiry: Option<Expiration>,
+}
+
+#[cw_serde]
+pub struct OrdersResponse {
+    pub orders: Vec<OrderResponse>,
+}
+
+#[cw_serde]
+pub struct DcaResponse {
+    pub id: u64,
+    pub owner: Addr,
+    /// The remainder of the deposit
+    pub offer_asset: Asset,
+    pub steps: Vec<SwapOperation>,
+    pub target_asset_info: AssetInfo,
+    pub chunk_amount: Uint128,
+    pub interval_seconds: u64,
+    pub max_price: Decimal,
+    pub status: DcaStatus,
+    pub executions: u64,
+    pub last_execution: Option<Timestamp>,
+    /// The earliest time of the next chunk if the schedule is active
+    pub next_execution: Option<Timestamp>,
+}
+
+#[cw_serde]
+pub struct DcaExecutionResponse {
+    pub index: u64,
+    pub offer_amount: Uint128,
+    pub return_amount: Uint128,
+    pub timestamp: Timestamp,
+}
+
+#[cw_serde]
+pub struct DcaHistoryResponse {
+    pub executions: Vec<DcaExecutionResponse>,
+}
+
+#[cw_serde]
+pub struct ConditionalOrderResponse {
+    pub id: u64,
+    pub owner: Addr,
+    pub offer_asset: Asset,
+    pub steps: Vec<SwapOperation>,
+    pub minimum_receive: Option<Uint128>,
+    pub price_pool: Addr,
+    pub dex: String,
+    pub condition: PriceCondition,
+    pub twap_window_seconds: u64,
+    /// The start of the current TWAP window
+    pub window_start: Timestamp,
+}
+
+#[cw_serde]
+pub struct ConditionalOrdersResponse {
+    pub orders: Vec<ConditionalOrderResponse>,
+}
+
+#[cw_serde]
+pub struct CommitmentResponse {
+    pub owner: Addr,
+    pub offer_asset: Asset,
+    /// The block height of the commitment
+    pub height: u64,
+    /// The last block height the swap can be revealed at
+    pub reveal_deadline: u64,
+}
+
+#[cw_serde]
+pub struct AuctionBatchResponse {
+    pub dex: String,
+    pub asset_infos: Vec<AssetInfo>,
+    /// The total offered amount of each pool asset
+    pub totals: Vec<Uint128>,
+    pub orders: Vec<AuctionOrderResponse>,
+    /// The first block height the batch can be settled at
+    pub settle_height: u64,
+}
+
+#[cw_serde]
+pub struct AuctionOrderResponse {
+    pub index: u64,
+    pub owner: Addr,
+    pub offer_asset: Asset,
+    pub max_spread: Option<Decimal>,
+}
+
+#[cw_serde]
+pub struct HashedTimelockResponse {
+    pub sender: Addr,
+    pub recipient: Addr,
+    pub asset: Asset,
+    pub timelock: Expiration,
+    pub route: Option<Vec<SwapOperation>>,
+    pub minimum_receive: Option<Uint128>,
+}
+
+#[cw_serde]
+pub struct IbcTransferResponse {
+    pub channel: String,
+    pub sequence: u64,
+    pub owner: Addr,
+    pub asset: Asset,
+    pub recovery_addr: Addr,
+}
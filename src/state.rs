@@ -1,8 +1,14 @@
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use cosmwasm_std::Addr;
-use cw_storage_plus::Item;
+use cosmwasm_std::{Addr, Binary, Decimal, Empty, Timestamp, Uint128};
+use cw_storage_plus::{Item, Map};
+
+use cw_utils::Expiration;
+
+use astroport_lib::asset::{Asset, AssetInfo};
+
+use crate::msg::{CompoundRoute, DcaStatus, PriceCondition, SwapOperation};
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
 pub struct State {
@@ -11,3 +17,288 @@ pub struct State {
 }
 
 pub const STATE: Item<State> = Item::new("state");
+
+/// This structure describes the oracle used to check the execution price of hops between two assets.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct PairOracle {
+    /// The Astroport oracle contract address
+    pub oracle: Addr,
+    /// The maximum relative deviation between a hop's return amount and the oracle's quote
+    pub max_deviation: Decimal,
+}
+
+/// Oracles keyed by the pair of asset identifiers returned from [`pair_key`]
+pub const PAIR_ORACLES: Map<(String, String), PairOracle> = Map::new("pair_oracles");
+
+/// Returns an order-independent storage key for a pair of assets.
+pub fn pair_key(a: &AssetInfo, b: &AssetInfo) -> (String, String) {
+    let (a, b) = (a.to_string(), b.to_string());
+    if a <= b {
+        (a, b)
+    } else {
+        (b, a)
+    }
+}
+
+/// This structure describes a batch swap waiting for its reply.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PendingBatchSwap {
+    /// The position of the swap in the batch
+    pub index: u64,
+    pub offer_asset: Asset,
+    pub target_asset_info: AssetInfo,
+    pub receiver: Addr,
+    /// The address that gets the offer asset back if the swap fails
+    pub refund_to: Addr,
+    /// The receiver balance of the target asset right before the route was executed
+    pub prev_balance: Uint128,
+}
+
+/// Batch swaps in execution order; replies consume them from the front
+pub const PENDING_BATCH_SWAPS: Item<Vec<PendingBatchSwap>> = Item::new("pending_batch_swaps");
+
+/// This structure describes a route that credits its offer to a recovery address if it fails.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PendingRecovery {
+    pub offer_asset: Asset,
+    /// The sender of the recoverable swap
+    pub sender: Addr,
+    pub recovery_addr: Addr,
+}
+
+pub const PENDING_RECOVERY: Item<PendingRecovery> = Item::new("pending_recovery");
+
+/// Escrowed assets of failed swaps and IBC transfers keyed by recovery address
+pub const RECOVERIES: Map<&Addr, Vec<Asset>> = Map::new("recoveries");
+
+/// This structure describes an outgoing IBC transfer waiting for its ibc-hooks callback.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct IbcTransfer {
+    pub channel: String,
+    pub owner: Addr,
+    pub asset: Asset,
+    /// The address credited with the asset if the transfer fails or times out
+    pub recovery_addr: Addr,
+}
+
+/// Outgoing IBC transfers keyed by source channel and packet sequence
+pub const IBC_TRANSFERS: Map<(&str, u64), IbcTransfer> = Map::new("ibc_transfers");
+
+/// Outgoing IBC transfers keyed by owner, source channel and packet sequence
+pub const IBC_TRANSFERS_BY_OWNER: Map<(&Addr, &str, u64), Empty> =
+    Map::new("ibc_transfers_by_owner");
+
+/// An outgoing IBC transfer waiting for the reply with its packet sequence
+pub const PENDING_IBC_TRANSFER: Item<IbcTransfer> = Item::new("pending_ibc_transfer");
+
+/// The Astroport generator that receives the LP tokens of `ProvideAndStake` and `Compound`
+pub const GENERATOR: Item<Addr> = Item::new("generator");
+
+/// This structure describes a liquidity provision waiting to be staked.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct PendingStake {
+    pub lp_token: Addr,
+    /// The contract LP token balance right before the provision
+    pub prev_lp_balance: Uint128,
+    /// The account the LP tokens are staked for
+    pub receiver: Addr,
+    /// The staked amount of the receiver right before the deposit
+    pub prev_deposit: Uint128,
+}
+
+/// Liquidity provisions waiting to be staked keyed by the id their reply ids carry
+pub const PENDING_STAKES: Map<u32, PendingStake> = Map::new("pending_stakes");
+
+/// The id of the last `ProvideAndStake` provision, wrapping around
+pub const LAST_STAKE_ID: Item<u32> = Item::new("last_stake_id");
+
+/// Compounding shares keyed by LP token and owner
+pub const COMPOUND_SHARES: Map<(&Addr, &Addr), Uint128> = Map::new("compound_shares");
+
+/// The total compounding shares of an LP token
+pub const COMPOUND_TOTAL_SHARES: Map<&Addr, Uint128> = Map::new("compound_total_shares");
+
+/// This structure describes how the rewards of a compounding LP token are reinvested.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct CompoundConfig {
+    /// The Astroport pair of the LP token
+    pub pool: Addr,
+    pub pool_asset_infos: Vec<AssetInfo>,
+    /// The pool asset the reward routes end in
+    pub asset_info: AssetInfo,
+    pub reward_routes: Vec<CompoundRoute>,
+    /// The slippage tolerance of the zap back into the pool
+    pub max_spread: Option<Decimal>,
+}
+
+/// Compound configs keyed by LP token
+pub const COMPOUND_CONFIGS: Map<&Addr, CompoundConfig> = Map::new("compound_configs");
+
+/// Escrowed leftovers of the compounding positions keyed by LP token, such as rewards claimed
+/// by generator deposits and zap refunds
+pub const COMPOUND_IDLE: Map<&Addr, Vec<Asset>> = Map::new("compound_idle");
+
+/// Contract balances held for open orders keyed by asset; swap hops can't spend them
+pub const ESCROWED: Map<String, Uint128> = Map::new("escrowed");
+
+/// The share of a limit order's return paid to the keeper that executes it
+pub const KEEPER_TIP_BPS: Item<u16> = Item::new("keeper_tip_bps");
+
+/// This structure describes an open limit order.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct LimitOrder {
+    pub id: u64,
+    pub owner: Addr,
+    /// The escrowed offer asset
+    pub offer_asset: Asset,
+    pub steps: Vec<SwapOperation>,
+    /// The ask asset of the last hop
+    pub target_asset_info: AssetInfo,
+    /// The minimum amount of the target asset received per unit of the offer asset
+    pub min_price: Decimal,
+    pub expiry: Option<Expiration>,
+}
+
+pub const LIMIT_ORDERS: Map<u64, LimitOrder> = Map::new("limit_orders");
+
+/// The id of the last placed limit order
+pub const LAST_ORDER_ID: Item<u64> = Item::new("last_order_id");
+
+/// Limit order ids keyed by owner
+pub const ORDERS_BY_OWNER: Map<(&Addr, u64), Empty> = Map::new("orders_by_owner");
+
+/// Limit order ids keyed by the [`pair_key`] of their offer and target assets
+pub const ORDERS_BY_PAIR: Map<((String, String), u64), Empty> = Map::new("orders_by_pair");
+
+/// This structure describes a dollar-cost-averaging schedule.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct DcaSchedule {
+    pub id: u64,
+    pub owner: Addr,
+    /// The escrowed remainder of the deposit
+    pub offer_asset: Asset,
+    pub steps: Vec<SwapOperation>,
+    /// The ask asset of the last hop
+    pub target_asset_info: AssetInfo,
+    pub chunk_amount: Uint128,
+    pub interval_seconds: u64,
+    /// The maximum amount of the offer asset paid per unit of the target asset
+    pub max_price: Decimal,
+    pub status: DcaStatus,
+    pub executions: u64,
+    pub last_execution: Option<Timestamp>,
+}
+
+pub const DCA_SCHEDULES: Map<u64, DcaSchedule> = Map::new("dca_schedules");
+
+/// The id of the last created schedule
+pub const LAST_DCA_ID: Item<u64> = Item::new("last_dca_id");
+
+/// This structure describes a single executed chunk of a schedule.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct DcaExecution {
+    pub index: u64,
+    pub offer_amount: Uint128,
+    pub return_amount: Uint128,
+    pub timestamp: Timestamp,
+}
+
+/// Executed chunks keyed by schedule id and execution index
+pub const DCA_EXECUTIONS: Map<(u64, u64), DcaExecution> = Map::new("dca_executions");
+
+/// This structure describes a cumulative price of a pool at a point in time.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct PriceObservation {
+    pub cumulative_price: Uint128,
+    pub timestamp: Timestamp,
+}
+
+/// This structure describes an escrowed order routed once a pool's TWAP meets its condition.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ConditionalOrder {
+    pub id: u64,
+    pub owner: Addr,
+    /// The escrowed offer asset
+    pub offer_asset: Asset,
+    pub steps: Vec<SwapOperation>,
+    pub minimum_receive: Option<Uint128>,
+    /// The pool whose TWAP of the offer asset is checked
+    pub price_pool: Addr,
+    pub dex: String,
+    pub condition: PriceCondition,
+    pub twap_window_seconds: u64,
+    /// The start of the current TWAP window
+    pub observation: PriceObservation,
+}
+
+pub const CONDITIONAL_ORDERS: Map<u64, ConditionalOrder> = Map::new("conditional_orders");
+
+/// The id of the last placed conditional order
+pub const LAST_CONDITIONAL_ORDER_ID: Item<u64> = Item::new("last_conditional_order_id");
+
+/// The next swap intent nonce of each signer
+pub const SIGNER_NONCES: Map<&Addr, u64> = Map::new("signer_nonces");
+
+/// The secp256k1 public keys of whitelisted market makers
+pub const RFQ_MAKERS: Map<&Addr, Binary> = Map::new("rfq_makers");
+
+/// The filled offer amounts of quotes keyed by maker and nonce
+pub const RFQ_FILLED: Map<(&Addr, u64), Uint128> = Map::new("rfq_filled");
+
+/// This structure describes an escrowed swap waiting for its reveal.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SwapCommitment {
+    pub owner: Addr,
+    pub offer_asset: Asset,
+    /// The block height of the commitment
+    pub height: u64,
+}
+
+/// Swap commitments keyed by owner and commitment hash
+pub const SWAP_COMMITMENTS: Map<(&Addr, &[u8]), SwapCommitment> = Map::new("swap_commitments");
+
+/// This structure describes the orders collected for a pool during an auction round.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AuctionBatch {
+    pub dex: String,
+    /// The pool assets in pool order
+    pub asset_infos: Vec<AssetInfo>,
+    /// The total offered amount of each pool asset
+    pub totals: Vec<Uint128>,
+    /// The number of orders in the batch
+    pub orders: u64,
+    /// The index of the next order
+    pub next_index: u64,
+}
+
+/// Batch auctions keyed by pool and round
+pub const AUCTION_BATCHES: Map<(&Addr, u64), AuctionBatch> = Map::new("auction_batches");
+
+/// This structure describes an escrowed batch auction order.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AuctionOrder {
+    pub owner: Addr,
+    pub offer_asset: Asset,
+    pub max_spread: Option<Decimal>,
+}
+
+/// Batch auction orders keyed by pool, round and order index
+pub const AUCTION_ORDERS: Map<(&Addr, u64, u64), AuctionOrder> = Map::new("auction_orders");
+
+/// This structure describes an asset locked until the preimage of its hashlock is revealed or
+/// its timelock expires.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct HashedTimelock {
+    pub sender: Addr,
+    pub recipient: Addr,
+    pub asset: Asset,
+    pub timelock: Expiration,
+    pub route: Option<Vec<SwapOperation>>,
+    pub minimum_receive: Option<Uint128>,
+}
+
+/// Hash time-locked swaps keyed by sender and hashlock
+pub const HASHED_TIMELOCKS: Map<(&Addr, &[u8]), HashedTimelock> = Map::new("hashed_timelocks");
+
+/// The claimed hash time-locked swap whose route is being executed
+pub const PENDING_CLAIM: Item<HashedTimelock> = Item::new("pending_claim");
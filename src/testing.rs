@@ -0,0 +1,3536 @@
+use cosmwasm_std::testing::{mock_env, mock_info, MockApi, MockStorage, MOCK_CONTRACT_ADDR};
+use cosmwasm_std::{
+    attr, coin, from_binary, to_binary, Addr, Api, BankMsg, Binary, CanonicalAddr, Coin, CosmosMsg,
+    Decimal, Env, MessageInfo, OwnedDeps, QuerierWrapper, RecoverPubkeyError, Reply, ReplyOn,
+    Response, StdError, StdResult, SubMsg, SubMsgResponse, SubMsgResult, Uint128, Uint64,
+    VerificationError, WasmMsg,
+};
+use cw2::{get_contract_version, set_contract_version};
+use cw20::{Cw20ExecuteMsg, Cw20ReceiveMsg, MinterResponse};
+use cw_utils::Expiration;
+use k256::ecdsa::signature::Signer;
+use k256::ecdsa::{Signature, SigningKey};
+use ripemd::Ripemd160;
+use serde::de::DeserializeOwned;
+use sha2::{Digest, Sha256};
+use std::marker::PhantomData;
+
+use astroport_lib::asset::{Asset, AssetInfo};
+use astroport_lib::generator::{
+    Config as GeneratorConfig, Cw20HookMsg as GeneratorCw20HookMsg, PendingTokenResponse,
+    PoolInfoResponse,
+};
+use astroport_lib::pair::{Cw20HookMsg as AstroportCw20HookMsg, ExecuteMsg as AstroportMsg};
+use sparrowswap_lib::pair::Cw20HookMsg as SparrowSwapCw20HookMsg;
+
+use crate::adapter::dex_adapter;
+use crate::contract::{execute, instantiate, migrate, query, reply, sudo};
+use crate::error::ContractError;
+use crate::ibc::{decode_transfer_sequence, ibc_callback_memo, MsgTransfer};
+use crate::mock_querier::{mock_dependencies, WasmMockQuerier};
+use crate::msg::{
+    CallbackMsg, CompoundConfigResponse, CompoundPositionResponse, CompoundRoute, ConsolidateInput,
+    Cw20HookMsg, DistributeLeg, ExecuteMsg, GetCountResponse, HashedTimelockResponse, IbcForward,
+    IbcLifecycleComplete, InstantiateMsg, MigrateMsg, PairOracleResponse, PostAction,
+    PriceCondition, QueryMsg, RfqQuote, RfqQuotePayload, SignedSwapPayload, SudoMsg, SwapIntent,
+    SwapOperation, SwapResponse, SwapReveal, UnxswapParams,
+};
+use crate::state::{IbcTransfer, AUCTION_BATCHES, ESCROWED, IBC_TRANSFERS};
+
+type MockDeps = OwnedDeps<MockStorage, MockApi, WasmMockQuerier>;
+
+const OWNER: &str = "owner";
+const PAIR: &str = "pair0000";
+const ORACLE: &str = "oracle0000";
+
+fn native(denom: &str) -> AssetInfo {
+    AssetInfo::NativeToken {
+        denom: denom.to_string(),
+    }
+}
+
+fn token(contract_addr: &str) -> AssetInfo {
+    AssetInfo::Token {
+        contract_addr: Addr::unchecked(contract_addr),
+    }
+}
+
+/// Instantiates the router with a uusd/uluna pair holding equal reserves.
+fn setup() -> MockDeps {
+    let mut deps = mock_dependencies(&[]);
+    deps.querier.with_pair(
+        PAIR,
+        &[
+            (native("uusd"), Uint128::new(1_000_000)),
+            (native("uluna"), Uint128::new(1_000_000)),
+        ],
+        "lptoken0000",
+    );
+    instantiate(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(OWNER, &[]),
+        InstantiateMsg { count: 0 },
+    )
+    .unwrap();
+    deps
+}
+
+/// Executes a message as the router itself, the way its hops are dispatched.
+fn execute_self(
+    deps: &mut MockDeps,
+    msg: ExecuteMsg,
+) -> Result<cosmwasm_std::Response, ContractError> {
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(MOCK_CONTRACT_ADDR, &[]),
+        msg,
+    )
+}
+
+fn set_pair_oracle(deps: &mut MockDeps, max_deviation: Decimal) {
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(OWNER, &[]),
+        ExecuteMsg::SetPairOracle {
+            asset_infos: [native("uusd"), native("uluna")],
+            oracle: ORACLE.to_string(),
+            max_deviation,
+        },
+    )
+    .unwrap();
+}
+
+fn astroport_swap(offer_asset_info: AssetInfo, offer_amount: u128) -> ExecuteMsg {
+    ExecuteMsg::AstroportSwap {
+        pool_address: PAIR.to_string(),
+        offer_asset_info,
+        offer_amount: Some(Uint128::new(offer_amount)),
+        ask_asset_info: None,
+        belief_price: None,
+        max_spread: None,
+        to: None,
+    }
+}
+
+#[test]
+fn swap_without_oracle_does_not_query_the_pair() {
+    let mut deps = mock_dependencies(&[coin(1_000, "uusd")]);
+    instantiate(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(OWNER, &[]),
+        InstantiateMsg { count: 0 },
+    )
+    .unwrap();
+
+    // The pair is unknown to the querier, so any query to it would fail the swap
+    let res = execute_self(&mut deps, astroport_swap(native("uusd"), 1_000)).unwrap();
+    assert_eq!(res.messages.len(), 1);
+    match &res.messages[0].msg {
+        CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr,
+            funds,
+            msg,
+        }) => {
+            assert_eq!(contract_addr, PAIR);
+            assert_eq!(funds, &vec![coin(1_000, "uusd")]);
+            assert!(matches!(
+                from_binary(msg).unwrap(),
+                AstroportMsg::Swap { .. }
+            ));
+        }
+        msg => panic!("unexpected message {msg:?}"),
+    }
+}
+
+#[test]
+fn oracle_for_other_assets_does_not_query_the_pair() {
+    let mut deps = mock_dependencies(&[coin(1_000, "uusd")]);
+    instantiate(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(OWNER, &[]),
+        InstantiateMsg { count: 0 },
+    )
+    .unwrap();
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(OWNER, &[]),
+        ExecuteMsg::SetPairOracle {
+            asset_infos: [native("uluna"), token("asset0000")],
+            oracle: ORACLE.to_string(),
+            max_deviation: Decimal::percent(1),
+        },
+    )
+    .unwrap();
+
+    execute_self(&mut deps, astroport_swap(native("uusd"), 1_000)).unwrap();
+}
+
+#[test]
+fn swap_within_oracle_deviation() {
+    let mut deps = setup();
+    deps.querier
+        .with_balance(MOCK_CONTRACT_ADDR, &[coin(1_000, "uusd")]);
+    deps.querier
+        .with_oracle(ORACLE, &[(native("uusd"), native("uluna"), Decimal::one())]);
+    set_pair_oracle(&mut deps, Decimal::percent(1));
+
+    // 1_000 uusd returns 999 uluna from the pair against 1_000 from the oracle
+    execute_self(&mut deps, astroport_swap(native("uusd"), 1_000)).unwrap();
+}
+
+#[test]
+fn swap_outside_oracle_deviation() {
+    let mut deps = setup();
+    deps.querier
+        .with_balance(MOCK_CONTRACT_ADDR, &[coin(1_000, "uusd")]);
+    deps.querier.with_oracle(
+        ORACLE,
+        &[(native("uusd"), native("uluna"), Decimal::percent(110))],
+    );
+    set_pair_oracle(&mut deps, Decimal::percent(5));
+
+    let err = execute_self(&mut deps, astroport_swap(native("uusd"), 1_000)).unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::OraclePriceDeviation {
+            expected: Uint128::new(1_100),
+            amount: Uint128::new(999),
+            max_deviation: Decimal::percent(5),
+        }
+    );
+}
+
+#[test]
+fn pair_oracle_is_owner_only_and_removable() {
+    let mut deps = setup();
+
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("anyone", &[]),
+        ExecuteMsg::SetPairOracle {
+            asset_infos: [native("uusd"), native("uluna")],
+            oracle: ORACLE.to_string(),
+            max_deviation: Decimal::percent(1),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+
+    set_pair_oracle(&mut deps, Decimal::percent(1));
+    let oracle: Option<PairOracleResponse> = from_binary(
+        &query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::PairOracle {
+                asset_infos: [native("uluna"), native("uusd")],
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(oracle.unwrap().oracle, Addr::unchecked(ORACLE));
+
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(OWNER, &[]),
+        ExecuteMsg::RemovePairOracle {
+            asset_infos: [native("uusd"), native("uluna")],
+        },
+    )
+    .unwrap();
+    let oracle: Option<PairOracleResponse> = from_binary(
+        &query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::PairOracle {
+                asset_infos: [native("uusd"), native("uluna")],
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(oracle, None);
+}
+
+#[test]
+fn migrate_sets_owner() {
+    let mut deps = setup();
+    set_contract_version(deps.as_mut().storage, "crates.io:wasm-dexrouter", "0.0.1").unwrap();
+
+    migrate(
+        deps.as_mut(),
+        mock_env(),
+        MigrateMsg {
+            owner: Some("new_owner".to_string()),
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        get_contract_version(deps.as_ref().storage).unwrap().version,
+        env!("CARGO_PKG_VERSION")
+    );
+
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(OWNER, &[]),
+        ExecuteMsg::SetKeeperTip { tip_bps: 10 },
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("new_owner", &[]),
+        ExecuteMsg::SetKeeperTip { tip_bps: 10 },
+    )
+    .unwrap();
+
+    let count: GetCountResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::GetCount {}).unwrap()).unwrap();
+    assert_eq!(count.count, 0);
+}
+
+#[test]
+fn migrate_rejects_other_contracts() {
+    let mut deps = setup();
+    set_contract_version(deps.as_mut().storage, "crates.io:other", "0.1.0").unwrap();
+
+    let err = migrate(deps.as_mut(), mock_env(), MigrateMsg { owner: None }).unwrap_err();
+    assert_eq!(err, ContractError::MigrationError {});
+}
+
+fn astroport_step(offer_asset_info: AssetInfo) -> SwapOperation {
+    SwapOperation::AstroportSwap {
+        pool_address: PAIR.to_string(),
+        offer_asset_info,
+        ask_asset_info: None,
+        belief_price: None,
+        max_spread: None,
+    }
+}
+
+fn batch_params(amount: u128, to: Option<&str>) -> UnxswapParams {
+    UnxswapParams {
+        offer_asset: Asset {
+            info: native("uusd"),
+            amount: Uint128::new(amount),
+        },
+        steps: vec![astroport_step(native("uusd"))],
+        minimum_receive: None,
+        to: to.map(Addr::unchecked),
+        target_asset_info: native("uluna"),
+    }
+}
+
+#[test]
+fn batch_unxswap_requires_the_offer_funds() {
+    let mut deps = setup();
+
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[coin(100, "uusd")]),
+        ExecuteMsg::BatchUnxswap {
+            swaps: vec![batch_params(100, None), batch_params(50, None)],
+            atomic: false,
+        },
+    )
+    .unwrap_err();
+    assert!(matches!(err, ContractError::Std(_)));
+}
+
+#[test]
+fn batch_unxswap_refunds_failed_swaps() {
+    let mut deps = setup();
+
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[coin(150, "uusd")]),
+        ExecuteMsg::BatchUnxswap {
+            swaps: vec![batch_params(100, Some("addr0001")), batch_params(50, None)],
+            atomic: false,
+        },
+    )
+    .unwrap();
+    assert_eq!(res.messages.len(), 2);
+    assert!(res
+        .messages
+        .iter()
+        .all(|msg| msg.reply_on == ReplyOn::Always && msg.id == 1));
+
+    deps.querier.with_balance("addr0001", &[coin(99, "uluna")]);
+    let res = reply(
+        deps.as_mut(),
+        mock_env(),
+        Reply {
+            id: 1,
+            result: SubMsgResult::Ok(SubMsgResponse {
+                events: vec![],
+                data: None,
+            }),
+        },
+    )
+    .unwrap();
+    assert!(res.messages.is_empty());
+    assert!(res.events[0]
+        .attributes
+        .contains(&attr("return_amount", "99")));
+
+    let res = reply(
+        deps.as_mut(),
+        mock_env(),
+        Reply {
+            id: 1,
+            result: SubMsgResult::Err("swap failed".to_string()),
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(BankMsg::Send {
+            to_address: "addr0000".to_string(),
+            amount: vec![coin(50, "uusd")],
+        })]
+    );
+}
+
+#[test]
+fn atomic_batch_unxswap_only_replies_on_success() {
+    let mut deps = setup();
+
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[coin(100, "uusd")]),
+        ExecuteMsg::BatchUnxswap {
+            swaps: vec![batch_params(100, None)],
+            atomic: true,
+        },
+    )
+    .unwrap();
+    assert_eq!(res.messages[0].reply_on, ReplyOn::Success);
+}
+
+#[test]
+fn execute_route_is_self_only() {
+    let mut deps = setup();
+
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[]),
+        ExecuteMsg::ExecuteRoute {
+            offer_asset: Asset {
+                info: native("uusd"),
+                amount: Uint128::new(100),
+            },
+            steps: vec![astroport_step(native("uusd"))],
+            minimum_receive: None,
+            to: "addr0000".to_string(),
+            target_asset_info: native("uluna"),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+}
+
+/// Decodes the contract and message of a `WasmMsg::Execute`.
+fn wasm_execute<T: DeserializeOwned>(msg: &CosmosMsg) -> (String, T) {
+    match msg {
+        CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr, msg, ..
+        }) => (contract_addr.clone(), from_binary(msg).unwrap()),
+        msg => panic!("unexpected message {msg:?}"),
+    }
+}
+
+fn unxswap_from(
+    sender: &str,
+    minimum_receive: Option<u128>,
+) -> (MockDeps, Result<Response, ContractError>) {
+    let mut deps = setup();
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(sender, &[]),
+        ExecuteMsg::UnxswapFrom {
+            owner: "addr0000".to_string(),
+            token: "asset0000".to_string(),
+            amount: Uint128::new(100),
+            steps: vec![astroport_step(token("asset0000"))],
+            minimum_receive: minimum_receive.map(Uint128::new),
+            to: None,
+            target_asset_info: native("uluna"),
+        },
+    );
+    (deps, res)
+}
+
+#[test]
+fn unxswap_from_is_owner_only() {
+    let (_, res) = unxswap_from("addr0001", None);
+    assert_eq!(res.unwrap_err(), ContractError::Unauthorized {});
+}
+
+#[test]
+fn unxswap_from_pulls_the_allowance_before_routing() {
+    let (_, res) = unxswap_from("addr0000", Some(90));
+    let res = res.unwrap();
+    assert_eq!(res.messages.len(), 3);
+
+    let (contract, msg) = wasm_execute::<Cw20ExecuteMsg>(&res.messages[0].msg);
+    assert_eq!(contract, "asset0000");
+    assert_eq!(
+        msg,
+        Cw20ExecuteMsg::TransferFrom {
+            owner: "addr0000".to_string(),
+            recipient: MOCK_CONTRACT_ADDR.to_string(),
+            amount: Uint128::new(100),
+        }
+    );
+    let (contract, msg) = wasm_execute::<ExecuteMsg>(&res.messages[1].msg);
+    assert_eq!(contract, MOCK_CONTRACT_ADDR);
+    assert!(matches!(
+        msg,
+        ExecuteMsg::AstroportSwap {
+            offer_amount: Some(amount),
+            to: Some(to),
+            ..
+        } if amount == Uint128::new(100) && to == "addr0000"
+    ));
+    let (_, msg) = wasm_execute::<ExecuteMsg>(&res.messages[2].msg);
+    assert_eq!(
+        msg,
+        ExecuteMsg::AssertMinimumReceive {
+            asset_info: native("uluna"),
+            prev_balance: Uint128::zero(),
+            minimum_receive: Uint128::new(90),
+            receiver: "addr0000".to_string(),
+        }
+    );
+}
+
+#[test]
+fn assert_minimum_receive_compares_the_balance_increase() {
+    let mut deps = setup();
+    deps.querier.with_balance("addr0000", &[coin(89, "uluna")]);
+
+    let msg = ExecuteMsg::AssertMinimumReceive {
+        asset_info: native("uluna"),
+        prev_balance: Uint128::zero(),
+        minimum_receive: Uint128::new(90),
+        receiver: "addr0000".to_string(),
+    };
+    let err = execute_self(&mut deps, msg.clone()).unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::AssertionMinimumReceive {
+            receive: Uint128::new(90),
+            amount: Uint128::new(89),
+        }
+    );
+
+    deps.querier.with_balance("addr0000", &[coin(90, "uluna")]);
+    execute_self(&mut deps, msg).unwrap();
+}
+
+fn consolidate_inputs() -> Vec<ConsolidateInput> {
+    vec![
+        ConsolidateInput {
+            asset: Asset {
+                info: native("uusd"),
+                amount: Uint128::new(100),
+            },
+            steps: vec![astroport_step(native("uusd"))],
+        },
+        ConsolidateInput {
+            asset: Asset {
+                info: token("asset0000"),
+                amount: Uint128::new(200),
+            },
+            steps: vec![astroport_step(token("asset0000"))],
+        },
+    ]
+}
+
+#[test]
+fn consolidate_routes_every_input_with_one_assertion() {
+    let mut deps = setup();
+
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[coin(100, "uusd")]),
+        ExecuteMsg::Consolidate {
+            inputs: consolidate_inputs(),
+            target_asset_info: native("uluna"),
+            minimum_receive: Some(Uint128::new(250)),
+            to: None,
+        },
+    )
+    .unwrap();
+    assert_eq!(res.messages.len(), 4);
+
+    let (contract, msg) = wasm_execute::<Cw20ExecuteMsg>(&res.messages[0].msg);
+    assert_eq!(contract, "asset0000");
+    assert!(matches!(
+        msg,
+        Cw20ExecuteMsg::TransferFrom { amount, .. } if amount == Uint128::new(200)
+    ));
+    let offer_amounts = res.messages[1..3]
+        .iter()
+        .map(|msg| match wasm_execute::<ExecuteMsg>(&msg.msg).1 {
+            ExecuteMsg::AstroportSwap { offer_amount, .. } => offer_amount.unwrap().u128(),
+            msg => panic!("unexpected message {msg:?}"),
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(offer_amounts, vec![100, 200]);
+    let (_, msg) = wasm_execute::<ExecuteMsg>(&res.messages[3].msg);
+    assert!(matches!(
+        msg,
+        ExecuteMsg::AssertMinimumReceive { minimum_receive, .. }
+            if minimum_receive == Uint128::new(250)
+    ));
+}
+
+#[test]
+fn consolidate_requires_native_inputs_attached() {
+    let mut deps = setup();
+
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[]),
+        ExecuteMsg::Consolidate {
+            inputs: consolidate_inputs(),
+            target_asset_info: native("uluna"),
+            minimum_receive: None,
+            to: None,
+        },
+    )
+    .unwrap_err();
+    assert!(matches!(err, ContractError::Std(_)));
+}
+
+#[test]
+fn consolidate_rejects_routes_through_other_inputs() {
+    let mut deps = setup();
+    let mut inputs = consolidate_inputs();
+    inputs[0].steps = vec![
+        astroport_step(native("uusd")),
+        astroport_step(token("asset0000")),
+    ];
+
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[coin(100, "uusd")]),
+        ExecuteMsg::Consolidate {
+            inputs,
+            target_asset_info: native("uluna"),
+            minimum_receive: None,
+            to: None,
+        },
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::RouteOverlap {
+            asset: "asset0000".to_string(),
+        }
+    );
+}
+
+fn distribute_leg(weight_bps: u16, target_asset_info: AssetInfo) -> DistributeLeg {
+    DistributeLeg {
+        weight_bps,
+        steps: vec![astroport_step(native("uusd"))],
+        minimum_receive: None,
+        target_asset_info,
+    }
+}
+
+#[test]
+fn distribute_splits_the_offer_by_weight() {
+    let mut deps = setup();
+
+    let mut legs = vec![
+        distribute_leg(3_333, native("uluna")),
+        distribute_leg(6_667, token("asset0000")),
+    ];
+    legs[1].minimum_receive = Some(Uint128::new(5));
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[coin(100, "uusd")]),
+        ExecuteMsg::Distribute {
+            offer_asset: Asset {
+                info: native("uusd"),
+                amount: Uint128::new(100),
+            },
+            legs,
+            to: None,
+        },
+    )
+    .unwrap();
+
+    // The last leg takes the rounding remainder
+    let offer_amounts = res
+        .messages
+        .iter()
+        .filter_map(|msg| match wasm_execute::<ExecuteMsg>(&msg.msg).1 {
+            ExecuteMsg::AstroportSwap { offer_amount, .. } => Some(offer_amount.unwrap().u128()),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(offer_amounts, vec![33, 67]);
+    assert_eq!(res.messages.len(), 3);
+    assert!(res.attributes.contains(&attr("leg_1", "67asset0000")));
+}
+
+#[test]
+fn distribute_rejects_invalid_weights() {
+    let mut deps = setup();
+
+    for legs in [
+        vec![distribute_leg(5_000, native("uluna"))],
+        vec![
+            distribute_leg(0, native("uluna")),
+            distribute_leg(10_000, token("asset0000")),
+        ],
+    ] {
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("addr0000", &[coin(100, "uusd")]),
+            ExecuteMsg::Distribute {
+                offer_asset: Asset {
+                    info: native("uusd"),
+                    amount: Uint128::new(100),
+                },
+                legs,
+                to: None,
+            },
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::InvalidWeights {});
+    }
+}
+
+#[test]
+fn distribute_rejects_legs_with_the_same_target() {
+    let mut deps = setup();
+
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[coin(100, "uusd")]),
+        ExecuteMsg::Distribute {
+            offer_asset: Asset {
+                info: native("uusd"),
+                amount: Uint128::new(100),
+            },
+            legs: vec![
+                distribute_leg(5_000, native("uluna")),
+                distribute_leg(5_000, native("uluna")),
+            ],
+            to: None,
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::DoublingAssets {});
+}
+
+fn cyclic_route() -> Vec<SwapOperation> {
+    vec![
+        astroport_step(native("uusd")),
+        astroport_step(native("uluna")),
+    ]
+}
+
+#[test]
+fn arbitrage_asserts_the_minimum_profit() {
+    let mut deps = setup();
+    deps.querier
+        .with_balance("addr0000", &[coin(1_000, "uusd")]);
+
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[coin(100, "uusd")]),
+        ExecuteMsg::Arbitrage {
+            steps: cyclic_route(),
+            min_profit: Uint128::new(5),
+        },
+    )
+    .unwrap();
+    assert_eq!(res.messages.len(), 3);
+    let (_, msg) = wasm_execute::<ExecuteMsg>(&res.messages[2].msg);
+    assert_eq!(
+        msg,
+        ExecuteMsg::AssertMinimumProfit {
+            asset_info: native("uusd"),
+            prev_balance: Uint128::new(1_000),
+            offer_amount: Uint128::new(100),
+            min_profit: Uint128::new(5),
+            receiver: "addr0000".to_string(),
+        }
+    );
+
+    let msg = ExecuteMsg::AssertMinimumProfit {
+        asset_info: native("uusd"),
+        prev_balance: Uint128::new(1_000),
+        offer_amount: Uint128::new(100),
+        min_profit: Uint128::new(5),
+        receiver: "addr0000".to_string(),
+    };
+    deps.querier
+        .with_balance("addr0000", &[coin(1_104, "uusd")]);
+    let err = execute_self(&mut deps, msg.clone()).unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::AssertionMinimumProfit {
+            min_profit: Uint128::new(5),
+            offer_amount: Uint128::new(100),
+            return_amount: Uint128::new(104),
+        }
+    );
+
+    deps.querier
+        .with_balance("addr0000", &[coin(1_105, "uusd")]);
+    let res = execute_self(&mut deps, msg).unwrap();
+    assert!(res.attributes.contains(&attr("profit", "5")));
+}
+
+#[test]
+fn arbitrage_requires_a_cyclic_native_route() {
+    let mut deps = setup();
+
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[coin(100, "uusd")]),
+        ExecuteMsg::Arbitrage {
+            steps: vec![astroport_step(native("uusd"))],
+            min_profit: Uint128::zero(),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::NotCyclicRoute {
+            asset: "uusd".to_string(),
+        }
+    );
+
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[]),
+        ExecuteMsg::Arbitrage {
+            steps: vec![astroport_step(token("asset0000"))],
+            min_profit: Uint128::zero(),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::NativeOfferRequired {});
+}
+
+fn unxswap_msg(post_action: Option<PostAction>, to: Option<&str>) -> ExecuteMsg {
+    ExecuteMsg::Unxswap {
+        steps: vec![astroport_step(native("uusd"))],
+        minimum_receive: None,
+        to: to.map(Addr::unchecked),
+        target_asset_info: native("uluna"),
+        post_action,
+        callback: None,
+        recovery_addr: None,
+        ibc_forward: None,
+    }
+}
+
+fn post_action() -> PostAction {
+    PostAction {
+        contract: "vault0000".to_string(),
+        msg: to_binary(&"deposit").unwrap(),
+    }
+}
+
+#[test]
+fn unxswap_post_action_collects_the_output_first() {
+    let mut deps = setup();
+    deps.querier
+        .with_balance(MOCK_CONTRACT_ADDR, &[coin(100, "uusd"), coin(7, "uluna")]);
+
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[coin(100, "uusd")]),
+        unxswap_msg(Some(post_action()), Some("addr0001")),
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::PostActionWithReceiver {});
+
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[coin(100, "uusd")]),
+        unxswap_msg(Some(post_action()), None),
+    )
+    .unwrap();
+    assert_eq!(res.messages.len(), 2);
+    let (_, msg) = wasm_execute::<ExecuteMsg>(&res.messages[0].msg);
+    assert!(matches!(
+        msg,
+        ExecuteMsg::AstroportSwap { to: Some(to), .. } if to == MOCK_CONTRACT_ADDR
+    ));
+    let (_, msg) = wasm_execute::<ExecuteMsg>(&res.messages[1].msg);
+    assert_eq!(
+        msg,
+        ExecuteMsg::ExecutePostAction {
+            asset_info: native("uluna"),
+            prev_balance: Uint128::new(7),
+            post_action: post_action(),
+        }
+    );
+}
+
+#[test]
+fn post_action_delivers_the_balance_increase() {
+    let mut deps = setup();
+    deps.querier
+        .with_balance(MOCK_CONTRACT_ADDR, &[coin(97, "uluna")]);
+
+    let msg = ExecuteMsg::ExecutePostAction {
+        asset_info: native("uluna"),
+        prev_balance: Uint128::new(7),
+        post_action: post_action(),
+    };
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[]),
+        msg.clone(),
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+
+    let res = execute_self(&mut deps, msg).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(WasmMsg::Execute {
+            contract_addr: "vault0000".to_string(),
+            funds: vec![coin(90, "uluna")],
+            msg: to_binary(&"deposit").unwrap(),
+        })]
+    );
+
+    deps.querier
+        .with_token_balances("asset0000", &[(MOCK_CONTRACT_ADDR, Uint128::new(40))]);
+    let res = execute_self(
+        &mut deps,
+        ExecuteMsg::ExecutePostAction {
+            asset_info: token("asset0000"),
+            prev_balance: Uint128::new(10),
+            post_action: post_action(),
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        wasm_execute::<Cw20ExecuteMsg>(&res.messages[0].msg),
+        (
+            "asset0000".to_string(),
+            Cw20ExecuteMsg::Send {
+                contract: "vault0000".to_string(),
+                amount: Uint128::new(30),
+                msg: to_binary(&"deposit").unwrap(),
+            }
+        )
+    );
+}
+
+#[test]
+fn unxswap_callback_reports_the_spendable_offer() {
+    let mut deps = setup();
+    deps.querier
+        .with_balance(MOCK_CONTRACT_ADDR, &[coin(500, "uusd")]);
+    // Another user's order keeps 400 uusd escrowed in the contract
+    ESCROWED
+        .save(
+            deps.as_mut().storage,
+            "uusd".to_string(),
+            &Uint128::new(400),
+        )
+        .unwrap();
+
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("caller0000", &[coin(100, "uusd")]),
+        ExecuteMsg::Unxswap {
+            steps: vec![astroport_step(native("uusd"))],
+            minimum_receive: None,
+            to: None,
+            target_asset_info: native("uluna"),
+            post_action: None,
+            callback: Some(to_binary(&"ping").unwrap()),
+            recovery_addr: None,
+            ibc_forward: None,
+        },
+    )
+    .unwrap();
+    assert_eq!(res.messages[1].reply_on, ReplyOn::Success);
+    let (_, msg) = wasm_execute::<ExecuteMsg>(&res.messages[1].msg);
+    assert_eq!(
+        msg,
+        ExecuteMsg::ExecuteSwapCallback {
+            offer_asset: Asset {
+                info: native("uusd"),
+                amount: Uint128::new(100),
+            },
+            target_asset_info: native("uluna"),
+            prev_balance: Uint128::zero(),
+            receiver: "caller0000".to_string(),
+            sender: "caller0000".to_string(),
+            callback: to_binary(&"ping").unwrap(),
+        }
+    );
+}
+
+#[test]
+fn swap_callback_sets_the_swap_result() {
+    let mut deps = setup();
+    deps.querier
+        .with_balance("caller0000", &[coin(95, "uluna")]);
+    let offer_asset = Asset {
+        info: native("uusd"),
+        amount: Uint128::new(100),
+    };
+    let return_asset = Asset {
+        info: native("uluna"),
+        amount: Uint128::new(95),
+    };
+
+    let res = execute_self(
+        &mut deps,
+        ExecuteMsg::ExecuteSwapCallback {
+            offer_asset: offer_asset.clone(),
+            target_asset_info: native("uluna"),
+            prev_balance: Uint128::zero(),
+            receiver: "caller0000".to_string(),
+            sender: "caller0000".to_string(),
+            callback: to_binary(&"ping").unwrap(),
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        wasm_execute::<CallbackMsg>(&res.messages[0].msg),
+        (
+            "caller0000".to_string(),
+            CallbackMsg::SwapCallback {
+                offer_asset: offer_asset.clone(),
+                return_asset: return_asset.clone(),
+                original_msg: to_binary(&"ping").unwrap(),
+            }
+        )
+    );
+
+    // The reply forwards the result as the data of the whole execution
+    let res = reply(
+        deps.as_mut(),
+        mock_env(),
+        Reply {
+            id: 2,
+            result: SubMsgResult::Ok(SubMsgResponse {
+                events: vec![],
+                data: res.data,
+            }),
+        },
+    )
+    .unwrap();
+    let data: SwapResponse = from_binary(&res.data.unwrap()).unwrap();
+    assert_eq!(
+        data,
+        SwapResponse {
+            offer_asset,
+            return_asset,
+        }
+    );
+}
+
+#[test]
+fn zap_in_forwards_the_slippage_limits() {
+    let mut deps = setup();
+    deps.querier
+        .with_balance(MOCK_CONTRACT_ADDR, &[coin(2_000, "uusd")]);
+
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[coin(2_000, "uusd")]),
+        ExecuteMsg::ZapIn {
+            pool: PAIR.to_string(),
+            dex: "astroport".to_string(),
+            offer_asset: Asset {
+                info: native("uusd"),
+                amount: Uint128::new(2_000),
+            },
+            swap_steps: vec![],
+            belief_price: Some(Decimal::one()),
+            slippage_tolerance: Some(Decimal::percent(1)),
+            auto_stake: None,
+            receiver: None,
+        },
+    )
+    .unwrap();
+    let (_, msg) = wasm_execute::<ExecuteMsg>(&res.messages[0].msg);
+    assert!(matches!(
+        msg,
+        ExecuteMsg::ZapInSwap {
+            belief_price: Some(belief_price),
+            slippage_tolerance: Some(slippage_tolerance),
+            refund_to,
+            ..
+        } if belief_price == Decimal::one()
+            && slippage_tolerance == Decimal::percent(1)
+            && refund_to == "addr0000"
+    ));
+}
+
+#[test]
+fn zap_in_swap_is_bounded_by_the_slippage_limits() {
+    let mut deps = setup();
+    deps.querier
+        .with_balance(MOCK_CONTRACT_ADDR, &[coin(2_000, "uusd")]);
+
+    let msg = ExecuteMsg::ZapInSwap {
+        pool: PAIR.to_string(),
+        dex: "astroport".to_string(),
+        asset_info: native("uusd"),
+        prev_balance: Uint128::zero(),
+        belief_price: Some(Decimal::one()),
+        slippage_tolerance: Some(Decimal::percent(1)),
+        auto_stake: None,
+        receiver: "addr0000".to_string(),
+        refund_to: "addr0000".to_string(),
+    };
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[]),
+        msg.clone(),
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+
+    let res = execute_self(&mut deps, msg).unwrap();
+    assert_eq!(res.messages.len(), 2);
+    let (_, msg) = wasm_execute::<ExecuteMsg>(&res.messages[0].msg);
+    match msg {
+        ExecuteMsg::DexSwap {
+            dex,
+            offer_amount,
+            belief_price,
+            max_spread,
+            ..
+        } => {
+            assert_eq!(dex, "astroport");
+            assert_eq!(offer_amount, Some(Uint128::new(999)));
+            assert_eq!(belief_price, Some(Decimal::one()));
+            assert_eq!(max_spread, Some(Decimal::percent(1)));
+        }
+        msg => panic!("unexpected message {msg:?}"),
+    }
+}
+
+#[test]
+fn zap_in_provide_refunds_the_dust() {
+    let mut deps = setup();
+    deps.querier.with_balance(
+        MOCK_CONTRACT_ADDR,
+        &[coin(1_000, "uusd"), coin(900, "uluna")],
+    );
+
+    let res = execute_self(
+        &mut deps,
+        ExecuteMsg::ZapInProvide {
+            pool: PAIR.to_string(),
+            dex: "astroport".to_string(),
+            asset: Asset {
+                info: native("uusd"),
+                amount: Uint128::new(1_000),
+            },
+            other_asset_info: native("uluna"),
+            other_prev_balance: Uint128::zero(),
+            slippage_tolerance: None,
+            auto_stake: None,
+            receiver: "addr0000".to_string(),
+            refund_to: "addr0001".to_string(),
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages[0],
+        SubMsg::new(BankMsg::Send {
+            to_address: "addr0001".to_string(),
+            amount: vec![coin(100, "uusd")],
+        })
+    );
+    match &res.messages[1].msg {
+        CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr,
+            funds,
+            ..
+        }) => {
+            assert_eq!(contract_addr, PAIR);
+            assert_eq!(funds, &vec![coin(900, "uluna"), coin(900, "uusd")]);
+        }
+        msg => panic!("unexpected message {msg:?}"),
+    }
+}
+
+fn zap_out_msg(lp_token: &str, minimum_receive: Option<u128>) -> (MessageInfo, ExecuteMsg) {
+    (
+        mock_info(lp_token, &[]),
+        ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: "addr0000".to_string(),
+            amount: Uint128::new(1_000),
+            msg: to_binary(&Cw20HookMsg::ZapOut {
+                dex: "astroport".to_string(),
+                target_asset_info: native("uluna"),
+                steps_per_asset: vec![vec![astroport_step(native("uusd"))]],
+                minimum_receive: minimum_receive.map(Uint128::new),
+                to: None,
+            })
+            .unwrap(),
+        }),
+    )
+}
+
+#[test]
+fn zap_out_only_accepts_the_pair_lp_token() {
+    let mut deps = setup();
+    // A token claiming to be minted by the pair is not its LP token
+    deps.querier.with_smart_response(
+        "fake0000",
+        "minter",
+        &Some(MinterResponse {
+            minter: PAIR.to_string(),
+            cap: None,
+        }),
+    );
+
+    let (info, msg) = zap_out_msg("fake0000", None);
+    let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+}
+
+#[test]
+fn zap_out_withdraws_then_routes() {
+    let mut deps = setup();
+    deps.querier.with_smart_response(
+        "lptoken0000",
+        "minter",
+        &Some(MinterResponse {
+            minter: PAIR.to_string(),
+            cap: None,
+        }),
+    );
+    deps.querier
+        .with_balance(MOCK_CONTRACT_ADDR, &[coin(10, "uusd")]);
+
+    let (info, msg) = zap_out_msg("lptoken0000", Some(1_900));
+    let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+    assert_eq!(res.messages.len(), 3);
+    let (contract, msg) = wasm_execute::<Cw20ExecuteMsg>(&res.messages[0].msg);
+    assert_eq!(contract, "lptoken0000");
+    assert!(matches!(
+        msg,
+        Cw20ExecuteMsg::Send { contract, amount, .. }
+            if contract == PAIR && amount == Uint128::new(1_000)
+    ));
+    let (_, msg) = wasm_execute::<ExecuteMsg>(&res.messages[1].msg);
+    let prev_balances = vec![
+        Asset {
+            info: native("uusd"),
+            amount: Uint128::new(10),
+        },
+        Asset {
+            info: native("uluna"),
+            amount: Uint128::zero(),
+        },
+    ];
+    assert_eq!(
+        msg,
+        ExecuteMsg::ZapOutRoute {
+            prev_balances: prev_balances.clone(),
+            target_asset_info: native("uluna"),
+            steps_per_asset: vec![vec![astroport_step(native("uusd"))]],
+            to: "addr0000".to_string(),
+            sender: "addr0000".to_string(),
+        }
+    );
+    let (_, msg) = wasm_execute::<ExecuteMsg>(&res.messages[2].msg);
+    assert!(matches!(msg, ExecuteMsg::AssertMinimumReceive { .. }));
+
+    // The withdrawal returned 1_000 of each asset
+    deps.querier.with_balance(
+        MOCK_CONTRACT_ADDR,
+        &[coin(1_010, "uusd"), coin(1_000, "uluna")],
+    );
+    let res = execute_self(
+        &mut deps,
+        ExecuteMsg::ZapOutRoute {
+            prev_balances,
+            target_asset_info: native("uluna"),
+            steps_per_asset: vec![vec![astroport_step(native("uusd"))]],
+            to: "addr0000".to_string(),
+            sender: "addr0000".to_string(),
+        },
+    )
+    .unwrap();
+    let (_, msg) = wasm_execute::<ExecuteMsg>(&res.messages[0].msg);
+    assert!(matches!(
+        msg,
+        ExecuteMsg::AstroportSwap { offer_amount: Some(amount), .. }
+            if amount == Uint128::new(1_000)
+    ));
+    assert_eq!(
+        res.messages[1],
+        SubMsg::new(BankMsg::Send {
+            to_address: "addr0000".to_string(),
+            amount: vec![coin(1_000, "uluna")],
+        })
+    );
+}
+
+const GENERATOR: &str = "generator0000";
+
+fn pool_info() -> PoolInfoResponse {
+    PoolInfoResponse {
+        alloc_point: Uint128::new(100),
+        astro_tokens_per_block: Uint128::zero(),
+        last_reward_block: 0,
+        current_block: 0,
+        global_reward_index: Decimal::zero(),
+        pending_astro_rewards: Uint128::zero(),
+        reward_proxy: None,
+        pending_proxy_rewards: None,
+        accumulated_proxy_rewards_per_share: vec![],
+        proxy_reward_balance_before_update: Uint128::zero(),
+        orphan_proxy_rewards: vec![],
+        lp_supply: Uint128::zero(),
+    }
+}
+
+fn set_generator(deps: &mut MockDeps) {
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(OWNER, &[]),
+        ExecuteMsg::SetGenerator {
+            generator: GENERATOR.to_string(),
+        },
+    )
+    .unwrap();
+}
+
+fn provide_and_stake(deps: &mut MockDeps) -> Result<Response, ContractError> {
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[coin(100, "uluna"), coin(100, "uusd")]),
+        ExecuteMsg::ProvideAndStake {
+            pool: PAIR.to_string(),
+            assets: vec![
+                Asset {
+                    info: native("uusd"),
+                    amount: Uint128::new(100),
+                },
+                Asset {
+                    info: native("uluna"),
+                    amount: Uint128::new(100),
+                },
+            ],
+            slippage_tolerance: None,
+            receiver: None,
+        },
+    )
+}
+
+fn reply_ok(deps: &mut MockDeps, id: u64) -> Result<Response, ContractError> {
+    reply(
+        deps.as_mut(),
+        mock_env(),
+        Reply {
+            id,
+            result: SubMsgResult::Ok(SubMsgResponse {
+                events: vec![],
+                data: None,
+            }),
+        },
+    )
+}
+
+#[test]
+fn provide_and_stake_requires_a_generator_pool() {
+    let mut deps = setup();
+    set_generator(&mut deps);
+
+    let err = provide_and_stake(&mut deps).unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::GeneratorPoolNotFound {
+            lp_token: "lptoken0000".to_string(),
+        }
+    );
+}
+
+#[test]
+fn provide_and_stake_deposits_the_minted_lp() {
+    let mut deps = setup();
+    set_generator(&mut deps);
+    deps.querier
+        .with_smart_response(GENERATOR, "pool_info", &pool_info());
+    deps.querier
+        .with_smart_response(GENERATOR, "deposit", &Uint128::new(10));
+    deps.querier
+        .with_token_balances("lptoken0000", &[(MOCK_CONTRACT_ADDR, Uint128::new(5))]);
+
+    // Provisions don't block each other while waiting for their replies
+    let first = provide_and_stake(&mut deps).unwrap();
+    let second = provide_and_stake(&mut deps).unwrap();
+    assert_eq!(first.messages[0].reply_on, ReplyOn::Success);
+    assert_ne!(first.messages[0].id, second.messages[0].id);
+
+    deps.querier
+        .with_token_balances("lptoken0000", &[(MOCK_CONTRACT_ADDR, Uint128::new(105))]);
+    let res = reply_ok(&mut deps, second.messages[0].id).unwrap();
+    let (contract, msg) = wasm_execute::<Cw20ExecuteMsg>(&res.messages[0].msg);
+    assert_eq!(contract, "lptoken0000");
+    assert_eq!(
+        msg,
+        Cw20ExecuteMsg::Send {
+            contract: GENERATOR.to_string(),
+            amount: Uint128::new(100),
+            msg: to_binary(&GeneratorCw20HookMsg::DepositFor("addr0000".to_string())).unwrap(),
+        }
+    );
+
+    deps.querier
+        .with_smart_response(GENERATOR, "deposit", &Uint128::new(110));
+    let res = reply_ok(&mut deps, res.messages[0].id).unwrap();
+    assert!(res.events[0]
+        .attributes
+        .contains(&attr("staked_amount", "100")));
+
+    // The stake is settled, while the first provision is still pending
+    reply_ok(&mut deps, second.messages[0].id).unwrap_err();
+    reply_ok(&mut deps, first.messages[0].id).unwrap();
+}
+
+const ASTRO: &str = "astro0000";
+const LP_TOKEN: &str = "lptoken0000";
+
+fn compound_routes() -> Vec<CompoundRoute> {
+    vec![CompoundRoute {
+        reward_asset_info: token(ASTRO),
+        steps: vec![SwapOperation::AstroportSwap {
+            pool_address: "pair0001".to_string(),
+            offer_asset_info: token(ASTRO),
+            ask_asset_info: Some(native("uusd")),
+            belief_price: None,
+            max_spread: None,
+        }],
+        min_harvest: Uint128::new(10),
+    }]
+}
+
+fn set_compound_config(deps: &mut MockDeps, sender: &str) -> Result<Response, ContractError> {
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(sender, &[]),
+        ExecuteMsg::SetCompoundConfig {
+            lp_token: LP_TOKEN.to_string(),
+            pool: PAIR.to_string(),
+            reward_routes: compound_routes(),
+            max_spread: Some(Decimal::percent(1)),
+        },
+    )
+}
+
+fn deposit_compound(
+    deps: &mut MockDeps,
+    sender: &str,
+    amount: u128,
+) -> Result<Response, ContractError> {
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(LP_TOKEN, &[]),
+        ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: sender.to_string(),
+            amount: Uint128::new(amount),
+            msg: to_binary(&Cw20HookMsg::DepositCompound {}).unwrap(),
+        }),
+    )
+}
+
+fn compound_assets(astro: u128, uusd: u128, uluna: u128) -> Vec<Asset> {
+    vec![
+        Asset {
+            info: token(ASTRO),
+            amount: Uint128::new(astro),
+        },
+        Asset {
+            info: native("uusd"),
+            amount: Uint128::new(uusd),
+        },
+        Asset {
+            info: native("uluna"),
+            amount: Uint128::new(uluna),
+        },
+    ]
+}
+
+fn compound_config(deps: &MockDeps) -> Option<CompoundConfigResponse> {
+    from_binary(
+        &query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::CompoundConfig {
+                lp_token: LP_TOKEN.to_string(),
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap()
+}
+
+fn set_pending_astro(deps: &mut MockDeps, pending: u128) {
+    deps.querier.with_smart_response(
+        GENERATOR,
+        "config",
+        &GeneratorConfig {
+            owner: Addr::unchecked(OWNER),
+            factory: Addr::unchecked("factory0000"),
+            generator_controller: None,
+            voting_escrow: None,
+            voting_escrow_delegation: None,
+            astro_token: token(ASTRO),
+            tokens_per_block: Uint128::zero(),
+            total_alloc_point: Uint128::zero(),
+            start_block: Uint64::zero(),
+            vesting_contract: Addr::unchecked("vesting0000"),
+            active_pools: vec![],
+            blocked_tokens_list: vec![],
+            guardian: None,
+            checkpoint_generator_limit: None,
+        },
+    );
+    deps.querier.with_smart_response(
+        GENERATOR,
+        "pending_token",
+        &PendingTokenResponse {
+            pending: Uint128::new(pending),
+            pending_on_proxy: None,
+        },
+    );
+}
+
+#[test]
+fn compound_config_is_owner_only_and_checks_the_pool() {
+    let mut deps = setup();
+    set_generator(&mut deps);
+
+    let err = set_compound_config(&mut deps, "addr0000").unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(OWNER, &[]),
+        ExecuteMsg::SetCompoundConfig {
+            lp_token: "lptoken0001".to_string(),
+            pool: PAIR.to_string(),
+            reward_routes: compound_routes(),
+            max_spread: None,
+        },
+    )
+    .unwrap_err();
+    assert!(err.to_string().contains("doesn't belong to the pool"));
+
+    // Callers can only compound an LP token the way the owner configured
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[]),
+        ExecuteMsg::Compound {
+            lp_token: LP_TOKEN.to_string(),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::CompoundConfigNotSet {
+            lp_token: LP_TOKEN.to_string(),
+        }
+    );
+
+    set_compound_config(&mut deps, OWNER).unwrap();
+    let config = compound_config(&deps).unwrap();
+    assert_eq!(config.pool, Addr::unchecked(PAIR));
+    assert_eq!(config.asset_info, native("uusd"));
+    assert_eq!(config.reward_routes, compound_routes());
+    assert_eq!(config.max_spread, Some(Decimal::percent(1)));
+}
+
+#[test]
+fn deposit_compound_escrows_the_claimed_rewards() {
+    let mut deps = setup();
+    set_generator(&mut deps);
+    deps.querier
+        .with_smart_response(GENERATOR, "deposit", &Uint128::zero());
+    deps.querier
+        .with_token_balances(ASTRO, &[(MOCK_CONTRACT_ADDR, Uint128::new(20))]);
+
+    let err = deposit_compound(&mut deps, "addr0000", 100).unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::CompoundConfigNotSet {
+            lp_token: LP_TOKEN.to_string(),
+        }
+    );
+
+    set_compound_config(&mut deps, OWNER).unwrap();
+    let res = deposit_compound(&mut deps, "addr0000", 100).unwrap();
+    assert!(res.attributes.contains(&attr("shares", "100000000")));
+    let (contract, msg) = wasm_execute::<Cw20ExecuteMsg>(&res.messages[0].msg);
+    assert_eq!(contract, LP_TOKEN);
+    assert!(matches!(msg, Cw20ExecuteMsg::Send { .. }));
+    let (contract, msg) = wasm_execute::<ExecuteMsg>(&res.messages[1].msg);
+    assert_eq!(contract, MOCK_CONTRACT_ADDR);
+    assert_eq!(
+        msg,
+        ExecuteMsg::EscrowCompoundLeftovers {
+            lp_token: LP_TOKEN.to_string(),
+            prev_balances: compound_assets(20, 0, 0),
+        }
+    );
+}
+
+#[test]
+fn compound_shares_resist_donation_inflation() {
+    let mut deps = setup();
+    set_generator(&mut deps);
+    set_compound_config(&mut deps, OWNER).unwrap();
+    deps.querier
+        .with_smart_response(GENERATOR, "deposit", &Uint128::zero());
+    deposit_compound(&mut deps, "attacker", 1).unwrap();
+
+    // The attacker stakes a large amount for the router directly in the generator
+    deps.querier
+        .with_smart_response(GENERATOR, "deposit", &Uint128::new(1_000_001));
+    deposit_compound(&mut deps, "addr0000", 1_000).unwrap();
+
+    deps.querier
+        .with_smart_response(GENERATOR, "deposit", &Uint128::new(1_001_001));
+    let position = |owner: &str| -> CompoundPositionResponse {
+        from_binary(
+            &query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::CompoundPosition {
+                    lp_token: LP_TOKEN.to_string(),
+                    owner: owner.to_string(),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap()
+    };
+    assert_eq!(position("addr0000").lp_amount, Uint128::new(999));
+    assert!(position("attacker").lp_amount < Uint128::new(1_000_001));
+}
+
+#[test]
+fn compound_leftovers_are_escrowed_until_the_next_compound() {
+    let mut deps = setup();
+    set_generator(&mut deps);
+    set_compound_config(&mut deps, OWNER).unwrap();
+    deps.querier
+        .with_token_balances(ASTRO, &[(MOCK_CONTRACT_ADDR, Uint128::new(50))]);
+    deps.querier
+        .with_balance(MOCK_CONTRACT_ADDR, &[coin(5, "uusd")]);
+
+    let leftovers = ExecuteMsg::EscrowCompoundLeftovers {
+        lp_token: LP_TOKEN.to_string(),
+        prev_balances: compound_assets(20, 0, 0),
+    };
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[]),
+        leftovers.clone(),
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+    execute_self(&mut deps, leftovers).unwrap();
+    assert_eq!(
+        compound_config(&deps).unwrap().idle_assets,
+        compound_assets(30, 5, 0)[..2].to_vec()
+    );
+
+    // Swap hops can't spend the leftovers
+    let err = execute_self(&mut deps, astroport_swap(native("uusd"), 5)).unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InsufficientFunds {
+            available: Uint128::zero(),
+            required: Uint128::new(5),
+        }
+    );
+
+    // The ASTRO leftovers lift the pending dust over the harvest threshold
+    set_pending_astro(&mut deps, 5);
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[]),
+        ExecuteMsg::Compound {
+            lp_token: LP_TOKEN.to_string(),
+        },
+    )
+    .unwrap();
+    assert_eq!(res.messages.len(), 3);
+    let (_, msg) = wasm_execute::<ExecuteMsg>(&res.messages[1].msg);
+    assert_eq!(
+        msg,
+        ExecuteMsg::CompoundSwap {
+            lp_token: LP_TOKEN.to_string(),
+            reward_routes: compound_routes(),
+            prev_balances: compound_assets(20, 0, 0),
+        }
+    );
+    let (_, msg) = wasm_execute::<ExecuteMsg>(&res.messages[2].msg);
+    assert_eq!(
+        msg,
+        ExecuteMsg::EscrowCompoundLeftovers {
+            lp_token: LP_TOKEN.to_string(),
+            prev_balances: compound_assets(20, 0, 0),
+        }
+    );
+    assert_eq!(
+        ESCROWED
+            .may_load(&deps.storage, token(ASTRO).to_string())
+            .unwrap(),
+        None
+    );
+    assert_eq!(
+        ESCROWED
+            .may_load(&deps.storage, "uusd".to_string())
+            .unwrap(),
+        None
+    );
+    assert_eq!(compound_config(&deps).unwrap().idle_assets, vec![]);
+}
+
+#[test]
+fn compound_skips_dust_rewards() {
+    let mut deps = setup();
+    set_generator(&mut deps);
+    set_compound_config(&mut deps, OWNER).unwrap();
+    set_pending_astro(&mut deps, 5);
+
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[]),
+        ExecuteMsg::Compound {
+            lp_token: LP_TOKEN.to_string(),
+        },
+    )
+    .unwrap();
+    assert!(res.messages.is_empty());
+    assert!(res.attributes.contains(&attr("skipped", "dust")));
+}
+
+#[test]
+fn compound_swap_zaps_with_the_configured_slippage() {
+    let mut deps = setup();
+    set_generator(&mut deps);
+    set_compound_config(&mut deps, OWNER).unwrap();
+    deps.querier
+        .with_token_balances(ASTRO, &[(MOCK_CONTRACT_ADDR, Uint128::new(80))]);
+
+    let msg = ExecuteMsg::CompoundSwap {
+        lp_token: LP_TOKEN.to_string(),
+        reward_routes: compound_routes(),
+        prev_balances: compound_assets(20, 0, 0),
+    };
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[]),
+        msg.clone(),
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+
+    let res = execute_self(&mut deps, msg).unwrap();
+    assert!(res.attributes.contains(&attr("claimed", "60astro0000")));
+    assert_eq!(res.messages.len(), 2);
+    let (_, msg) = wasm_execute::<ExecuteMsg>(&res.messages[1].msg);
+    assert_eq!(
+        msg,
+        ExecuteMsg::ZapInSwap {
+            pool: PAIR.to_string(),
+            dex: "astroport".to_string(),
+            asset_info: native("uusd"),
+            prev_balance: Uint128::zero(),
+            belief_price: None,
+            slippage_tolerance: Some(Decimal::percent(1)),
+            auto_stake: Some(true),
+            receiver: MOCK_CONTRACT_ADDR.to_string(),
+            refund_to: MOCK_CONTRACT_ADDR.to_string(),
+        }
+    );
+}
+
+fn set_keeper_tip(
+    deps: &mut MockDeps,
+    sender: &str,
+    tip_bps: u16,
+) -> Result<Response, ContractError> {
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(sender, &[]),
+        ExecuteMsg::SetKeeperTip { tip_bps },
+    )
+}
+
+fn place_limit_order(deps: &mut MockDeps, min_price: Decimal) {
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[coin(1_000, "uusd")]),
+        ExecuteMsg::PlaceLimitOrder {
+            offer_asset: Asset {
+                info: native("uusd"),
+                amount: Uint128::new(1_000),
+            },
+            steps: vec![astroport_step(native("uusd"))],
+            min_price,
+            expiry: None,
+        },
+    )
+    .unwrap();
+}
+
+fn execute_order(deps: &mut MockDeps, id: u64) -> Result<Response, ContractError> {
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("keeper0000", &[]),
+        ExecuteMsg::ExecuteOrder { id },
+    )
+}
+
+#[test]
+fn keeper_tip_is_capped() {
+    let mut deps = setup();
+
+    let err = set_keeper_tip(&mut deps, "addr0000", 100).unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+    let err = set_keeper_tip(&mut deps, OWNER, 501).unwrap_err();
+    assert_eq!(err, ContractError::InvalidKeeperTip { max_tip_bps: 500 });
+    set_keeper_tip(&mut deps, OWNER, 500).unwrap();
+}
+
+#[test]
+fn limit_order_price_is_checked_after_the_keeper_tip() {
+    let mut deps = setup();
+    set_keeper_tip(&mut deps, OWNER, 500).unwrap();
+    deps.querier
+        .with_balance(MOCK_CONTRACT_ADDR, &[coin(1_000, "uusd")]);
+
+    // The route returns 999 uluna, of which the keeper gets 49
+    place_limit_order(&mut deps, Decimal::percent(96));
+    let err = execute_order(&mut deps, 1).unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::OrderPriceNotReached {
+            id: 1,
+            receive: Uint128::new(960),
+            amount: Uint128::new(950),
+        }
+    );
+
+    place_limit_order(&mut deps, Decimal::percent(94));
+    let res = execute_order(&mut deps, 2).unwrap();
+    let (_, msg) = wasm_execute::<ExecuteMsg>(&res.messages[1].msg);
+    assert_eq!(
+        msg,
+        ExecuteMsg::SettleOrder {
+            id: 2,
+            owner: "addr0000".to_string(),
+            keeper: "keeper0000".to_string(),
+            target_asset_info: native("uluna"),
+            prev_balance: Uint128::zero(),
+            minimum_receive: Uint128::new(940),
+        }
+    );
+}
+
+#[test]
+fn settle_order_checks_the_return_after_the_keeper_tip() {
+    let mut deps = setup();
+    set_keeper_tip(&mut deps, OWNER, 500).unwrap();
+    deps.querier
+        .with_balance(MOCK_CONTRACT_ADDR, &[coin(1_000, "uluna")]);
+    let settle_order = |minimum_receive: u128| ExecuteMsg::SettleOrder {
+        id: 1,
+        owner: "addr0000".to_string(),
+        keeper: "keeper0000".to_string(),
+        target_asset_info: native("uluna"),
+        prev_balance: Uint128::zero(),
+        minimum_receive: Uint128::new(minimum_receive),
+    };
+
+    let err = execute_self(&mut deps, settle_order(960)).unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::AssertionMinimumReceive {
+            receive: Uint128::new(960),
+            amount: Uint128::new(950),
+        }
+    );
+
+    let res = execute_self(&mut deps, settle_order(950)).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![
+            SubMsg::new(BankMsg::Send {
+                to_address: "keeper0000".to_string(),
+                amount: vec![coin(50, "uluna")],
+            }),
+            SubMsg::new(BankMsg::Send {
+                to_address: "addr0000".to_string(),
+                amount: vec![coin(950, "uluna")],
+            }),
+        ]
+    );
+}
+
+fn create_dca(deps: &mut MockDeps, max_price: Decimal) -> Result<Response, ContractError> {
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[coin(300, "uusd")]),
+        ExecuteMsg::CreateDca {
+            offer_asset: Asset {
+                info: native("uusd"),
+                amount: Uint128::new(300),
+            },
+            steps: vec![astroport_step(native("uusd"))],
+            chunk_amount: Uint128::new(100),
+            interval_seconds: 60,
+            max_price,
+        },
+    )
+}
+
+#[test]
+fn dca_chunks_are_bounded_by_the_max_price() {
+    let mut deps = setup();
+    deps.querier
+        .with_balance(MOCK_CONTRACT_ADDR, &[coin(300, "uusd")]);
+
+    let err = create_dca(&mut deps, Decimal::zero()).unwrap_err();
+    assert!(err.to_string().contains("Max price must be positive"));
+    create_dca(&mut deps, Decimal::percent(200)).unwrap();
+
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("keeper0000", &[]),
+        ExecuteMsg::ExecuteDca { id: 1 },
+    )
+    .unwrap();
+    let (_, msg) = wasm_execute::<ExecuteMsg>(&res.messages[1].msg);
+    assert_eq!(
+        msg,
+        ExecuteMsg::SettleDca {
+            id: 1,
+            index: 1,
+            offer_amount: Uint128::new(100),
+            prev_balance: Uint128::zero(),
+            minimum_receive: Uint128::new(50),
+        }
+    );
+
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("keeper0000", &[]),
+        ExecuteMsg::ExecuteDca { id: 1 },
+    )
+    .unwrap_err();
+    assert!(matches!(err, ContractError::DcaNotReady { id: 1, .. }));
+}
+
+fn place_stop_loss(deps: &mut MockDeps) {
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[coin(1_000, "uusd")]),
+        ExecuteMsg::PlaceConditionalOrder {
+            offer_asset: Asset {
+                info: native("uusd"),
+                amount: Uint128::new(1_000),
+            },
+            steps: vec![astroport_step(native("uusd"))],
+            minimum_receive: None,
+            price_pool: PAIR.to_string(),
+            dex: "astroport".to_string(),
+            condition: PriceCondition::Below {
+                price: Decimal::percent(90),
+            },
+            twap_window_seconds: 100,
+        },
+    )
+    .unwrap();
+}
+
+fn set_cumulative_price(deps: &mut MockDeps, cumulative_price: u128) {
+    deps.querier.with_cumulative_prices(
+        PAIR,
+        &[(
+            native("uusd"),
+            native("uluna"),
+            Uint128::new(cumulative_price),
+        )],
+    );
+}
+
+fn trigger(deps: &mut MockDeps, seconds: u64) -> Result<Response, ContractError> {
+    let mut env = mock_env();
+    env.block.time = env.block.time.plus_seconds(seconds);
+    execute(
+        deps.as_mut(),
+        env,
+        mock_info("keeper0000", &[]),
+        ExecuteMsg::Trigger { id: 1 },
+    )
+}
+
+#[test]
+fn conditional_order_triggers_on_the_twap() {
+    let mut deps = setup();
+    set_cumulative_price(&mut deps, 0);
+    place_stop_loss(&mut deps);
+
+    let err = trigger(&mut deps, 99).unwrap_err();
+    assert!(matches!(
+        err,
+        ContractError::TwapWindowNotElapsed { id: 1, .. }
+    ));
+
+    // A TWAP of 0.95 keeps the order open and restarts the window
+    set_cumulative_price(&mut deps, 95_000_000);
+    let res = trigger(&mut deps, 100).unwrap();
+    assert!(res.messages.is_empty());
+    assert!(res.attributes.contains(&attr("twap", "0.95")));
+    assert!(res.attributes.contains(&attr("triggered", "false")));
+    trigger(&mut deps, 199).unwrap_err();
+
+    // The next window averages 0.85, whatever the spot price of the pair
+    set_cumulative_price(&mut deps, 180_000_000);
+    let res = trigger(&mut deps, 200).unwrap();
+    assert!(res.attributes.contains(&attr("twap", "0.85")));
+    assert!(res.attributes.contains(&attr("triggered", "true")));
+    let (_, msg) = wasm_execute::<ExecuteMsg>(&res.messages[0].msg);
+    assert_eq!(
+        msg,
+        ExecuteMsg::AstroportSwap {
+            pool_address: PAIR.to_string(),
+            offer_asset_info: native("uusd"),
+            offer_amount: Some(Uint128::new(1_000)),
+            ask_asset_info: None,
+            belief_price: None,
+            max_spread: None,
+            to: Some("addr0000".to_string()),
+        }
+    );
+    assert_eq!(
+        ESCROWED
+            .may_load(&deps.storage, "uusd".to_string())
+            .unwrap(),
+        None
+    );
+    trigger(&mut deps, 300).unwrap_err();
+}
+
+#[test]
+fn conditional_order_is_cancelled_by_its_owner() {
+    let mut deps = setup();
+    set_cumulative_price(&mut deps, 0);
+    place_stop_loss(&mut deps);
+
+    let cancel = |deps: &mut MockDeps, sender: &str| {
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(sender, &[]),
+            ExecuteMsg::CancelConditionalOrder { id: 1 },
+        )
+    };
+    let err = cancel(&mut deps, "keeper0000").unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+
+    let res = cancel(&mut deps, "addr0000").unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(BankMsg::Send {
+            to_address: "addr0000".to_string(),
+            amount: vec![coin(1_000, "uusd")],
+        })]
+    );
+}
+
+/// An API whose account addresses are the hex encoding of their canonical bytes, so that signer
+/// addresses can be derived from public keys like on chain.
+struct HexApi(MockApi);
+
+impl HexApi {
+    fn decode(human: &str) -> Option<Vec<u8>> {
+        if human.len() < 40 {
+            return None;
+        }
+        (0..human.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(human.get(i..i + 2)?, 16).ok())
+            .collect()
+    }
+}
+
+impl Api for HexApi {
+    fn addr_validate(&self, human: &str) -> StdResult<Addr> {
+        let canonical = self.addr_canonicalize(human)?;
+        let addr = self.addr_humanize(&canonical)?;
+        if addr != human {
+            return Err(StdError::generic_err(
+                "Invalid input: address not normalized",
+            ));
+        }
+        Ok(addr)
+    }
+
+    fn addr_canonicalize(&self, human: &str) -> StdResult<CanonicalAddr> {
+        match Self::decode(human) {
+            Some(bytes) => Ok(bytes.into()),
+            None => self.0.addr_canonicalize(human),
+        }
+    }
+
+    fn addr_humanize(&self, canonical: &CanonicalAddr) -> StdResult<Addr> {
+        match canonical.len() {
+            20 | 32 => Ok(Addr::unchecked(
+                canonical
+                    .as_slice()
+                    .iter()
+                    .map(|byte| format!("{byte:02x}"))
+                    .collect::<String>(),
+            )),
+            _ => self.0.addr_humanize(canonical),
+        }
+    }
+
+    fn secp256k1_verify(
+        &self,
+        message_hash: &[u8],
+        signature: &[u8],
+        public_key: &[u8],
+    ) -> Result<bool, VerificationError> {
+        self.0.secp256k1_verify(message_hash, signature, public_key)
+    }
+
+    fn secp256k1_recover_pubkey(
+        &self,
+        message_hash: &[u8],
+        signature: &[u8],
+        recovery_param: u8,
+    ) -> Result<Vec<u8>, RecoverPubkeyError> {
+        self.0
+            .secp256k1_recover_pubkey(message_hash, signature, recovery_param)
+    }
+
+    fn ed25519_verify(
+        &self,
+        message: &[u8],
+        signature: &[u8],
+        public_key: &[u8],
+    ) -> Result<bool, VerificationError> {
+        self.0.ed25519_verify(message, signature, public_key)
+    }
+
+    fn ed25519_batch_verify(
+        &self,
+        messages: &[&[u8]],
+        signatures: &[&[u8]],
+        public_keys: &[&[u8]],
+    ) -> Result<bool, VerificationError> {
+        self.0
+            .ed25519_batch_verify(messages, signatures, public_keys)
+    }
+
+    fn debug(&self, message: &str) {
+        self.0.debug(message)
+    }
+}
+
+type SignerDeps = OwnedDeps<MockStorage, HexApi, WasmMockQuerier>;
+
+fn signer_setup() -> (SignerDeps, SigningKey) {
+    let mock = setup();
+    let mut deps = OwnedDeps {
+        storage: mock.storage,
+        api: HexApi(mock.api),
+        querier: mock.querier,
+        custom_query_type: PhantomData,
+    };
+    deps.querier
+        .with_balance(MOCK_CONTRACT_ADDR, &[coin(1_000, "uluna")]);
+    (deps, SigningKey::from_bytes(&[7u8; 32]).unwrap())
+}
+
+fn signer_addr(key: &SigningKey) -> String {
+    let pubkey = key.verifying_key().to_bytes();
+    HexApi(MockApi::default())
+        .addr_humanize(
+            &Ripemd160::digest(&Sha256::digest(pubkey)[..])
+                .to_vec()
+                .into(),
+        )
+        .unwrap()
+        .to_string()
+}
+
+fn swap_intent(key: &SigningKey, nonce: u64) -> SwapIntent {
+    SwapIntent {
+        signer: signer_addr(key),
+        offer_asset: Asset {
+            info: token("token0000"),
+            amount: Uint128::new(100),
+        },
+        steps: vec![SwapOperation::AstroportSwap {
+            pool_address: PAIR.to_string(),
+            offer_asset_info: token("token0000"),
+            ask_asset_info: Some(native("uluna")),
+            belief_price: None,
+            max_spread: None,
+        }],
+        minimum_receive: Uint128::new(90),
+        receiver: None,
+        max_relayer_fee: Uint128::new(5),
+        nonce,
+        deadline: mock_env().block.time.seconds() + 60,
+    }
+}
+
+fn execute_signed_swap(
+    deps: &mut SignerDeps,
+    key: &SigningKey,
+    signed: &SwapIntent,
+    intent: SwapIntent,
+) -> Result<Response, ContractError> {
+    let payload = SignedSwapPayload {
+        chain_id: mock_env().block.chain_id,
+        contract: MOCK_CONTRACT_ADDR.to_string(),
+        intent: signed.clone(),
+    };
+    // The key signs the SHA-256 hash of the message
+    let signature: Signature = key.sign(to_binary(&payload).unwrap().as_slice());
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("relayer0000", &[]),
+        ExecuteMsg::ExecuteSignedSwap {
+            intent,
+            signature: Binary::from(signature.as_ref()),
+            pubkey: Binary::from(key.verifying_key().to_bytes().to_vec()),
+        },
+    )
+}
+
+#[test]
+fn signed_swap_pulls_the_allowance_of_the_signer() {
+    let (mut deps, key) = signer_setup();
+    let intent = swap_intent(&key, 0);
+
+    let res = execute_signed_swap(&mut deps, &key, &intent, intent.clone()).unwrap();
+    let (contract, msg) = wasm_execute::<Cw20ExecuteMsg>(&res.messages[0].msg);
+    assert_eq!(contract, "token0000");
+    assert_eq!(
+        msg,
+        Cw20ExecuteMsg::TransferFrom {
+            owner: signer_addr(&key),
+            recipient: MOCK_CONTRACT_ADDR.to_string(),
+            amount: Uint128::new(100),
+        }
+    );
+    let (_, msg) = wasm_execute::<ExecuteMsg>(&res.messages[2].msg);
+    assert_eq!(
+        msg,
+        ExecuteMsg::SettleSignedSwap {
+            target_asset_info: native("uluna"),
+            prev_balance: Uint128::new(1_000),
+            minimum_receive: Uint128::new(90),
+            max_relayer_fee: Uint128::new(5),
+            receiver: signer_addr(&key),
+            relayer: "relayer0000".to_string(),
+        }
+    );
+
+    // The nonce can't be replayed
+    let err = execute_signed_swap(&mut deps, &key, &intent, intent.clone()).unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidNonce {
+            expected: 1,
+            nonce: 0,
+        }
+    );
+}
+
+#[test]
+fn signed_swap_rejects_altered_and_expired_intents() {
+    let (mut deps, key) = signer_setup();
+    let intent = swap_intent(&key, 0);
+
+    let mut altered = intent.clone();
+    altered.minimum_receive = Uint128::zero();
+    let err = execute_signed_swap(&mut deps, &key, &intent, altered).unwrap_err();
+    assert_eq!(err, ContractError::InvalidSignature {});
+
+    // The key must belong to the signer
+    let mut other = intent.clone();
+    other.signer = signer_addr(&SigningKey::from_bytes(&[8u8; 32]).unwrap());
+    let err = execute_signed_swap(&mut deps, &key, &other, other.clone()).unwrap_err();
+    assert_eq!(err, ContractError::InvalidSignature {});
+
+    let mut expired = intent;
+    expired.deadline = mock_env().block.time.seconds() - 1;
+    let err = execute_signed_swap(&mut deps, &key, &expired, expired.clone()).unwrap_err();
+    assert_eq!(err, ContractError::IntentExpired {});
+}
+
+#[test]
+fn signed_swap_fee_keeps_the_minimum_receive() {
+    let mut deps = setup();
+    deps.querier
+        .with_balance(MOCK_CONTRACT_ADDR, &[coin(93, "uluna")]);
+    let settle = |minimum_receive: u128| ExecuteMsg::SettleSignedSwap {
+        target_asset_info: native("uluna"),
+        prev_balance: Uint128::zero(),
+        minimum_receive: Uint128::new(minimum_receive),
+        max_relayer_fee: Uint128::new(5),
+        receiver: "addr0000".to_string(),
+        relayer: "relayer0000".to_string(),
+    };
+
+    let err = execute_self(&mut deps, settle(94)).unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::AssertionMinimumReceive {
+            receive: Uint128::new(94),
+            amount: Uint128::new(93),
+        }
+    );
+
+    // The relayer gets 3 instead of 5 so that the receiver keeps 90
+    let res = execute_self(&mut deps, settle(90)).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![
+            SubMsg::new(BankMsg::Send {
+                to_address: "relayer0000".to_string(),
+                amount: vec![coin(3, "uluna")],
+            }),
+            SubMsg::new(BankMsg::Send {
+                to_address: "addr0000".to_string(),
+                amount: vec![coin(90, "uluna")],
+            }),
+        ]
+    );
+}
+
+const MAKER: &str = "maker0000";
+
+/// Whitelists the maker key and funds the router with uusd to fill quotes with.
+fn rfq_setup(balance: u128) -> (MockDeps, SigningKey) {
+    let mut deps = setup();
+    let key = SigningKey::from_bytes(&[9u8; 32]).unwrap();
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(OWNER, &[]),
+        ExecuteMsg::SetRfqMaker {
+            maker: MAKER.to_string(),
+            pubkey: Binary::from(key.verifying_key().to_bytes().to_vec()),
+        },
+    )
+    .unwrap();
+    deps.querier
+        .with_balance(MOCK_CONTRACT_ADDR, &[coin(balance, "uusd")]);
+    (deps, key)
+}
+
+/// Returns a signed quote of 200 token0000 for 100 uusd made out to `taker`.
+fn rfq_step(key: &SigningKey, taker: &str) -> SwapOperation {
+    let quote = RfqQuote {
+        taker: Addr::unchecked(taker),
+        offer_asset_info: native("uusd"),
+        ask_asset_info: token("token0000"),
+        offer_amount: Uint128::new(100),
+        ask_amount: Uint128::new(200),
+        expiry: mock_env().block.time.seconds() + 60,
+        nonce: 1,
+    };
+    let payload = RfqQuotePayload {
+        chain_id: mock_env().block.chain_id,
+        contract: MOCK_CONTRACT_ADDR.to_string(),
+        maker: MAKER.to_string(),
+        quote: quote.clone(),
+    };
+    let signature: Signature = key.sign(to_binary(&payload).unwrap().as_slice());
+    SwapOperation::Rfq {
+        maker: MAKER.to_string(),
+        quote,
+        signature: Binary::from(signature.as_ref()),
+    }
+}
+
+fn rfq_swap(step: SwapOperation) -> ExecuteMsg {
+    match step {
+        SwapOperation::Rfq {
+            maker,
+            quote,
+            signature,
+        } => ExecuteMsg::RfqSwap {
+            maker,
+            quote,
+            signature,
+            offer_amount: None,
+            to: Some("addr0000".to_string()),
+        },
+        _ => unreachable!(),
+    }
+}
+
+fn rfq_transfer_amount(res: &Response) -> Uint128 {
+    match wasm_execute::<Cw20ExecuteMsg>(&res.messages[1].msg) {
+        (_, Cw20ExecuteMsg::TransferFrom { amount, .. }) => amount,
+        (_, msg) => panic!("unexpected message {:?}", msg),
+    }
+}
+
+#[test]
+fn rfq_swap_is_self_only() {
+    let (mut deps, key) = rfq_setup(100);
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[]),
+        rfq_swap(rfq_step(&key, "addr0000")),
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+}
+
+#[test]
+fn rfq_quote_is_only_routed_for_its_taker() {
+    let (mut deps, key) = rfq_setup(0);
+    let msg = ExecuteMsg::Unxswap {
+        steps: vec![rfq_step(&key, "addr0001")],
+        minimum_receive: None,
+        to: None,
+        target_asset_info: token("token0000"),
+        post_action: None,
+        callback: None,
+        recovery_addr: None,
+        ibc_forward: None,
+    };
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[coin(100, "uusd")]),
+        msg,
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::RfqTakerMismatch {
+            taker: "addr0000".to_string()
+        }
+    );
+}
+
+#[test]
+fn rfq_quote_is_filled_in_parts() {
+    let (mut deps, key) = rfq_setup(60);
+    let step = rfq_step(&key, "addr0000");
+
+    // The first fill only covers the 60 uusd the router holds
+    let res = execute_self(&mut deps, rfq_swap(step.clone())).unwrap();
+    assert_eq!(
+        res.messages[0],
+        SubMsg::new(BankMsg::Send {
+            to_address: MAKER.to_string(),
+            amount: vec![coin(60, "uusd")],
+        })
+    );
+    assert_eq!(rfq_transfer_amount(&res), Uint128::new(120));
+
+    // The 60 uusd of the second fill exceed the 40 uusd left on the quote and would be stranded
+    let err = execute_self(&mut deps, rfq_swap(step.clone())).unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::RfqFillExceedsQuote {
+            remaining: Uint128::new(40),
+        }
+    );
+
+    // The router's whole 40 uusd fill the rest, so no offer is left behind
+    deps.querier
+        .with_balance(MOCK_CONTRACT_ADDR, &[coin(40, "uusd")]);
+    let res = execute_self(&mut deps, rfq_swap(step.clone())).unwrap();
+    assert_eq!(
+        res.messages[0],
+        SubMsg::new(BankMsg::Send {
+            to_address: MAKER.to_string(),
+            amount: vec![coin(40, "uusd")],
+        })
+    );
+    assert_eq!(rfq_transfer_amount(&res), Uint128::new(80));
+
+    let err = execute_self(&mut deps, rfq_swap(step)).unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::RfqQuoteFilled {
+            maker: MAKER.to_string(),
+            nonce: 1,
+        }
+    );
+}
+
+fn swap_reveal(salt: &str) -> SwapReveal {
+    SwapReveal {
+        steps: vec![astroport_step(native("uusd"))],
+        minimum_receive: Some(Uint128::new(90)),
+        salt: Binary::from(salt.as_bytes()),
+    }
+}
+
+fn reveal_hash(reveal: &SwapReveal) -> Binary {
+    Binary::from(Sha256::digest(to_binary(reveal).unwrap().as_slice()).to_vec())
+}
+
+fn env_at(height: u64) -> Env {
+    let mut env = mock_env();
+    env.block.height = height;
+    env
+}
+
+/// Commits 100 uusd of addr0000 at the mock block height.
+fn commit_swap(deps: &mut MockDeps) {
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[coin(100, "uusd")]),
+        ExecuteMsg::CommitSwap {
+            hash: reveal_hash(&swap_reveal("salt")),
+        },
+    )
+    .unwrap();
+    deps.querier
+        .with_balance(MOCK_CONTRACT_ADDR, &[coin(100, "uusd")]);
+}
+
+fn reveal_swap(
+    deps: &mut MockDeps,
+    height: u64,
+    reveal: SwapReveal,
+) -> Result<Response, ContractError> {
+    execute(
+        deps.as_mut(),
+        env_at(height),
+        mock_info("addr0000", &[]),
+        ExecuteMsg::RevealSwap {
+            steps: reveal.steps,
+            minimum_receive: reveal.minimum_receive,
+            salt: reveal.salt,
+        },
+    )
+}
+
+#[test]
+fn reveal_swap_routes_the_commitment_in_a_later_block() {
+    let mut deps = setup();
+    commit_swap(&mut deps);
+    let height = mock_env().block.height;
+    assert_eq!(
+        ESCROWED.load(&deps.storage, "uusd".to_string()).unwrap(),
+        Uint128::new(100)
+    );
+
+    let err = reveal_swap(&mut deps, height, swap_reveal("salt")).unwrap_err();
+    assert_eq!(err, ContractError::RevealTooEarly {});
+    let err = reveal_swap(&mut deps, height + 1, swap_reveal("other")).unwrap_err();
+    assert_eq!(err, ContractError::CommitmentNotFound {});
+
+    let res = reveal_swap(&mut deps, height + 1, swap_reveal("salt")).unwrap();
+    let (_, msg) = wasm_execute::<ExecuteMsg>(&res.messages[0].msg);
+    assert!(matches!(
+        msg,
+        ExecuteMsg::AstroportSwap { offer_amount: Some(amount), to: Some(to), .. }
+            if amount == Uint128::new(100) && to == "addr0000"
+    ));
+    let (_, msg) = wasm_execute::<ExecuteMsg>(&res.messages[1].msg);
+    assert!(matches!(
+        msg,
+        ExecuteMsg::AssertMinimumReceive { minimum_receive, .. }
+            if minimum_receive == Uint128::new(90)
+    ));
+    assert_eq!(
+        ESCROWED
+            .may_load(&deps.storage, "uusd".to_string())
+            .unwrap(),
+        None
+    );
+
+    let err = reveal_swap(&mut deps, height + 2, swap_reveal("salt")).unwrap_err();
+    assert_eq!(err, ContractError::CommitmentNotFound {});
+}
+
+#[test]
+fn commitment_is_refunded_after_the_timeout() {
+    let mut deps = setup();
+    commit_swap(&mut deps);
+    let deadline = mock_env().block.height + 100;
+    let refund = |deps: &mut MockDeps, height: u64| {
+        execute(
+            deps.as_mut(),
+            env_at(height),
+            mock_info("addr0000", &[]),
+            ExecuteMsg::RefundCommit {
+                hash: reveal_hash(&swap_reveal("salt")),
+            },
+        )
+    };
+
+    let err = refund(&mut deps, deadline).unwrap_err();
+    assert_eq!(err, ContractError::CommitmentNotExpired { deadline });
+    let err = reveal_swap(&mut deps, deadline + 1, swap_reveal("salt")).unwrap_err();
+    assert_eq!(err, ContractError::CommitmentExpired { deadline });
+
+    let res = refund(&mut deps, deadline + 1).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(BankMsg::Send {
+            to_address: "addr0000".to_string(),
+            amount: vec![coin(100, "uusd")],
+        })]
+    );
+    let err = refund(&mut deps, deadline + 1).unwrap_err();
+    assert_eq!(err, ContractError::CommitmentNotFound {});
+}
+
+/// Submits an auction order to the pair in the round of the mock block height.
+fn submit_auction_order(
+    deps: &mut MockDeps,
+    sender: &str,
+    offer: Coin,
+    max_spread: Option<Decimal>,
+) -> Result<Response, ContractError> {
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(sender, &[offer]),
+        ExecuteMsg::SubmitAuctionOrder {
+            pool: PAIR.to_string(),
+            dex: "astroport".to_string(),
+            max_spread,
+        },
+    )
+}
+
+/// Sets the uusd and uluna reserves of the pair, which are its mid price.
+fn set_auction_reserves(deps: &mut MockDeps, uusd: u128, uluna: u128) {
+    deps.querier.with_pair(
+        PAIR,
+        &[
+            (native("uusd"), Uint128::new(uusd)),
+            (native("uluna"), Uint128::new(uluna)),
+        ],
+        "lptoken0000",
+    );
+}
+
+fn settle_auction(deps: &mut MockDeps) -> Result<Response, ContractError> {
+    let mut env = mock_env();
+    env.block.height = (env.block.height / 10 + 1) * 10;
+    execute(
+        deps.as_mut(),
+        env,
+        mock_info("keeper0000", &[]),
+        ExecuteMsg::SettleAuction {
+            pool: PAIR.to_string(),
+            round: mock_env().block.height / 10,
+        },
+    )
+}
+
+fn distribute_auction(
+    deps: &mut MockDeps,
+    prices: [u128; 2],
+    prev_balances: [u128; 2],
+) -> Result<Response, ContractError> {
+    execute_self(
+        deps,
+        ExecuteMsg::DistributeAuction {
+            pool: PAIR.to_string(),
+            round: mock_env().block.height / 10,
+            prices: prices.iter().map(|price| Uint128::new(*price)).collect(),
+            prev_balances: prev_balances
+                .iter()
+                .map(|balance| Uint128::new(*balance))
+                .collect(),
+        },
+    )
+}
+
+#[test]
+fn auction_orders_must_be_worth_the_minimum() {
+    let mut deps = setup();
+    set_auction_reserves(&mut deps, 1_000_000, 2_000_000);
+    let err = submit_auction_order(&mut deps, "addr0000", coin(999, "uusd"), None).unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::AuctionOrderTooSmall {
+            min_value: Uint128::new(1_000),
+        }
+    );
+    // 1_500 uluna are worth 750 uusd at the mid price
+    let err = submit_auction_order(&mut deps, "addr0000", coin(1_500, "uluna"), None).unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::AuctionOrderTooSmall {
+            min_value: Uint128::new(1_000),
+        }
+    );
+    submit_auction_order(&mut deps, "addr0000", coin(1_000, "uusd"), None).unwrap();
+}
+
+#[test]
+fn auction_clears_at_the_pool_mid_price() {
+    let mut deps = setup();
+    // The pool quotes 2 uluna per uusd
+    set_auction_reserves(&mut deps, 1_000_000, 2_000_000);
+    submit_auction_order(&mut deps, "addr0000", coin(10_000, "uusd"), None).unwrap();
+    submit_auction_order(&mut deps, "addr0001", coin(10_000, "uluna"), None).unwrap();
+    deps.querier.with_balance(
+        MOCK_CONTRACT_ADDR,
+        &[coin(10_000, "uusd"), coin(10_000, "uluna")],
+    );
+
+    let res = settle_auction(&mut deps).unwrap();
+    // 10_000 uluna match 5_000 uusd, the other 5_000 uusd are swapped through the pool
+    let (_, msg) = wasm_execute::<ExecuteMsg>(&res.messages[0].msg);
+    assert!(matches!(
+        msg,
+        ExecuteMsg::DexSwap { offer_amount: Some(amount), belief_price: None, .. }
+            if amount == Uint128::new(5_000)
+    ));
+    let (_, msg) = wasm_execute::<ExecuteMsg>(&res.messages[1].msg);
+    assert!(matches!(
+        msg,
+        ExecuteMsg::DistributeAuction { prices, .. }
+            if prices == vec![Uint128::new(1_000_000), Uint128::new(2_000_000)]
+    ));
+
+    // The net swap returned 9_950 uluna
+    deps.querier
+        .with_balance(MOCK_CONTRACT_ADDR, &[coin(19_950, "uluna")]);
+    let res = distribute_auction(&mut deps, [1_000_000, 2_000_000], [10_000, 10_000]).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![
+            SubMsg::new(BankMsg::Send {
+                to_address: "addr0000".to_string(),
+                amount: vec![coin(19_950, "uluna")],
+            }),
+            SubMsg::new(BankMsg::Send {
+                to_address: "addr0001".to_string(),
+                amount: vec![coin(5_000, "uusd")],
+            }),
+        ]
+    );
+}
+
+#[test]
+fn auction_refunds_orders_worth_nothing_at_settlement() {
+    let mut deps = setup();
+    submit_auction_order(&mut deps, "addr0000", coin(10_000, "uusd"), None).unwrap();
+    submit_auction_order(&mut deps, "addr0001", coin(1_000, "uluna"), None).unwrap();
+
+    // By settlement 1_000 uluna are worth less than 1 uusd
+    set_auction_reserves(&mut deps, 1_000, 10_000_000);
+    let res = settle_auction(&mut deps).unwrap();
+    assert_eq!(
+        res.messages[0],
+        SubMsg::new(BankMsg::Send {
+            to_address: "addr0001".to_string(),
+            amount: vec![coin(1_000, "uluna")],
+        })
+    );
+    let (_, msg) = wasm_execute::<ExecuteMsg>(&res.messages[1].msg);
+    assert!(matches!(
+        msg,
+        ExecuteMsg::DexSwap { offer_amount: Some(amount), .. }
+            if amount == Uint128::new(10_000)
+    ));
+    assert_eq!(
+        AUCTION_BATCHES
+            .load(
+                &deps.storage,
+                (&Addr::unchecked(PAIR), mock_env().block.height / 10)
+            )
+            .unwrap()
+            .totals,
+        vec![Uint128::new(10_000), Uint128::zero()]
+    );
+}
+
+#[test]
+fn auction_refunds_orders_that_reject_the_spread() {
+    let mut deps = setup();
+    submit_auction_order(
+        &mut deps,
+        "addr0000",
+        coin(100_000, "uusd"),
+        Some(Decimal::zero()),
+    )
+    .unwrap();
+    submit_auction_order(
+        &mut deps,
+        "addr0001",
+        coin(100_000, "uusd"),
+        Some(Decimal::percent(20)),
+    )
+    .unwrap();
+    submit_auction_order(&mut deps, "addr0002", coin(100_000, "uluna"), None).unwrap();
+
+    // The net swap of 100_000 uusd would fill the uusd orders about 4.5% below the mid price, so
+    // the order that accepts no spread is refunded and the rest matches without a swap
+    let res = settle_auction(&mut deps).unwrap();
+    assert_eq!(res.messages.len(), 2);
+    assert_eq!(
+        res.messages[0],
+        SubMsg::new(BankMsg::Send {
+            to_address: "addr0000".to_string(),
+            amount: vec![coin(100_000, "uusd")],
+        })
+    );
+    let (_, msg) = wasm_execute::<ExecuteMsg>(&res.messages[1].msg);
+    assert!(matches!(msg, ExecuteMsg::DistributeAuction { .. }));
+}
+
+fn hashlock(preimage: &str) -> Binary {
+    Binary::from(Sha256::digest(preimage.as_bytes()).to_vec())
+}
+
+fn env_after(seconds: u64) -> Env {
+    let mut env = mock_env();
+    env.block.time = env.block.time.plus_seconds(seconds);
+    env
+}
+
+/// Locks 100 uusd of `sender` for addr0001 behind the hash of "secret" for a minute.
+fn lock_swap(deps: &mut MockDeps, sender: &str, route: Option<Vec<SwapOperation>>) {
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(sender, &[coin(100, "uusd")]),
+        ExecuteMsg::LockSwap {
+            hashlock: hashlock("secret"),
+            timelock: Expiration::AtTime(mock_env().block.time.plus_seconds(60)),
+            recipient: "addr0001".to_string(),
+            asset: Asset {
+                info: native("uusd"),
+                amount: Uint128::new(100),
+            },
+            route,
+            minimum_receive: None,
+        },
+    )
+    .unwrap();
+}
+
+fn claim(deps: &mut MockDeps, env: Env, preimage: &str) -> Result<Response, ContractError> {
+    execute(
+        deps.as_mut(),
+        env,
+        mock_info("relayer0000", &[]),
+        ExecuteMsg::Claim {
+            sender: "addr0000".to_string(),
+            preimage: Binary::from(preimage.as_bytes()),
+        },
+    )
+}
+
+fn refund(deps: &mut MockDeps, env: Env, sender: &str) -> Result<Response, ContractError> {
+    execute(
+        deps.as_mut(),
+        env,
+        mock_info("relayer0000", &[]),
+        ExecuteMsg::Refund {
+            sender: sender.to_string(),
+            hashlock: hashlock("secret"),
+        },
+    )
+}
+
+#[test]
+fn hashed_timelock_is_claimed_with_the_preimage_before_the_timelock() {
+    let mut deps = setup();
+    lock_swap(&mut deps, "addr0000", None);
+
+    let err = claim(&mut deps, mock_env(), "guess").unwrap_err();
+    assert_eq!(err, ContractError::HashedTimelockNotFound {});
+    let err = claim(&mut deps, env_after(60), "secret").unwrap_err();
+    assert_eq!(err, ContractError::TimelockExpired {});
+
+    let res = claim(&mut deps, env_after(59), "secret").unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(BankMsg::Send {
+            to_address: "addr0001".to_string(),
+            amount: vec![coin(100, "uusd")],
+        })]
+    );
+    let err = claim(&mut deps, mock_env(), "secret").unwrap_err();
+    assert_eq!(err, ContractError::HashedTimelockNotFound {});
+}
+
+#[test]
+fn hashed_timelock_is_refunded_to_its_sender_after_the_timelock() {
+    let mut deps = setup();
+    // The same hashlock can't block or take over the lock of another sender
+    lock_swap(&mut deps, "addr0000", None);
+    lock_swap(&mut deps, "addr0002", None);
+
+    let err = refund(&mut deps, env_after(59), "addr0000").unwrap_err();
+    assert_eq!(err, ContractError::TimelockNotExpired {});
+
+    let res = refund(&mut deps, env_after(60), "addr0000").unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(BankMsg::Send {
+            to_address: "addr0000".to_string(),
+            amount: vec![coin(100, "uusd")],
+        })]
+    );
+    let err = refund(&mut deps, env_after(60), "addr0000").unwrap_err();
+    assert_eq!(err, ContractError::HashedTimelockNotFound {});
+
+    let lock: Option<HashedTimelockResponse> = from_binary(
+        &query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::HashedTimelock {
+                sender: "addr0002".to_string(),
+                hashlock: hashlock("secret"),
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(lock.unwrap().sender, Addr::unchecked("addr0002"));
+}
+
+#[test]
+fn hashed_timelock_route_failure_delivers_the_locked_asset() {
+    let mut deps = setup();
+    lock_swap(
+        &mut deps,
+        "addr0000",
+        Some(vec![astroport_step(native("uusd"))]),
+    );
+
+    let res = claim(&mut deps, mock_env(), "secret").unwrap();
+    assert_eq!(res.messages.len(), 1);
+    assert_eq!(res.messages[0].reply_on, ReplyOn::Error);
+    let (_, msg) = wasm_execute::<ExecuteMsg>(&res.messages[0].msg);
+    assert_eq!(msg, ExecuteMsg::ExecuteClaimRoute {});
+
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[]),
+        ExecuteMsg::ExecuteClaimRoute {},
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+
+    // The route fails and reverts, the reply delivers the uusd as is
+    let res = reply(
+        deps.as_mut(),
+        mock_env(),
+        Reply {
+            id: res.messages[0].id,
+            result: SubMsgResult::Err("swap failed".to_string()),
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(BankMsg::Send {
+            to_address: "addr0001".to_string(),
+            amount: vec![coin(100, "uusd")],
+        })]
+    );
+}
+
+#[test]
+fn hashed_timelock_route_runs_for_the_recipient() {
+    let mut deps = setup();
+    lock_swap(
+        &mut deps,
+        "addr0000",
+        Some(vec![astroport_step(native("uusd"))]),
+    );
+    claim(&mut deps, mock_env(), "secret").unwrap();
+
+    let res = execute_self(&mut deps, ExecuteMsg::ExecuteClaimRoute {}).unwrap();
+    let (_, msg) = wasm_execute::<ExecuteMsg>(&res.messages[0].msg);
+    assert!(matches!(
+        msg,
+        ExecuteMsg::AstroportSwap { offer_amount: Some(amount), to: Some(to), .. }
+            if amount == Uint128::new(100) && to == "addr0001"
+    ));
+    let err = execute_self(&mut deps, ExecuteMsg::ExecuteClaimRoute {}).unwrap_err();
+    assert!(matches!(err, ContractError::Std(StdError::NotFound { .. })));
+}
+
+fn recoveries(deps: &MockDeps, addr: &str) -> Vec<Asset> {
+    from_binary(
+        &query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::Recoveries {
+                addr: addr.to_string(),
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap()
+}
+
+#[test]
+fn ibc_hooks_swap_credits_the_recovery_address_on_failure() {
+    let mut deps = setup();
+    deps.querier
+        .with_balance(MOCK_CONTRACT_ADDR, &[coin(100, "uusd")]);
+    let mut msg = unxswap_msg(None, None);
+    if let ExecuteMsg::Unxswap { recovery_addr, .. } = &mut msg {
+        *recovery_addr = Some("addr0000".to_string());
+    }
+    // ibc-hooks executes the memo from an address derived from the channel and the sender
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("ibchooks0000", &[coin(100, "uusd")]),
+        msg,
+    )
+    .unwrap();
+    assert_eq!(res.messages.len(), 1);
+    assert_eq!(res.messages[0].reply_on, ReplyOn::Always);
+    let (_, msg) = wasm_execute::<ExecuteMsg>(&res.messages[0].msg);
+    assert!(matches!(
+        msg,
+        ExecuteMsg::Unxswap { recovery_addr: None, to: Some(to), .. } if to == "ibchooks0000"
+    ));
+
+    reply(
+        deps.as_mut(),
+        mock_env(),
+        Reply {
+            id: res.messages[0].id,
+            result: SubMsgResult::Err("swap failed".to_string()),
+        },
+    )
+    .unwrap();
+    let credit = Asset {
+        info: native("uusd"),
+        amount: Uint128::new(100),
+    };
+    assert_eq!(recoveries(&deps, "addr0000"), vec![credit]);
+
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[]),
+        ExecuteMsg::Recover {},
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(BankMsg::Send {
+            to_address: "addr0000".to_string(),
+            amount: vec![coin(100, "uusd")],
+        })]
+    );
+    assert_eq!(recoveries(&deps, "addr0000"), vec![]);
+}
+
+fn ibc_lifecycle_complete(deps: &mut MockDeps, sequence: u64, success: Option<bool>) -> Response {
+    let msg = match success {
+        Some(success) => IbcLifecycleComplete::IbcAck {
+            channel: "channel-0".to_string(),
+            sequence,
+            ack: String::new(),
+            success,
+        },
+        None => IbcLifecycleComplete::IbcTimeout {
+            channel: "channel-0".to_string(),
+            sequence,
+        },
+    };
+    sudo(
+        deps.as_mut(),
+        mock_env(),
+        SudoMsg::IbcLifecycleComplete(msg),
+    )
+    .unwrap()
+}
+
+#[test]
+fn ibc_lifecycle_complete_credits_failed_transfers() {
+    let mut deps = setup();
+    for sequence in 1..=3 {
+        IBC_TRANSFERS
+            .save(
+                deps.as_mut().storage,
+                ("channel-0", sequence),
+                &IbcTransfer {
+                    channel: "channel-0".to_string(),
+                    owner: Addr::unchecked("addr0000"),
+                    asset: Asset {
+                        info: native("uluna"),
+                        amount: Uint128::new(100),
+                    },
+                    recovery_addr: Addr::unchecked("addr0001"),
+                },
+            )
+            .unwrap();
+    }
+
+    let res = ibc_lifecycle_complete(&mut deps, 1, Some(true));
+    assert!(res.attributes.contains(&attr("status", "success")));
+    assert_eq!(recoveries(&deps, "addr0001"), vec![]);
+
+    let res = ibc_lifecycle_complete(&mut deps, 2, Some(false));
+    assert!(res.attributes.contains(&attr("status", "failed")));
+    let res = ibc_lifecycle_complete(&mut deps, 3, None);
+    assert!(res.attributes.contains(&attr("status", "failed")));
+    assert_eq!(
+        recoveries(&deps, "addr0001"),
+        vec![Asset {
+            info: native("uluna"),
+            amount: Uint128::new(200),
+        }]
+    );
+    assert_eq!(
+        ESCROWED.load(&deps.storage, "uluna".to_string()).unwrap(),
+        Uint128::new(200)
+    );
+
+    // A repeated callback doesn't credit the transfer twice
+    let res = ibc_lifecycle_complete(&mut deps, 3, None);
+    assert!(res.attributes.contains(&attr("status", "unknown")));
+    assert_eq!(recoveries(&deps, "addr0001")[0].amount, Uint128::new(200));
+}
+
+#[derive(Debug, PartialEq)]
+enum ProtoValue {
+    Varint(u64),
+    Bytes(Vec<u8>),
+}
+
+fn encode_proto_varint(buf: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buf.push((value as u8 & 0x7f) | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+fn decode_proto_varint(data: &mut &[u8]) -> u64 {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let (byte, rest) = data.split_first().expect("truncated varint");
+        *data = rest;
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            break;
+        }
+    }
+    value
+}
+
+/// Decodes the fields of a protobuf message in wire order.
+fn decode_proto_fields(mut data: &[u8]) -> Vec<(u64, ProtoValue)> {
+    let mut fields = vec![];
+    while !data.is_empty() {
+        let key = decode_proto_varint(&mut data);
+        let value = match key & 7 {
+            0 => ProtoValue::Varint(decode_proto_varint(&mut data)),
+            2 => {
+                let len = decode_proto_varint(&mut data) as usize;
+                let (bytes, rest) = data.split_at(len);
+                data = rest;
+                ProtoValue::Bytes(bytes.to_vec())
+            }
+            wire_type => panic!("unexpected wire type {}", wire_type),
+        };
+        fields.push((key >> 3, value));
+    }
+    fields
+}
+
+fn proto_string(value: &str) -> ProtoValue {
+    ProtoValue::Bytes(value.as_bytes().to_vec())
+}
+
+#[test]
+fn msg_transfer_encoding_round_trips() {
+    let memo = r#"{"ibc_callback":"cosmos2contract"}"#;
+    let msg = MsgTransfer {
+        source_port: "transfer".to_string(),
+        source_channel: "channel-0".to_string(),
+        token: coin(1_000_000, "uluna"),
+        sender: MOCK_CONTRACT_ADDR.to_string(),
+        receiver: "osmo1receiver".to_string(),
+        timeout_timestamp: 1_700_000_000_000_000_000,
+        memo: memo.to_string(),
+    };
+
+    let fields = decode_proto_fields(msg.encode().as_slice());
+    let token = match &fields[2] {
+        (3, ProtoValue::Bytes(token)) => decode_proto_fields(token),
+        field => panic!("unexpected token field {:?}", field),
+    };
+    assert_eq!(
+        token,
+        vec![(1, proto_string("uluna")), (2, proto_string("1000000"))]
+    );
+    assert_eq!(fields[0], (1, proto_string("transfer")));
+    assert_eq!(fields[1], (2, proto_string("channel-0")));
+    assert_eq!(fields[3], (4, proto_string(MOCK_CONTRACT_ADDR)));
+    assert_eq!(fields[4], (5, proto_string("osmo1receiver")));
+    // The timeout height (field 6) is left unset in favour of the timestamp
+    assert_eq!(
+        fields[5],
+        (7, ProtoValue::Varint(1_700_000_000_000_000_000))
+    );
+    assert_eq!(fields[6], (8, proto_string(memo)));
+    assert_eq!(fields.len(), 7);
+}
+
+#[test]
+fn transfer_sequence_round_trips() {
+    for sequence in [1, 127, 128, 300, u64::MAX] {
+        let mut data = vec![];
+        encode_proto_varint(&mut data, 1 << 3);
+        encode_proto_varint(&mut data, sequence);
+        assert_eq!(decode_transfer_sequence(&data).unwrap(), sequence);
+    }
+
+    // Unknown fields before the sequence are skipped
+    let mut data = vec![];
+    encode_proto_varint(&mut data, 2 << 3 | 2);
+    encode_proto_varint(&mut data, 3);
+    data.extend_from_slice(b"abc");
+    encode_proto_varint(&mut data, 3 << 3);
+    encode_proto_varint(&mut data, 5);
+    encode_proto_varint(&mut data, 1 << 3);
+    encode_proto_varint(&mut data, 42);
+    assert_eq!(decode_transfer_sequence(&data).unwrap(), 42);
+
+    // An empty response has no sequence and a length past the end is truncated
+    decode_transfer_sequence(&[]).unwrap_err();
+    decode_transfer_sequence(&[2 << 3 | 2, 5, b'a']).unwrap_err();
+}
+
+#[test]
+fn recoverable_ibc_forward_is_owned_by_the_swap_sender() {
+    let mut deps = setup();
+    deps.querier
+        .with_balance(MOCK_CONTRACT_ADDR, &[coin(100, "uusd")]);
+    let ibc_forward = IbcForward {
+        channel: "channel-0".to_string(),
+        receiver: "osmo1receiver".to_string(),
+        timeout_seconds: 600,
+        memo: None,
+    };
+    let mut msg = unxswap_msg(None, None);
+    if let ExecuteMsg::Unxswap {
+        recovery_addr,
+        ibc_forward: forward,
+        ..
+    } = &mut msg
+    {
+        *recovery_addr = Some("addr0001".to_string());
+        *forward = Some(ibc_forward);
+    }
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[coin(100, "uusd")]),
+        msg,
+    )
+    .unwrap();
+
+    // The inner route is executed by the contract on behalf of addr0000
+    let (_, inner) = wasm_execute::<ExecuteMsg>(&res.messages[0].msg);
+    let res = execute_self(&mut deps, inner).unwrap();
+    let (_, msg) = wasm_execute::<ExecuteMsg>(&res.messages.last().unwrap().msg);
+    assert!(matches!(
+        msg,
+        ExecuteMsg::ExecuteIbcForward { owner, recovery_addr, .. }
+            if owner == "addr0000" && recovery_addr == "addr0001"
+    ));
+}
+
+fn dex_swap(dex: &str, offer_amount: u128) -> ExecuteMsg {
+    ExecuteMsg::DexSwap {
+        dex: dex.to_string(),
+        pool_address: PAIR.to_string(),
+        offer_asset_info: native("uusd"),
+        offer_amount: Some(Uint128::new(offer_amount)),
+        ask_asset_info: None,
+        belief_price: None,
+        max_spread: Some(Decimal::percent(1)),
+        to: None,
+    }
+}
+
+#[test]
+fn dex_swap_uses_the_registered_adapter() {
+    let mut deps = setup();
+    deps.querier
+        .with_balance(MOCK_CONTRACT_ADDR, &[coin(1_000, "uusd")]);
+
+    let err = execute_self(&mut deps, dex_swap("unknown", 100)).unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::UnknownDex {
+            dex: "unknown".to_string()
+        }
+    );
+
+    let res = execute_self(&mut deps, dex_swap("astroport", 100)).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(WasmMsg::Execute {
+            contract_addr: PAIR.to_string(),
+            funds: vec![coin(100, "uusd")],
+            msg: to_binary(&AstroportMsg::Swap {
+                offer_asset: Asset {
+                    info: native("uusd"),
+                    amount: Uint128::new(100),
+                },
+                ask_asset_info: None,
+                belief_price: None,
+                max_spread: Some(Decimal::percent(1)),
+                to: None,
+            })
+            .unwrap(),
+        })]
+    );
+}
+
+#[test]
+fn adapters_send_cw20_offers_with_the_swap_hook() {
+    let offer_asset = Asset {
+        info: token("token0000"),
+        amount: Uint128::new(100),
+    };
+    let to = Some("addr0000".to_string());
+    // Only the Astroport hook names the ask asset
+    let hooks = [
+        (
+            "sparrowswap",
+            to_binary(&SparrowSwapCw20HookMsg::Swap {
+                belief_price: None,
+                max_spread: None,
+                to: to.clone(),
+            }),
+        ),
+        (
+            "astroport",
+            to_binary(&AstroportCw20HookMsg::Swap {
+                ask_asset_info: Some(native("uluna")),
+                belief_price: None,
+                max_spread: None,
+                to: to.clone(),
+            }),
+        ),
+    ];
+    for (dex, hook) in hooks {
+        let msg = dex_adapter(dex)
+            .unwrap()
+            .swap_msg(
+                PAIR,
+                &offer_asset,
+                Some(native("uluna")),
+                None,
+                None,
+                to.clone(),
+            )
+            .unwrap();
+        assert_eq!(
+            msg,
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: "token0000".to_string(),
+                funds: vec![],
+                msg: to_binary(&Cw20ExecuteMsg::Send {
+                    contract: PAIR.to_string(),
+                    amount: Uint128::new(100),
+                    msg: hook.unwrap(),
+                })
+                .unwrap(),
+            })
+        );
+    }
+}
+
+#[test]
+fn adapters_withdraw_liquidity_with_the_pair_hook() {
+    let hooks = [
+        (
+            "sparrowswap",
+            to_binary(&SparrowSwapCw20HookMsg::WithdrawLiquidity {}),
+        ),
+        (
+            "astroport",
+            to_binary(&AstroportCw20HookMsg::WithdrawLiquidity { assets: vec![] }),
+        ),
+    ];
+    for (dex, hook) in hooks {
+        let msg = dex_adapter(dex)
+            .unwrap()
+            .withdraw_liquidity_msg(PAIR, "lptoken0000", Uint128::new(100))
+            .unwrap();
+        assert_eq!(
+            msg,
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: "lptoken0000".to_string(),
+                funds: vec![],
+                msg: to_binary(&Cw20ExecuteMsg::Send {
+                    contract: PAIR.to_string(),
+                    amount: Uint128::new(100),
+                    msg: hook.unwrap(),
+                })
+                .unwrap(),
+            })
+        );
+    }
+}
+
+#[test]
+fn adapters_query_the_pair() {
+    let deps = setup();
+    let querier = QuerierWrapper::new(&deps.querier);
+    let offer_asset = Asset {
+        info: native("uusd"),
+        amount: Uint128::new(1_000),
+    };
+    for dex in ["sparrowswap", "astroport"] {
+        let adapter = dex_adapter(dex).unwrap();
+        // 1_000 uusd against 1_000_000 of each reserve without fees
+        let simulation = adapter
+            .simulate(&querier, PAIR, &offer_asset, None)
+            .unwrap();
+        assert_eq!(simulation.return_amount, Uint128::new(999));
+        let reverse_simulation = adapter
+            .reverse_simulate(
+                &querier,
+                PAIR,
+                None,
+                &Asset {
+                    info: native("uluna"),
+                    amount: Uint128::new(999),
+                },
+            )
+            .unwrap();
+        assert_eq!(reverse_simulation.offer_amount, Uint128::new(1_000));
+        assert_eq!(
+            adapter.pair_info(&querier, PAIR).unwrap().asset_infos,
+            vec![native("uusd"), native("uluna")]
+        );
+        assert_eq!(
+            adapter
+                .ask_asset_info(&querier, PAIR, &native("uusd"))
+                .unwrap(),
+            native("uluna")
+        );
+    }
+}
+
+#[test]
+fn ibc_callback_is_spliced_into_the_memo() {
+    let callback = r#"{"ibc_callback":"cosmos2contract"}"#;
+    assert_eq!(
+        ibc_callback_memo(None, "cosmos2contract").unwrap(),
+        callback
+    );
+    assert_eq!(
+        ibc_callback_memo(Some(" { } "), "cosmos2contract").unwrap(),
+        callback
+    );
+    assert_eq!(
+        ibc_callback_memo(
+            Some(r#"{"wasm":{"contract":"osmo1vault","msg":{"deposit":{"ratio":0.5}}}}"#),
+            "cosmos2contract"
+        )
+        .unwrap(),
+        r#"{"wasm":{"contract":"osmo1vault","msg":{"deposit":{"ratio":0.5}}},"ibc_callback":"cosmos2contract"}"#
+    );
+    // a null entry is overridden by the callback that follows it
+    assert_eq!(
+        ibc_callback_memo(Some(r#"{"ibc_callback":null}"#), "cosmos2contract").unwrap(),
+        r#"{"ibc_callback":null,"ibc_callback":"cosmos2contract"}"#
+    );
+
+    for memo in [
+        "[]",
+        "\"memo\"",
+        "{} {}",
+        r#"{"ibc_callback":"osmo1other"}"#,
+    ] {
+        ibc_callback_memo(Some(memo), "cosmos2contract").unwrap_err();
+    }
+}